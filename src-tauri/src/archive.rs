@@ -0,0 +1,220 @@
+use aes_gcm::{
+    aead::{Aead, KeyInit, Payload},
+    Aes256Gcm, Nonce,
+};
+use argon2::Argon2;
+use rand_core::{OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    io::{Cursor, Read, Write},
+    path::Path,
+};
+use zip::{write::FileOptions, CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::{backup, migrations, BackupSettings};
+
+const MAGIC: &[u8; 8] = b"BPOSARCH";
+const ARCHIVE_VERSION: u8 = 1;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const HEADER_LEN: usize = MAGIC.len() + 1 + SALT_LEN + NONCE_LEN;
+const EXTENSION: &str = "bposarchiv";
+
+const MANIFEST_ENTRY: &str = "manifest.json";
+const DATABASE_ENTRY: &str = "database.db";
+const SETTINGS_ENTRY: &str = "settings.json";
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Manifest {
+    archive_version: u8,
+    schema_version: i64,
+    created_at: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchiveRecord {
+    pub path: String,
+    pub archive_version: u8,
+    pub schema_version: i64,
+    pub created_at: String,
+    pub size_bytes: u64,
+}
+
+pub struct OpenedArchive {
+    pub settings: String,
+    database: Vec<u8>,
+}
+
+pub fn export(
+    db_path: &Path,
+    settings_json: &str,
+    target_dir: &Path,
+    password: &str,
+) -> Result<ArchiveRecord, String> {
+    if password.chars().count() < 8 {
+        return Err("Passwort muss mindestens 8 Zeichen lang sein".into());
+    }
+    if !target_dir.is_dir() {
+        return Err(format!(
+            "Zielordner {} existiert nicht",
+            target_dir.display()
+        ));
+    }
+
+    let stem = backup::db_stem(db_path);
+    let snapshot_path = db_path.with_file_name(format!(".{}-archiv.tmp", stem));
+    backup::snapshot(db_path, &snapshot_path)?;
+    let database = fs::read(&snapshot_path).map_err(|e| e.to_string());
+    let _ = fs::remove_file(&snapshot_path);
+    let database = database?;
+
+    let schema_version = {
+        let conn = rusqlite::Connection::open(db_path).map_err(|e| e.to_string())?;
+        conn.pragma_query_value(None, "user_version", |row| row.get(0))
+            .map_err(|e| e.to_string())?
+    };
+    let manifest = Manifest {
+        archive_version: ARCHIVE_VERSION,
+        schema_version,
+        created_at: backup::local_time(db_path, "%Y-%m-%d %H:%M:%S")?,
+    };
+
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+    let manifest_json = serde_json::to_vec_pretty(&manifest).map_err(|e| e.to_string())?;
+    for (name, content) in [
+        (MANIFEST_ENTRY, manifest_json.as_slice()),
+        (DATABASE_ENTRY, database.as_slice()),
+        (SETTINGS_ENTRY, settings_json.as_bytes()),
+    ] {
+        zip.start_file(name, options).map_err(|e| e.to_string())?;
+        zip.write_all(content).map_err(|e| e.to_string())?;
+    }
+    let plain = zip.finish().map_err(|e| e.to_string())?.into_inner();
+
+    let mut header = Vec::with_capacity(HEADER_LEN);
+    header.extend_from_slice(MAGIC);
+    header.push(ARCHIVE_VERSION);
+    let mut salt = [0u8; SALT_LEN];
+    let mut nonce = [0u8; NONCE_LEN];
+    OsRng.fill_bytes(&mut salt);
+    OsRng.fill_bytes(&mut nonce);
+    header.extend_from_slice(&salt);
+    header.extend_from_slice(&nonce);
+
+    let cipher = cipher(password, &salt)?;
+    let encrypted = cipher
+        .encrypt(
+            Nonce::from_slice(&nonce),
+            Payload {
+                msg: &plain,
+                aad: &header,
+            },
+        )
+        .map_err(|_| "Archiv konnte nicht verschlüsselt werden".to_string())?;
+
+    let stamp = backup::local_time(db_path, "%Y%m%d-%H%M%S")?;
+    let path = target_dir.join(format!("{}-archiv-{}.{}", stem, stamp, EXTENSION));
+    let partial = path.with_extension(format!("{}.part", EXTENSION));
+    let written = fs::File::create(&partial).and_then(|mut file| {
+        file.write_all(&header)?;
+        file.write_all(&encrypted)?;
+        file.sync_all()
+    });
+    if let Err(err) = written {
+        let _ = fs::remove_file(&partial);
+        return Err(format!("Archiv konnte nicht geschrieben werden: {}", err));
+    }
+    fs::rename(&partial, &path).map_err(|e| e.to_string())?;
+
+    Ok(ArchiveRecord {
+        path: path.to_string_lossy().to_string(),
+        archive_version: manifest.archive_version,
+        schema_version: manifest.schema_version,
+        created_at: manifest.created_at,
+        size_bytes: fs::metadata(&path).map(|m| m.len()).unwrap_or(0),
+    })
+}
+
+pub fn open(archive_path: &Path, password: &str) -> Result<OpenedArchive, String> {
+    let raw =
+        fs::read(archive_path).map_err(|e| format!("Archiv konnte nicht gelesen werden: {}", e))?;
+    if raw.len() < HEADER_LEN || &raw[..MAGIC.len()] != MAGIC {
+        return Err("Datei ist kein Sicherungsarchiv".into());
+    }
+    let version = raw[MAGIC.len()];
+    if version != ARCHIVE_VERSION {
+        return Err(format!(
+            "Archivversion {} wird nicht unterstützt (erwartet {})",
+            version, ARCHIVE_VERSION
+        ));
+    }
+    let (header, encrypted) = raw.split_at(HEADER_LEN);
+    let salt = &header[MAGIC.len() + 1..MAGIC.len() + 1 + SALT_LEN];
+    let nonce = &header[MAGIC.len() + 1 + SALT_LEN..];
+
+    let plain = cipher(password, salt)?
+        .decrypt(
+            Nonce::from_slice(nonce),
+            Payload {
+                msg: encrypted,
+                aad: header,
+            },
+        )
+        .map_err(|_| "Passwort ist falsch oder das Archiv ist beschädigt".to_string())?;
+
+    let mut zip = ZipArchive::new(Cursor::new(plain)).map_err(|e| e.to_string())?;
+    let manifest: Manifest = serde_json::from_slice(&read_entry(&mut zip, MANIFEST_ENTRY)?)
+        .map_err(|e| format!("Archivbeschreibung ist ungültig: {}", e))?;
+    if manifest.archive_version != version {
+        return Err("Archivversion im Inhalt passt nicht zum Archivkopf".into());
+    }
+    if manifest.schema_version > migrations::latest_version() {
+        return Err(format!(
+            "Archiv enthält Datenbankversion {}, dieses Programm kennt nur Version {}",
+            manifest.schema_version,
+            migrations::latest_version()
+        ));
+    }
+    let settings = String::from_utf8(read_entry(&mut zip, SETTINGS_ENTRY)?)
+        .map_err(|e| format!("Einstellungen im Archiv sind ungültig: {}", e))?;
+    let database = read_entry(&mut zip, DATABASE_ENTRY)?;
+
+    Ok(OpenedArchive { settings, database })
+}
+
+pub fn restore_database(
+    db_path: &Path,
+    backup_settings: &BackupSettings,
+    archive: &OpenedArchive,
+) -> Result<backup::BackupRecord, String> {
+    let extracted = db_path.with_file_name(format!(
+        ".{}-wiederherstellung.tmp",
+        backup::db_stem(db_path)
+    ));
+    fs::write(&extracted, &archive.database).map_err(|e| e.to_string())?;
+    let dir = backup::backup_dir(backup_settings, db_path);
+    let result = backup::replace_database(db_path, &dir, &extracted, "aus dem Archiv");
+    let _ = fs::remove_file(&extracted);
+    result
+}
+
+fn cipher(password: &str, salt: &[u8]) -> Result<Aes256Gcm, String> {
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(password.as_bytes(), salt, &mut key)
+        .map_err(|e| e.to_string())?;
+    Aes256Gcm::new_from_slice(&key).map_err(|e| e.to_string())
+}
+
+fn read_entry(zip: &mut ZipArchive<Cursor<Vec<u8>>>, name: &str) -> Result<Vec<u8>, String> {
+    let mut entry = zip
+        .by_name(name)
+        .map_err(|_| format!("Archiv ist unvollständig: {} fehlt", name))?;
+    let mut content = Vec::new();
+    entry.read_to_end(&mut content).map_err(|e| e.to_string())?;
+    Ok(content)
+}
@@ -0,0 +1,357 @@
+use rusqlite::{
+    backup::{Backup, StepResult},
+    Connection,
+};
+use serde::Serialize;
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use crate::{calendar, integrity_check, migrations, BackupSettings};
+
+pub const KIND_SCHEDULED: &str = "auto";
+pub const KIND_EXIT: &str = "beenden";
+pub const KIND_MANUAL: &str = "manuell";
+const KIND_BEFORE_RESTORE: &str = "vor-wiederherstellung";
+
+const ROTATING_KINDS: [&str; 2] = [KIND_SCHEDULED, KIND_EXIT];
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupRecord {
+    pub file_name: String,
+    pub path: String,
+    pub kind: String,
+    pub created_at: String,
+    pub size_bytes: u64,
+    #[serde(skip)]
+    stamp: String,
+}
+
+pub fn backup_dir(settings: &BackupSettings, db_path: &Path) -> PathBuf {
+    if !settings.directory.trim().is_empty() {
+        return PathBuf::from(settings.directory.trim());
+    }
+    db_path
+        .parent()
+        .map(|parent| parent.join("backups"))
+        .unwrap_or_else(|| PathBuf::from("backups"))
+}
+
+pub fn create(db_path: &Path, dir: &Path, kind: &str) -> Result<BackupRecord, String> {
+    fs::create_dir_all(dir)
+        .map_err(|e| format!("Sicherungsordner konnte nicht angelegt werden: {}", e))?;
+    let stamp = local_time(db_path, "%Y%m%d-%H%M%S")?;
+    let file_name = format!("{}-{}-{}.db", db_stem(db_path), kind, stamp);
+    let path = dir.join(&file_name);
+    let partial = dir.join(format!("{}.part", file_name));
+
+    snapshot(db_path, &partial).map_err(|e| format!("Sicherung fehlgeschlagen: {}", e))?;
+    fs::rename(&partial, &path).map_err(|e| e.to_string())?;
+
+    record(db_path, &path).ok_or_else(|| "Sicherung konnte nicht gelesen werden".to_string())
+}
+
+pub fn list(db_path: &Path, dir: &Path) -> Result<Vec<BackupRecord>, String> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut backups = Vec::new();
+    for entry in fs::read_dir(dir).map_err(|e| e.to_string())? {
+        let entry = entry.map_err(|e| e.to_string())?;
+        if let Some(backup) = record(db_path, &entry.path()) {
+            backups.push(backup);
+        }
+    }
+    backups.sort_by(|a, b| b.stamp.cmp(&a.stamp));
+    Ok(backups)
+}
+
+pub fn is_due(db_path: &Path, dir: &Path, interval_hours: u32) -> Result<bool, String> {
+    let interval = Duration::from_secs(u64::from(interval_hours.max(1)) * 3_600);
+    let newest = list(db_path, dir)?
+        .into_iter()
+        .filter(|backup| ROTATING_KINDS.contains(&backup.kind.as_str()))
+        .filter_map(|backup| fs::metadata(&backup.path).and_then(|m| m.modified()).ok())
+        .max();
+    Ok(match newest {
+        Some(modified) => SystemTime::now()
+            .duration_since(modified)
+            .map(|age| age >= interval)
+            .unwrap_or(false),
+        None => true,
+    })
+}
+
+pub fn prune(db_path: &Path, dir: &Path, settings: &BackupSettings) -> Result<(), String> {
+    let rotating: Vec<_> = list(db_path, dir)?
+        .into_iter()
+        .filter(|backup| ROTATING_KINDS.contains(&backup.kind.as_str()))
+        .collect();
+
+    let mut keep = HashSet::new();
+    let mut days = Vec::new();
+    let mut weeks = Vec::new();
+    for (index, backup) in rotating.iter().enumerate() {
+        let day = &backup.stamp[..8];
+        let week = week_index(day);
+        if index == 0 {
+            keep.insert(backup.file_name.clone());
+        }
+        if !days.contains(&day) && days.len() < settings.keep_daily as usize {
+            days.push(day);
+            keep.insert(backup.file_name.clone());
+        }
+        if !weeks.contains(&week) && weeks.len() < settings.keep_weekly as usize {
+            weeks.push(week);
+            keep.insert(backup.file_name.clone());
+        }
+    }
+
+    for backup in rotating {
+        if !keep.contains(&backup.file_name) {
+            fs::remove_file(&backup.path).map_err(|e| {
+                format!(
+                    "Alte Sicherung {} konnte nicht gelöscht werden: {}",
+                    backup.file_name, e
+                )
+            })?;
+        }
+    }
+    Ok(())
+}
+
+pub fn snapshot(db_path: &Path, target: &Path) -> Result<(), String> {
+    let source = Connection::open(db_path).map_err(|e| e.to_string())?;
+    let result = copy_database(&source, target).and_then(|_| {
+        let copy = Connection::open(target).map_err(|e| e.to_string())?;
+        integrity_check(&copy).map_err(|e| format!("Integritätsprüfung fehlgeschlagen: {}", e))
+    });
+    if result.is_err() {
+        let _ = fs::remove_file(target);
+    }
+    result
+}
+
+pub fn restore(db_path: &Path, dir: &Path, file_name: &str) -> Result<BackupRecord, String> {
+    let backup = list(db_path, dir)?
+        .into_iter()
+        .find(|backup| backup.file_name == file_name)
+        .ok_or_else(|| format!("Sicherung {} nicht gefunden", file_name))?;
+    replace_database(db_path, dir, Path::new(&backup.path), file_name)
+}
+
+pub fn replace_database(
+    db_path: &Path,
+    dir: &Path,
+    source_path: &Path,
+    label: &str,
+) -> Result<BackupRecord, String> {
+    let source = Connection::open(source_path).map_err(|e| e.to_string())?;
+    integrity_check(&source).map_err(|e| format!("Sicherung {} ist beschädigt: {}", label, e))?;
+    let version: i64 = source
+        .pragma_query_value(None, "user_version", |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    if version > migrations::latest_version() {
+        return Err(format!(
+            "Sicherung {} stammt von einer neueren Programmversion",
+            label
+        ));
+    }
+
+    // The backup is migrated and checked in a staging file first, so a failed
+    // migration never touches the live database.
+    let staged = db_path.with_file_name(format!(".{}-wiederherstellung-neu.tmp", db_stem(db_path)));
+    let _ = fs::remove_file(&staged);
+    let result = stage(&source, &staged, db_path, dir, label).and_then(|staged_conn| {
+        let safety = create(db_path, dir, KIND_BEFORE_RESTORE)?;
+        let mut target = Connection::open(db_path).map_err(|e| e.to_string())?;
+        // A single step copies all pages in one write transaction on the target.
+        let copied = Backup::new(&staged_conn, &mut target)
+            .and_then(|copy| copy.step(-1))
+            .map_err(|e| e.to_string())
+            .and_then(|step| match step {
+                StepResult::Done => Ok(()),
+                _ => Err("Datenbank ist gerade in Benutzung".to_string()),
+            });
+        copied.map_err(|e| {
+            format!(
+                "Wiederherstellung fehlgeschlagen, Sicherung des vorherigen Stands: {}: {}",
+                safety.file_name, e
+            )
+        })?;
+        Ok(safety)
+    });
+    let _ = fs::remove_file(&staged);
+    result
+}
+
+fn stage(
+    source: &Connection,
+    staged: &Path,
+    db_path: &Path,
+    dir: &Path,
+    label: &str,
+) -> Result<Connection, String> {
+    copy_database(source, staged)
+        .map_err(|e| format!("Sicherung {} konnte nicht kopiert werden: {}", label, e))?;
+    let mut conn = Connection::open(staged).map_err(|e| e.to_string())?;
+    migrations::run(&mut conn, db_path, dir)?;
+    integrity_check(&conn)
+        .map_err(|e| format!("Wiederhergestellte Datenbank ist fehlerhaft: {}", e))?;
+    Ok(conn)
+}
+
+fn copy_database(source: &Connection, target: &Path) -> Result<(), String> {
+    let mut destination = Connection::open(target).map_err(|e| e.to_string())?;
+    let backup = Backup::new(source, &mut destination).map_err(|e| e.to_string())?;
+    backup
+        .run_to_completion(256, Duration::from_millis(25), None)
+        .map_err(|e| e.to_string())
+}
+
+fn record(db_path: &Path, path: &Path) -> Option<BackupRecord> {
+    let file_name = path.file_name()?.to_str()?.to_string();
+    let rest = file_name
+        .strip_prefix(&format!("{}-", db_stem(db_path)))?
+        .strip_suffix(".db")?;
+    if rest.len() < 17 || !rest.is_char_boundary(rest.len() - 15) {
+        return None;
+    }
+    let (kind, stamp) = rest.split_at(rest.len() - 15);
+    let kind = kind.strip_suffix('-')?;
+    let valid_stamp = stamp
+        .char_indices()
+        .all(|(i, c)| if i == 8 { c == '-' } else { c.is_ascii_digit() });
+    if kind.is_empty() || !valid_stamp {
+        return None;
+    }
+
+    Some(BackupRecord {
+        path: path.to_string_lossy().to_string(),
+        kind: kind.to_string(),
+        created_at: format!(
+            "{}-{}-{} {}:{}:{}",
+            &stamp[0..4],
+            &stamp[4..6],
+            &stamp[6..8],
+            &stamp[9..11],
+            &stamp[11..13],
+            &stamp[13..15]
+        ),
+        size_bytes: fs::metadata(path).map(|m| m.len()).unwrap_or(0),
+        stamp: stamp.to_string(),
+        file_name,
+    })
+}
+
+pub fn local_time(db_path: &Path, format: &str) -> Result<String, String> {
+    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+    conn.query_row("SELECT strftime(?, 'now', 'localtime')", [format], |row| {
+        row.get(0)
+    })
+    .map_err(|e| e.to_string())
+}
+
+pub fn db_stem(db_path: &Path) -> &str {
+    db_path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("datenbank")
+}
+
+fn week_index(day: &str) -> i64 {
+    let year: i64 = day[0..4].parse().unwrap_or(1970);
+    let month: i64 = day[4..6].parse().unwrap_or(1);
+    let day: i64 = day[6..8].parse().unwrap_or(1);
+
+    (calendar::days_from_civil(year, month, day) + 3).div_euclid(7)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn database(name: &str) -> (PathBuf, PathBuf) {
+        let root = std::env::temp_dir().join(format!("backup-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        let db_path = root.join("boulderado.db");
+        let mut conn = Connection::open(&db_path).unwrap();
+        conn.execute_batch("PRAGMA journal_mode = WAL;").unwrap();
+        migrations::run(&mut conn, &db_path, &root.join("backups")).unwrap();
+        add_member(&db_path, "Erika");
+        (db_path, root.join("backups"))
+    }
+
+    fn add_member(db_path: &Path, first_name: &str) {
+        Connection::open(db_path)
+            .unwrap()
+            .execute(
+                "INSERT INTO members (first_name, last_name) VALUES (?, 'Test')",
+                [first_name],
+            )
+            .unwrap();
+    }
+
+    fn members(db_path: &Path) -> Vec<String> {
+        Connection::open(db_path)
+            .unwrap()
+            .prepare("SELECT first_name FROM members ORDER BY id")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn restores_backup_and_keeps_safety_copy() {
+        let (db_path, dir) = database("roundtrip");
+        let backup = create(&db_path, &dir, KIND_MANUAL).unwrap();
+        add_member(&db_path, "Max");
+
+        let safety = restore(&db_path, &dir, &backup.file_name).unwrap();
+        assert_eq!(safety.kind, KIND_BEFORE_RESTORE);
+        assert_eq!(members(&db_path), vec!["Erika"]);
+        assert_eq!(members(Path::new(&safety.path)), vec!["Erika", "Max"]);
+        let conn = Connection::open(&db_path).unwrap();
+        integrity_check(&conn).unwrap();
+        let version: i64 = conn
+            .pragma_query_value(None, "user_version", |row| row.get(0))
+            .unwrap();
+        assert_eq!(version, migrations::latest_version());
+
+        let _ = fs::remove_dir_all(dir.parent().unwrap());
+    }
+
+    #[test]
+    fn failed_migration_leaves_live_database_untouched() {
+        let (db_path, dir) = database("failed");
+        let backup = create(&db_path, &dir, KIND_MANUAL).unwrap();
+        // Pretend the last migration is still missing; re-running it fails.
+        Connection::open(&backup.path)
+            .unwrap()
+            .pragma_update(None, "user_version", migrations::latest_version() - 1)
+            .unwrap();
+        add_member(&db_path, "Max");
+
+        let err = restore(&db_path, &dir, &backup.file_name).err().unwrap();
+        assert!(err.contains("Datenbankmigration"), "{}", err);
+        assert_eq!(members(&db_path), vec!["Erika", "Max"]);
+        let restore_safety_copies = list(&db_path, &dir)
+            .unwrap()
+            .into_iter()
+            .filter(|backup| backup.kind == KIND_BEFORE_RESTORE)
+            .count();
+        assert_eq!(restore_safety_copies, 0);
+        assert!(!db_path
+            .with_file_name(".boulderado-wiederherstellung-neu.tmp")
+            .exists());
+
+        let _ = fs::remove_dir_all(dir.parent().unwrap());
+    }
+}
@@ -0,0 +1,62 @@
+/// Days since 1970-01-01 for a Gregorian date.
+pub fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// Inverse of `days_from_civil`: year, month and day.
+pub fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// Adds whole months to an ISO date, clamping the day to the end of the target
+/// month (2024-01-31 + 1 month = 2024-02-29).
+pub fn add_months(date: &str, months: i64) -> Option<String> {
+    let mut parts = date.splitn(3, '-').map(|part| part.parse::<i64>().ok());
+    let (year, month, day) = (parts.next()??, parts.next()??, parts.next()??);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    let index = year * 12 + (month - 1) + months;
+    let (year, month) = (index.div_euclid(12), index.rem_euclid(12) + 1);
+    let (next_year, next_month) = if month == 12 {
+        (year + 1, 1)
+    } else {
+        (year, month + 1)
+    };
+    let days_in_month = days_from_civil(next_year, next_month, 1) - days_from_civil(year, month, 1);
+    Some(format!(
+        "{:04}-{:02}-{:02}",
+        year,
+        month,
+        day.min(days_in_month)
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn add_months_clamps_to_month_end() {
+        assert_eq!(add_months("2024-01-31", 1).as_deref(), Some("2024-02-29"));
+        assert_eq!(add_months("2023-01-31", 1).as_deref(), Some("2023-02-28"));
+        assert_eq!(add_months("2024-01-31", 3).as_deref(), Some("2024-04-30"));
+        assert_eq!(add_months("2024-11-30", 3).as_deref(), Some("2025-02-28"));
+        assert_eq!(add_months("2024-03-15", 12).as_deref(), Some("2025-03-15"));
+        assert_eq!(add_months("2024-13-01", 1), None);
+    }
+}
@@ -0,0 +1,50 @@
+use qrcode::{render::svg, EcLevel, QrCode};
+use rand_core::{OsRng, RngCore};
+
+pub const KINDS: [&str; 3] = ["qr", "barcode", "nfc"];
+pub const STATUSES: [&str; 3] = ["active", "lost", "blocked"];
+
+const TOKEN_PREFIX: &str = "BP";
+
+/// Scanners in keyboard-wedge mode and NFC readers format the same identifier
+/// differently (separators, case, trailing newline), so only letters and digits
+/// are kept and compared in upper case.
+pub fn normalize_token(raw: &str) -> Result<String, String> {
+    let token: String = raw
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    if token.len() < 4 {
+        return Err("Kartennummer ist zu kurz".into());
+    }
+    if token.len() > 64 {
+        return Err("Kartennummer ist zu lang".into());
+    }
+    Ok(token)
+}
+
+pub fn generate_token() -> String {
+    let mut bytes = [0u8; 8];
+    OsRng.fill_bytes(&mut bytes);
+    let hex: String = bytes.iter().map(|b| format!("{:02X}", b)).collect();
+    format!("{}{}", TOKEN_PREFIX, hex)
+}
+
+pub fn qr_svg(token: &str) -> Result<String, String> {
+    let code = QrCode::with_error_correction_level(token.as_bytes(), EcLevel::M)
+        .map_err(|e| format!("QR-Code konnte nicht erzeugt werden: {}", e))?;
+    Ok(code
+        .render::<svg::Color>()
+        .min_dimensions(240, 240)
+        .quiet_zone(true)
+        .build())
+}
+
+pub fn status_reason(status: &str) -> &'static str {
+    match status {
+        "lost" => "Karte ist als verloren gemeldet",
+        "blocked" => "Karte ist gesperrt",
+        _ => "Karte ist nicht aktiv",
+    }
+}
@@ -0,0 +1,91 @@
+use rusqlite::Connection;
+use serde::Serialize;
+
+pub const TOPROPE: &str = "toprope";
+pub const LEAD: &str = "vorstieg";
+pub const KINDS: [&str; 2] = [TOPROPE, LEAD];
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CertificationSummary {
+    pub kind: String,
+    pub issued_on: String,
+    pub expires_on: Option<String>,
+}
+
+/// What the desk needs to know about a member's belay certifications at check-in.
+pub struct Assessment {
+    pub valid: Vec<CertificationSummary>,
+    pub warnings: Vec<String>,
+    /// Set when the member may not belay at all, i.e. holds neither certification.
+    pub rope_block: Option<String>,
+}
+
+pub fn label(kind: &str) -> &'static str {
+    match kind {
+        LEAD => "Vorstiegsschein",
+        _ => "Toprope-Schein",
+    }
+}
+
+pub fn assess(conn: &Connection, member_id: i64, rope_area: bool) -> rusqlite::Result<Assessment> {
+    let mut stmt = conn.prepare(
+        "SELECT kind,
+                issued_on,
+                expires_on,
+                expires_on IS NOT NULL AND expires_on < DATE('now', 'localtime')
+        FROM member_certifications
+        WHERE member_id = ? AND revoked_at IS NULL
+        ORDER BY (expires_on IS NULL) DESC, expires_on DESC",
+    )?;
+    let rows = stmt
+        .query_map([member_id], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, Option<String>>(2)?,
+                row.get::<_, bool>(3)?,
+            ))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    let mut valid = Vec::new();
+    let mut expired: Vec<(String, String)> = Vec::new();
+    for (kind, issued_on, expires_on, is_expired) in rows {
+        if is_expired {
+            if !expired.iter().any(|(k, _)| *k == kind) {
+                expired.push((kind, expires_on.unwrap_or_default()));
+            }
+        } else if !valid.iter().any(|c: &CertificationSummary| c.kind == kind) {
+            valid.push(CertificationSummary {
+                kind,
+                issued_on,
+                expires_on,
+            });
+        }
+    }
+
+    let has = |kind: &str| valid.iter().any(|c| c.kind == kind);
+    let mut warnings = Vec::new();
+    for kind in KINDS {
+        if has(kind) {
+            continue;
+        }
+        // A lead certification includes toprope belaying.
+        if kind == TOPROPE && has(LEAD) {
+            continue;
+        }
+        if let Some((_, date)) = expired.iter().find(|(k, _)| k == kind) {
+            warnings.push(format!("{} abgelaufen am {}", label(kind), date));
+        } else if rope_area {
+            warnings.push(format!("Kein {}", label(kind)));
+        }
+    }
+    let rope_block = (!has(TOPROPE) && !has(LEAD))
+        .then(|| "Kein gültiger Sicherungsschein für den Seilbereich".to_string());
+    Ok(Assessment {
+        valid,
+        warnings,
+        rope_block,
+    })
+}
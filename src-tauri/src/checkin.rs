@@ -0,0 +1,550 @@
+use rusqlite::{Connection, OptionalExtension};
+use serde::Serialize;
+
+use crate::CheckinSettings;
+
+pub const SELECT_EARLIEST_END: &str = "earliest_end";
+pub const SELECT_ASK: &str = "ask";
+
+#[derive(Serialize, Debug)]
+#[serde(tag = "code", rename_all = "camelCase")]
+pub enum CheckinError {
+    Failed {
+        message: String,
+    },
+    Rejected {
+        message: String,
+        reasons: Vec<Rejection>,
+    },
+    ChooseMembership {
+        message: String,
+        options: Vec<CheckinOption>,
+    },
+}
+
+impl From<String> for CheckinError {
+    fn from(message: String) -> Self {
+        CheckinError::Failed { message }
+    }
+}
+
+impl From<&str> for CheckinError {
+    fn from(message: &str) -> Self {
+        CheckinError::Failed {
+            message: message.to_string(),
+        }
+    }
+}
+
+impl CheckinError {
+    pub fn rejected(reasons: Vec<Rejection>) -> Self {
+        let message = match reasons.as_slice() {
+            [single] => single.reason.clone(),
+            [] => "Keine aktive Mitgliedschaft gefunden".to_string(),
+            _ => "Keine Mitgliedschaft ist für diesen Check-in gültig".to_string(),
+        };
+        CheckinError::Rejected { message, reasons }
+    }
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Rejection {
+    pub member_membership_id: Option<i64>,
+    pub membership_name: Option<String>,
+    pub reason: String,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CheckinOption {
+    pub member_membership_id: i64,
+    pub membership_name: String,
+    pub remaining_uses: Option<i64>,
+    pub end_date: Option<String>,
+}
+
+pub struct Candidate {
+    pub id: i64,
+    pub membership_id: i64,
+    pub membership_name: String,
+    pub remaining_uses: Option<i64>,
+    start_date: Option<String>,
+    end_date: Option<String>,
+    frozen_since: Option<String>,
+    allowed_weekdays: Option<String>,
+    time_windows: Vec<(String, String)>,
+}
+
+/// Local date, ISO weekday (1 = Monday) and time of the check-in.
+pub struct Moment {
+    date: String,
+    weekday: u32,
+    time: String,
+}
+
+pub enum Decision {
+    Consume(Candidate),
+    /// Re-entry within the configured window; refers to the previous check-in.
+    Reentry {
+        membership_id: Option<i64>,
+        member_membership_id: Option<i64>,
+    },
+}
+
+pub fn now(conn: &Connection) -> rusqlite::Result<Moment> {
+    conn.query_row(
+        "SELECT DATE('now', 'localtime'),
+                CAST(strftime('%w', 'now', 'localtime') AS INTEGER),
+                strftime('%H:%M', 'now', 'localtime')",
+        [],
+        |row| {
+            let weekday: u32 = row.get(1)?;
+            Ok(Moment {
+                date: row.get(0)?,
+                weekday: if weekday == 0 { 7 } else { weekday },
+                time: row.get(2)?,
+            })
+        },
+    )
+}
+
+pub fn decide(
+    conn: &Connection,
+    settings: &CheckinSettings,
+    member_id: i64,
+    requested: Option<i64>,
+    moment: &Moment,
+) -> Result<Decision, CheckinError> {
+    let status: String = conn
+        .query_row(
+            "SELECT status FROM members WHERE id = ?",
+            [member_id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?
+        .ok_or("Mitglied nicht gefunden")?;
+    if status == "blocked" {
+        return Err(CheckinError::rejected(vec![Rejection {
+            member_membership_id: None,
+            membership_name: None,
+            reason: "Mitglied ist gesperrt".into(),
+        }]));
+    }
+
+    let previous: Option<(Option<i64>, Option<i64>, f64, bool)> = conn
+        .query_row(
+            "SELECT membership_id,
+                    member_membership_id,
+                    (julianday('now') - julianday(created_at)) * 24,
+                    DATE(created_at, 'localtime') = DATE('now', 'localtime')
+            FROM member_checkins
+            WHERE member_id = ?
+            ORDER BY created_at DESC, id DESC
+            LIMIT 1",
+            [member_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    if let Some((membership_id, member_membership_id, hours_ago, same_day)) = previous {
+        // Re-entry needs the previous membership to be valid still; a membership
+        // frozen, expired or cancelled since then falls back to a regular check-in.
+        let in_window = settings.reentry_window_hours > 0
+            && hours_ago < f64::from(settings.reentry_window_hours);
+        let reentry_valid = match member_membership_id {
+            Some(id) if in_window => {
+                reentry_allowed(conn, id, moment).map_err(|e| e.to_string())?
+            }
+            _ => false,
+        };
+        if reentry_valid {
+            return Ok(Decision::Reentry {
+                membership_id,
+                member_membership_id,
+            });
+        }
+        if settings.once_per_day && same_day {
+            return Err(CheckinError::rejected(vec![Rejection {
+                member_membership_id: None,
+                membership_name: None,
+                reason: "Mitglied ist heute bereits eingecheckt".into(),
+            }]));
+        }
+    }
+
+    let candidates = load_candidates(conn, member_id).map_err(|e| e.to_string())?;
+    let mut valid = Vec::new();
+    let mut reasons = Vec::new();
+    for candidate in candidates {
+        if requested.is_some_and(|id| id != candidate.id) {
+            continue;
+        }
+        match evaluate(&candidate, moment) {
+            Ok(()) => valid.push(candidate),
+            Err(reason) => reasons.push(Rejection {
+                member_membership_id: Some(candidate.id),
+                membership_name: Some(candidate.membership_name.clone()),
+                reason,
+            }),
+        }
+    }
+    if requested.is_some() && valid.is_empty() && reasons.is_empty() {
+        return Err("Mitgliedschaft gehört nicht zu diesem Mitglied".into());
+    }
+
+    if valid.len() > 1 && requested.is_none() && settings.membership_selection == SELECT_ASK {
+        return Err(CheckinError::ChooseMembership {
+            message: "Mehrere Mitgliedschaften sind gültig, bitte eine auswählen".into(),
+            options: valid
+                .into_iter()
+                .map(|candidate| CheckinOption {
+                    member_membership_id: candidate.id,
+                    membership_name: candidate.membership_name,
+                    remaining_uses: candidate.remaining_uses,
+                    end_date: candidate.end_date,
+                })
+                .collect(),
+        });
+    }
+    // Candidates are loaded own memberships first, then by end date, so the
+    // member's own membership that expires first is used before a shared one.
+    match valid.into_iter().next() {
+        Some(candidate) => Ok(Decision::Consume(candidate)),
+        None => Err(CheckinError::rejected(reasons)),
+    }
+}
+
+fn evaluate(candidate: &Candidate, moment: &Moment) -> Result<(), String> {
+    if let Some(start) = candidate.start_date.as_deref() {
+        if date_part(start) > moment.date.as_str() {
+            return Err(format!("Gültig erst ab {}", date_part(start)));
+        }
+    }
+    if let Some(end) = candidate.end_date.as_deref() {
+        if date_part(end) < moment.date.as_str() {
+            return Err(format!("Abgelaufen am {}", date_part(end)));
+        }
+    }
+    if let Some(since) = candidate.frozen_since.as_deref() {
+        return Err(format!("Pausiert seit {}", since));
+    }
+    if candidate.remaining_uses.is_some_and(|uses| uses <= 0) {
+        return Err("Keine Eintritte mehr übrig".into());
+    }
+    if let Some(weekdays) = candidate.allowed_weekdays.as_deref() {
+        let allowed = parse_weekdays(weekdays);
+        if !allowed.contains(&moment.weekday) {
+            let names: Vec<&str> = allowed.iter().map(|day| weekday_name(*day)).collect();
+            return Err(format!("Nur gültig am {}", names.join(", ")));
+        }
+    }
+    if !candidate.time_windows.is_empty()
+        && !candidate
+            .time_windows
+            .iter()
+            .any(|(start, end)| start.as_str() <= moment.time.as_str() && moment.time < *end)
+    {
+        let windows: Vec<String> = candidate
+            .time_windows
+            .iter()
+            .map(|(start, end)| format!("{}-{}", start, end))
+            .collect();
+        return Err(format!("Nur gültig von {} Uhr", windows.join(", ")));
+    }
+    Ok(())
+}
+
+/// Whether the membership still covers the day; remaining uses and time
+/// restrictions do not apply to a re-entry.
+fn reentry_allowed(
+    conn: &Connection,
+    member_membership_id: i64,
+    moment: &Moment,
+) -> rusqlite::Result<bool> {
+    let candidate = conn
+        .query_row(
+            "SELECT membership_id, start_date, end_date, frozen_since
+            FROM member_memberships WHERE id = ?",
+            [member_membership_id],
+            |row| {
+                Ok(Candidate {
+                    id: member_membership_id,
+                    membership_id: row.get(0)?,
+                    membership_name: String::new(),
+                    remaining_uses: None,
+                    start_date: row.get(1)?,
+                    end_date: row.get(2)?,
+                    frozen_since: row.get(3)?,
+                    allowed_weekdays: None,
+                    time_windows: Vec::new(),
+                })
+            },
+        )
+        .optional()?;
+    Ok(candidate.is_some_and(|candidate| evaluate(&candidate, moment).is_ok()))
+}
+
+fn load_candidates(conn: &Connection, member_id: i64) -> rusqlite::Result<Vec<Candidate>> {
+    let mut stmt = conn.prepare(
+        "SELECT mm.id,
+                mm.membership_id,
+                CASE WHEN mm.member_id = ?1 THEN ms.membership_type
+                     ELSE ms.membership_type || ' (von ' || o.first_name || ' ' || o.last_name || ')'
+                END,
+                mm.remaining_uses,
+                mm.start_date,
+                mm.end_date,
+                mm.frozen_since,
+                ms.allowed_weekdays
+        FROM member_memberships mm
+        JOIN memberships ms ON ms.id = mm.membership_id
+        JOIN members o ON o.id = mm.member_id
+        WHERE mm.member_id = ?1
+           OR mm.member_id IN (
+                SELECT other.member_id
+                FROM household_members own
+                JOIN households h ON h.id = own.household_id AND h.shared_memberships = 1
+                JOIN household_members other ON other.household_id = own.household_id
+                WHERE own.member_id = ?1
+           )
+        ORDER BY mm.member_id <> ?1, (mm.end_date IS NULL) ASC, mm.end_date ASC, mm.created_at ASC",
+    )?;
+    let mut candidates = stmt
+        .query_map([member_id], |row| {
+            Ok(Candidate {
+                id: row.get(0)?,
+                membership_id: row.get(1)?,
+                membership_name: row.get(2)?,
+                remaining_uses: row.get(3)?,
+                start_date: row.get(4)?,
+                end_date: row.get(5)?,
+                frozen_since: row.get(6)?,
+                allowed_weekdays: row.get(7)?,
+                time_windows: Vec::new(),
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    let mut windows = conn.prepare(
+        "SELECT start_time, end_time FROM membership_time_windows
+        WHERE membership_id = ?
+        ORDER BY start_time",
+    )?;
+    for candidate in &mut candidates {
+        candidate.time_windows = windows
+            .query_map([candidate.membership_id], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+    }
+    Ok(candidates)
+}
+
+pub fn parse_weekdays(value: &str) -> Vec<u32> {
+    value
+        .split(',')
+        .filter_map(|day| day.trim().parse().ok())
+        .filter(|day| (1..=7).contains(day))
+        .collect()
+}
+
+pub fn is_valid_time(value: &str) -> bool {
+    let Some((hours, minutes)) = value.split_once(':') else {
+        return false;
+    };
+    hours.len() == 2
+        && minutes.len() == 2
+        && hours
+            .bytes()
+            .chain(minutes.bytes())
+            .all(|b| b.is_ascii_digit())
+        && hours.parse::<u32>().is_ok_and(|hours| hours <= 24)
+        && minutes.parse::<u32>().is_ok_and(|minutes| minutes < 60)
+        && (hours != "24" || minutes == "00")
+}
+
+fn weekday_name(day: u32) -> &'static str {
+    match day {
+        1 => "Mo",
+        2 => "Di",
+        3 => "Mi",
+        4 => "Do",
+        5 => "Fr",
+        6 => "Sa",
+        _ => "So",
+    }
+}
+
+fn date_part(value: &str) -> &str {
+    value.get(..10).unwrap_or(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate() -> Candidate {
+        Candidate {
+            id: 1,
+            membership_id: 1,
+            membership_name: "Monatskarte".into(),
+            remaining_uses: None,
+            start_date: Some("2024-05-01".into()),
+            end_date: Some("2024-05-31".into()),
+            frozen_since: None,
+            allowed_weekdays: None,
+            time_windows: Vec::new(),
+        }
+    }
+
+    /// 2024-05-15 is a Wednesday.
+    fn at(time: &str) -> Moment {
+        Moment {
+            date: "2024-05-15".into(),
+            weekday: 3,
+            time: time.into(),
+        }
+    }
+
+    #[test]
+    fn weekday_restriction() {
+        let mut weekend = candidate();
+        weekend.allowed_weekdays = Some("6,7".into());
+        assert_eq!(
+            evaluate(&weekend, &at("10:00")).unwrap_err(),
+            "Nur gültig am Sa, So"
+        );
+        weekend.allowed_weekdays = Some("3".into());
+        assert!(evaluate(&weekend, &at("10:00")).is_ok());
+    }
+
+    #[test]
+    fn time_window_edges() {
+        let mut morning = candidate();
+        morning.time_windows = vec![("06:00".into(), "12:00".into())];
+        assert!(evaluate(&morning, &at("06:00")).is_ok());
+        assert!(evaluate(&morning, &at("11:59")).is_ok());
+        assert_eq!(
+            evaluate(&morning, &at("12:00")).unwrap_err(),
+            "Nur gültig von 06:00-12:00 Uhr"
+        );
+        assert!(evaluate(&morning, &at("05:59")).is_err());
+
+        let mut evening = candidate();
+        evening.time_windows = vec![("20:00".into(), "24:00".into())];
+        assert!(evaluate(&evening, &at("23:59")).is_ok());
+        assert!(evaluate(&evening, &at("19:59")).is_err());
+    }
+
+    #[test]
+    fn frozen_and_expired() {
+        let mut frozen = candidate();
+        frozen.frozen_since = Some("2024-05-10".into());
+        assert_eq!(
+            evaluate(&frozen, &at("10:00")).unwrap_err(),
+            "Pausiert seit 2024-05-10"
+        );
+
+        let mut expired = candidate();
+        expired.end_date = Some("2024-05-14".into());
+        assert_eq!(
+            evaluate(&expired, &at("10:00")).unwrap_err(),
+            "Abgelaufen am 2024-05-14"
+        );
+        expired.end_date = Some("2024-05-15".into());
+        assert!(evaluate(&expired, &at("10:00")).is_ok());
+
+        let mut upcoming = candidate();
+        upcoming.start_date = Some("2024-05-16".into());
+        assert_eq!(
+            evaluate(&upcoming, &at("10:00")).unwrap_err(),
+            "Gültig erst ab 2024-05-16"
+        );
+
+        let mut used_up = candidate();
+        used_up.remaining_uses = Some(0);
+        assert!(evaluate(&used_up, &at("10:00")).is_err());
+    }
+
+    #[test]
+    fn valid_times() {
+        assert!(is_valid_time("00:00"));
+        assert!(is_valid_time("09:30"));
+        assert!(is_valid_time("24:00"));
+        assert!(!is_valid_time("24:30"));
+        assert!(!is_valid_time("+1:00"));
+        assert!(!is_valid_time("10:+5"));
+        assert!(!is_valid_time("9:30"));
+        assert!(!is_valid_time("12:60"));
+        assert!(!is_valid_time("1200"));
+    }
+
+    #[test]
+    fn weekdays_are_parsed_leniently() {
+        assert_eq!(parse_weekdays("1, 3,5"), vec![1, 3, 5]);
+        assert_eq!(parse_weekdays("0,7,8,x,"), vec![7]);
+        assert!(parse_weekdays("").is_empty());
+    }
+
+    fn reentry(conn: &Connection) -> Result<Decision, CheckinError> {
+        let settings = CheckinSettings {
+            reentry_window_hours: 2,
+            once_per_day: false,
+            ..CheckinSettings::default()
+        };
+        decide(conn, &settings, 1, None, &now(conn).unwrap())
+    }
+
+    #[test]
+    fn reentry_requires_previous_membership_to_be_valid() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        crate::migrations::run(
+            &mut conn,
+            std::path::Path::new(":memory:"),
+            std::path::Path::new(""),
+        )
+        .unwrap();
+        conn.execute_batch(
+            "
+            INSERT INTO members (id, first_name, last_name) VALUES (1, 'Erika', 'Müller');
+            INSERT INTO memberships (id, membership_type, max_uses) VALUES (1, '10er-Karte', 10);
+            INSERT INTO member_memberships (id, member_id, membership_id, remaining_uses, start_date)
+            VALUES (1, 1, 1, 0, '2000-01-01');
+            INSERT INTO member_checkins (member_id, membership_id, member_membership_id)
+            VALUES (1, 1, 1);
+            ",
+        )
+        .unwrap();
+
+        // The last entry was used for the previous check-in.
+        assert!(matches!(
+            reentry(&conn),
+            Ok(Decision::Reentry {
+                member_membership_id: Some(1),
+                ..
+            })
+        ));
+
+        conn.execute(
+            "UPDATE member_memberships SET frozen_since = DATE('now', 'localtime') WHERE id = 1",
+            [],
+        )
+        .unwrap();
+        assert!(matches!(reentry(&conn), Err(CheckinError::Rejected { .. })));
+
+        conn.execute(
+            "UPDATE member_memberships
+            SET frozen_since = NULL, end_date = DATE('now', 'localtime', '-1 day')
+            WHERE id = 1",
+            [],
+        )
+        .unwrap();
+        assert!(matches!(reentry(&conn), Err(CheckinError::Rejected { .. })));
+
+        conn.execute("DELETE FROM member_memberships WHERE id = 1", [])
+            .unwrap();
+        assert!(matches!(reentry(&conn), Err(CheckinError::Rejected { .. })));
+    }
+}
@@ -0,0 +1,1085 @@
+use rusqlite::{params, Connection, OptionalExtension};
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use crate::{format_amount, vat, BusinessSettings, PaymentMethod};
+
+const TAXONOMY_VERSION: &str = "2.3";
+
+#[derive(Clone, Copy)]
+enum Column {
+    Text(usize),
+    Integer,
+    Decimal(usize),
+}
+
+struct Table {
+    file: &'static str,
+    name: &'static str,
+    description: &'static str,
+    columns: &'static [(&'static str, Column)],
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    fn new(
+        file: &'static str,
+        name: &'static str,
+        description: &'static str,
+        columns: &'static [(&'static str, Column)],
+    ) -> Self {
+        Self {
+            file,
+            name,
+            description,
+            columns,
+            rows: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, row: Vec<String>) {
+        debug_assert_eq!(row.len(), self.columns.len(), "{}", self.file);
+        self.rows.push(row);
+    }
+
+    fn to_csv(&self) -> String {
+        let mut out = String::new();
+        let header: Vec<&str> = self.columns.iter().map(|(name, _)| *name).collect();
+        out.push_str(&header.join(";"));
+        out.push_str("\r\n");
+        for row in &self.rows {
+            let cells: Vec<String> = row
+                .iter()
+                .zip(self.columns.iter())
+                .map(|(value, (_, column))| match column {
+                    Column::Text(_) => format!("\"{}\"", value.replace('"', "\"\"")),
+                    _ => value.clone(),
+                })
+                .collect();
+            out.push_str(&cells.join(";"));
+            out.push_str("\r\n");
+        }
+        out
+    }
+}
+
+const Z: [(&str, Column); 3] = [
+    ("Z_KASSE_ID", Column::Text(50)),
+    ("Z_ERSTELLUNG", Column::Text(19)),
+    ("Z_NR", Column::Integer),
+];
+
+macro_rules! columns {
+    ($($name:literal => $column:expr),* $(,)?) => {
+        &[Z[0], Z[1], Z[2], $(($name, $column)),*]
+    };
+}
+
+const BON_COLUMNS: &[(&str, Column)] = columns![
+    "BON_ID" => Column::Text(40),
+    "BON_NR" => Column::Integer,
+    "BON_TYP" => Column::Text(30),
+    "BON_NAME" => Column::Text(60),
+    "TERMINAL_ID" => Column::Text(50),
+    "BON_STORNO" => Column::Integer,
+    "BON_START" => Column::Text(19),
+    "BON_ENDE" => Column::Text(19),
+    "BEDIENER_ID" => Column::Text(50),
+    "BEDIENER_NAME" => Column::Text(50),
+    "UMS_BRUTTO" => Column::Decimal(2),
+    "KUNDE_NAME" => Column::Text(50),
+    "KUNDE_ID" => Column::Text(50),
+    "KUNDE_TYP" => Column::Text(50),
+    "KUNDE_STRASSE" => Column::Text(60),
+    "KUNDE_PLZ" => Column::Text(10),
+    "KUNDE_ORT" => Column::Text(62),
+    "KUNDE_LAND" => Column::Text(3),
+    "KUNDE_USTID" => Column::Text(15),
+    "BON_NOTIZ" => Column::Text(255),
+];
+
+const BON_VAT_COLUMNS: &[(&str, Column)] = columns![
+    "BON_ID" => Column::Text(40),
+    "UST_SCHLUESSEL" => Column::Integer,
+    "BON_BRUTTO" => Column::Decimal(5),
+    "BON_NETTO" => Column::Decimal(5),
+    "BON_UST" => Column::Decimal(5),
+];
+
+const ALLOCATION_COLUMNS: &[(&str, Column)] = columns![
+    "BON_ID" => Column::Text(40),
+    "ABRECHNUNGSKREIS" => Column::Text(50),
+];
+
+const DATAPAYMENT_COLUMNS: &[(&str, Column)] = columns![
+    "BON_ID" => Column::Text(40),
+    "ZAHLART_TYP" => Column::Text(25),
+    "ZAHLART_NAME" => Column::Text(60),
+    "ZAHLWAEH_CODE" => Column::Text(3),
+    "ZAHLWAEH_BETRAG" => Column::Decimal(2),
+    "BASISWAEH_BETRAG" => Column::Decimal(2),
+];
+
+const REFERENCE_COLUMNS: &[(&str, Column)] = columns![
+    "BON_ID" => Column::Text(40),
+    "POS_ZEILE" => Column::Text(50),
+    "REF_TYP" => Column::Text(20),
+    "REF_NAME" => Column::Text(40),
+    "REF_DATUM" => Column::Text(19),
+    "REF_Z_KASSE_ID" => Column::Text(50),
+    "REF_Z_NR" => Column::Integer,
+    "REF_BON_ID" => Column::Text(40),
+];
+
+const BON_TSE_COLUMNS: &[(&str, Column)] = columns![
+    "BON_ID" => Column::Text(40),
+    "TSE_ID" => Column::Integer,
+    "TSE_TANR" => Column::Integer,
+    "TSE_TA_START" => Column::Text(29),
+    "TSE_TA_ENDE" => Column::Text(29),
+    "TSE_TA_VORGANGSART" => Column::Text(30),
+    "TSE_TA_SIGZ" => Column::Integer,
+    "TSE_TA_SIG" => Column::Text(512),
+    "TSE_TA_FEHLER" => Column::Text(200),
+    "TSE_VORGANGSDATEN" => Column::Text(1000),
+];
+
+const LINE_COLUMNS: &[(&str, Column)] = columns![
+    "BON_ID" => Column::Text(40),
+    "POS_ZEILE" => Column::Text(50),
+    "GUTSCHEIN_NR" => Column::Text(50),
+    "ARTIKELTEXT" => Column::Text(255),
+    "POS_TERMINAL_ID" => Column::Text(50),
+    "GV_TYP" => Column::Text(30),
+    "GV_NAME" => Column::Text(40),
+    "INHAUS" => Column::Integer,
+    "P_STORNO" => Column::Integer,
+    "AGENTUR_ID" => Column::Integer,
+    "ART_NR" => Column::Text(50),
+    "GTIN" => Column::Text(50),
+    "WARENGR_ID" => Column::Text(40),
+    "WARENGR" => Column::Text(50),
+    "MENGE" => Column::Decimal(3),
+    "FAKTOR" => Column::Decimal(3),
+    "EINHEIT" => Column::Text(50),
+    "STK_BR" => Column::Decimal(5),
+];
+
+const LINE_VAT_COLUMNS: &[(&str, Column)] = columns![
+    "BON_ID" => Column::Text(40),
+    "POS_ZEILE" => Column::Text(50),
+    "UST_SCHLUESSEL" => Column::Integer,
+    "POS_BRUTTO" => Column::Decimal(5),
+    "POS_NETTO" => Column::Decimal(5),
+    "POS_UST" => Column::Decimal(5),
+];
+
+const ITEMAMOUNT_COLUMNS: &[(&str, Column)] = columns![
+    "BON_ID" => Column::Text(40),
+    "POS_ZEILE" => Column::Text(50),
+    "TYP" => Column::Text(20),
+    "PF_BRUTTO" => Column::Decimal(5),
+    "PF_NETTO" => Column::Decimal(5),
+    "PF_UST" => Column::Decimal(5),
+];
+
+const SUBITEM_COLUMNS: &[(&str, Column)] = columns![
+    "BON_ID" => Column::Text(40),
+    "POS_ZEILE" => Column::Text(50),
+    "ZI_ART_NR" => Column::Text(50),
+    "ZI_GTIN" => Column::Text(50),
+    "ZI_NAME" => Column::Text(60),
+    "ZI_WARENGR_ID" => Column::Text(40),
+    "ZI_WARENGR" => Column::Text(50),
+    "ZI_MENGE" => Column::Decimal(3),
+    "ZI_FAKTOR" => Column::Decimal(3),
+    "ZI_EINHEIT" => Column::Text(50),
+    "ZI_UST_SCHLUESSEL" => Column::Integer,
+    "ZI_BASISPREIS_BRUTTO" => Column::Decimal(5),
+    "ZI_BASISPREIS_NETTO" => Column::Decimal(5),
+    "ZI_BASISPREIS_UST" => Column::Decimal(5),
+];
+
+const CLOSING_COLUMNS: &[(&str, Column)] = columns![
+    "Z_BUCHUNGSTAG" => Column::Text(10),
+    "TAXONOMIE_VERSION" => Column::Text(10),
+    "Z_START_ID" => Column::Text(40),
+    "Z_ENDE_ID" => Column::Text(40),
+    "NAME" => Column::Text(60),
+    "STRASSE" => Column::Text(60),
+    "PLZ" => Column::Text(10),
+    "ORT" => Column::Text(62),
+    "LAND" => Column::Text(3),
+    "STNR" => Column::Text(20),
+    "USTID" => Column::Text(15),
+    "Z_SE_ZAHLUNGEN" => Column::Decimal(2),
+    "Z_SE_BARZAHLUNGEN" => Column::Decimal(2),
+];
+
+const LOCATION_COLUMNS: &[(&str, Column)] = columns![
+    "LOC_NAME" => Column::Text(60),
+    "LOC_STRASSE" => Column::Text(60),
+    "LOC_PLZ" => Column::Text(10),
+    "LOC_ORT" => Column::Text(62),
+    "LOC_LAND" => Column::Text(3),
+    "LOC_USTID" => Column::Text(15),
+];
+
+const CASHREGISTER_COLUMNS: &[(&str, Column)] = columns![
+    "KASSE_BRAND" => Column::Text(50),
+    "KASSE_MODELL" => Column::Text(50),
+    "KASSE_SERIENNR" => Column::Text(70),
+    "KASSE_SW_BRAND" => Column::Text(50),
+    "KASSE_SW_VERSION" => Column::Text(50),
+    "KASSE_BASISWAEH_CODE" => Column::Text(3),
+    "KEINE_UST_ZUORDNUNG" => Column::Integer,
+];
+
+const SLAVE_COLUMNS: &[(&str, Column)] = columns![
+    "KASSE_SERIENNR" => Column::Text(70),
+    "KASSE_SW_BRAND" => Column::Text(50),
+    "KASSE_SW_VERSION" => Column::Text(50),
+];
+
+const AGENCY_COLUMNS: &[(&str, Column)] = columns![
+    "AGENTUR_ID" => Column::Integer,
+    "AGENTUR_NAME" => Column::Text(60),
+    "AGENTUR_STRASSE" => Column::Text(60),
+    "AGENTUR_PLZ" => Column::Text(10),
+    "AGENTUR_ORT" => Column::Text(62),
+    "AGENTUR_LAND" => Column::Text(3),
+    "AGENTUR_STNR" => Column::Text(20),
+    "AGENTUR_USTID" => Column::Text(15),
+];
+
+const VAT_COLUMNS: &[(&str, Column)] = columns![
+    "UST_SCHLUESSEL" => Column::Integer,
+    "UST_SATZ" => Column::Decimal(2),
+    "UST_BESCHR" => Column::Text(55),
+];
+
+const TSE_COLUMNS: &[(&str, Column)] = columns![
+    "TSE_ID" => Column::Integer,
+    "TSE_SERIAL" => Column::Text(68),
+    "TSE_SIG_ALGO" => Column::Text(21),
+    "TSE_ZEITFORMAT" => Column::Text(31),
+    "TSE_PD_ENCODING" => Column::Text(5),
+    "TSE_PUBLIC_KEY" => Column::Text(512),
+    "TSE_ZERTIFIKAT_I" => Column::Text(1000),
+    "TSE_ZERTIFIKAT_II" => Column::Text(1000),
+];
+
+const BUSINESSCASE_COLUMNS: &[(&str, Column)] = columns![
+    "GV_TYP" => Column::Text(30),
+    "GV_NAME" => Column::Text(40),
+    "AGENTUR_ID" => Column::Integer,
+    "UST_SCHLUESSEL" => Column::Integer,
+    "Z_UMS_BRUTTO" => Column::Decimal(5),
+    "Z_UMS_NETTO" => Column::Decimal(5),
+    "Z_UST" => Column::Decimal(5),
+];
+
+const PAYMENT_COLUMNS: &[(&str, Column)] = columns![
+    "ZAHLART_TYP" => Column::Text(25),
+    "ZAHLART_NAME" => Column::Text(60),
+    "Z_ZAHLART_BETRAG" => Column::Decimal(2),
+];
+
+const CASH_PER_CURRENCY_COLUMNS: &[(&str, Column)] = columns![
+    "ZAHLART_WAEH" => Column::Text(3),
+    "ZAHLART_BETRAG_WAEH" => Column::Decimal(2),
+];
+
+const VAT_KEY_DESCRIPTIONS: &[(i64, &str)] = &[
+    (1, "Regelsteuersatz"),
+    (2, "Ermäßigter Steuersatz"),
+    (3, "Durchschnittsatz (§ 24 Abs. 1 Nr. 3 UStG)"),
+    (4, "Durchschnittsatz (§ 24 Abs. 1 Nr. 1 UStG)"),
+    (5, "Nicht Steuerbar"),
+    (6, "Umsatzsteuerfrei"),
+    (7, "UmsatzsteuerNichtErmittelbar"),
+];
+
+pub(crate) struct ExportSummary {
+    pub path: PathBuf,
+    pub closing_count: usize,
+    pub transaction_count: usize,
+}
+
+struct Closing {
+    session_id: i64,
+    z_number: i64,
+    created: String,
+    business_day: String,
+}
+
+fn payment_type(method: PaymentMethod) -> &'static str {
+    match method {
+        PaymentMethod::Cash => "Bar",
+        PaymentMethod::Card => "ECKarte",
+        PaymentMethod::Balance => "Unbar",
+    }
+}
+
+fn load_closings(conn: &Connection, session_ids: &[i64]) -> rusqlite::Result<Vec<Closing>> {
+    let mut stmt = conn.prepare(
+        "SELECT id,
+                z_number,
+                STRFTIME('%Y-%m-%dT%H:%M:%S', closed_at, 'localtime'),
+                DATE(closed_at, 'localtime')
+        FROM cash_sessions
+        WHERE id = ? AND status = 'closed'",
+    )?;
+    let mut closings = Vec::new();
+    for id in session_ids {
+        let closing = stmt.query_row([id], |row| {
+            Ok(Closing {
+                session_id: row.get(0)?,
+                z_number: row.get(1)?,
+                created: row.get(2)?,
+                business_day: row.get(3)?,
+            })
+        })?;
+        closings.push(closing);
+    }
+    closings.sort_by_key(|closing| closing.z_number);
+    Ok(closings)
+}
+
+pub(crate) fn export(
+    conn: &Connection,
+    business: &BusinessSettings,
+    currency: &str,
+    session_ids: &[i64],
+    target_dir: &Path,
+    as_zip: bool,
+) -> Result<ExportSummary, String> {
+    let closings = load_closings(conn, session_ids).map_err(|e| e.to_string())?;
+    let (first, last) = match (closings.first(), closings.last()) {
+        (Some(first), Some(last)) => (first, last),
+        _ => return Err("Keine abgeschlossenen Kassensitzungen im Zeitraum.".into()),
+    };
+
+    let mut tables =
+        build_tables(conn, business, currency, &closings).map_err(|e| e.to_string())?;
+    let transaction_count = tables
+        .iter()
+        .find(|table| table.file == "transactions.csv")
+        .map(|table| table.rows.len())
+        .unwrap_or(0);
+    let index = index_xml(business, &tables, &first.business_day, &last.business_day);
+
+    let folder_name = format!(
+        "DSFinV-K_{}_Z{}-Z{}",
+        sanitize_file_name(&business.cash_register_id),
+        first.z_number,
+        last.z_number
+    );
+    fs::create_dir_all(target_dir).map_err(|e| e.to_string())?;
+
+    let path = if as_zip {
+        let path = target_dir.join(format!("{}.zip", folder_name));
+        let file = fs::File::create(&path).map_err(|e| e.to_string())?;
+        let mut archive = zip::ZipWriter::new(file);
+        let options =
+            zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+        archive
+            .start_file("index.xml", options)
+            .map_err(|e| e.to_string())?;
+        archive
+            .write_all(index.as_bytes())
+            .map_err(|e| e.to_string())?;
+        for table in tables.iter_mut() {
+            archive
+                .start_file(table.file, options)
+                .map_err(|e| e.to_string())?;
+            archive
+                .write_all(table.to_csv().as_bytes())
+                .map_err(|e| e.to_string())?;
+        }
+        archive.finish().map_err(|e| e.to_string())?;
+        path
+    } else {
+        let path = target_dir.join(&folder_name);
+        fs::create_dir_all(&path).map_err(|e| e.to_string())?;
+        fs::write(path.join("index.xml"), &index).map_err(|e| e.to_string())?;
+        for table in &tables {
+            fs::write(path.join(table.file), table.to_csv()).map_err(|e| e.to_string())?;
+        }
+        path
+    };
+
+    Ok(ExportSummary {
+        path,
+        closing_count: closings.len(),
+        transaction_count,
+    })
+}
+
+fn sanitize_file_name(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+fn build_tables(
+    conn: &Connection,
+    business: &BusinessSettings,
+    currency: &str,
+    closings: &[Closing],
+) -> Result<Vec<Table>, Box<dyn std::error::Error>> {
+    let mut bons = Table::new("transactions.csv", "Bonkopf", "Bonkopf", BON_COLUMNS);
+    let mut bon_vat = Table::new(
+        "transactions_vat.csv",
+        "Bonkopf_USt",
+        "Bonkopf - Umsatzsteuer",
+        BON_VAT_COLUMNS,
+    );
+    let allocation = Table::new(
+        "allocation_groups.csv",
+        "Bonkopf_AbrKreis",
+        "Bonkopf - Abrechnungskreis",
+        ALLOCATION_COLUMNS,
+    );
+    let mut datapayment = Table::new(
+        "datapayment.csv",
+        "Bonkopf_Zahlarten",
+        "Bonkopf - Zahlarten",
+        DATAPAYMENT_COLUMNS,
+    );
+    let mut references = Table::new(
+        "references.csv",
+        "Bon_Referenzen",
+        "Bon - Referenzen",
+        REFERENCE_COLUMNS,
+    );
+    let mut bon_tse = Table::new(
+        "transactions_tse.csv",
+        "TSE_Transaktionen",
+        "TSE - Transaktionen",
+        BON_TSE_COLUMNS,
+    );
+    let mut lines = Table::new("lines.csv", "Bonpos", "Bonpositionen", LINE_COLUMNS);
+    let mut line_vat = Table::new(
+        "lines_vat.csv",
+        "Bonpos_USt",
+        "Bonpositionen - Umsatzsteuer",
+        LINE_VAT_COLUMNS,
+    );
+    let mut itemamounts = Table::new(
+        "itemamounts.csv",
+        "Bonpos_Preisfindung",
+        "Bonpositionen - Preisfindung",
+        ITEMAMOUNT_COLUMNS,
+    );
+    let subitems = Table::new(
+        "subitems.csv",
+        "Bonpos_Zusatzinfo",
+        "Bonpositionen - Zusatzinformationen",
+        SUBITEM_COLUMNS,
+    );
+    let mut closing_table = Table::new(
+        "cashpointclosing.csv",
+        "Stamm_Abschluss",
+        "Stammdaten - Kassenabschluss",
+        CLOSING_COLUMNS,
+    );
+    let mut location = Table::new(
+        "location.csv",
+        "Stamm_Orte",
+        "Stammdaten - Orte",
+        LOCATION_COLUMNS,
+    );
+    let mut cashregister = Table::new(
+        "cashregister.csv",
+        "Stamm_Kassen",
+        "Stammdaten - Kassen",
+        CASHREGISTER_COLUMNS,
+    );
+    let slaves = Table::new(
+        "slaves.csv",
+        "Stamm_Terminals",
+        "Stammdaten - Terminals",
+        SLAVE_COLUMNS,
+    );
+    let agencies = Table::new(
+        "pa.csv",
+        "Stamm_Agenturen",
+        "Stammdaten - Agenturen",
+        AGENCY_COLUMNS,
+    );
+    let mut vat = Table::new(
+        "vat.csv",
+        "Stamm_USt",
+        "Stammdaten - Umsatzsteuer",
+        VAT_COLUMNS,
+    );
+    let mut tse = Table::new("tse.csv", "Stamm_TSE", "Stammdaten - TSE", TSE_COLUMNS);
+    let mut businesscases = Table::new(
+        "businesscases.csv",
+        "Z_GV_Typ",
+        "Kassenabschluss - Geschäftsvorfalltypen",
+        BUSINESSCASE_COLUMNS,
+    );
+    let mut payments = Table::new(
+        "payment.csv",
+        "Z_Zahlart",
+        "Kassenabschluss - Zahlarten",
+        PAYMENT_COLUMNS,
+    );
+    let mut cash_per_currency = Table::new(
+        "cash_per_currency.csv",
+        "Z_Waehrungen",
+        "Kassenabschluss - Bargeld nach Währungen",
+        CASH_PER_CURRENCY_COLUMNS,
+    );
+
+    let kasse = business.cash_register_id.clone();
+    let mut transaction_stmt = conn.prepare(
+        "SELECT t.id,
+                t.total_cents,
+                STRFTIME('%Y-%m-%dT%H:%M:%S', t.created_at, 'localtime'),
+                t.user_id,
+                u.display_name,
+                t.member_id,
+                m.first_name || ' ' || m.last_name,
+                t.cancels_transaction_id,
+                t.description
+        FROM transactions t
+        LEFT JOIN users u ON u.id = t.user_id
+        LEFT JOIN members m ON m.id = t.member_id
+        WHERE t.cash_session_id = ?
+        ORDER BY t.id ASC",
+    )?;
+    let mut line_stmt = conn.prepare(
+        "SELECT product_id,
+                product_name,
+                product_type_id,
+                product_type_name,
+                unit_price_cents,
+                quantity,
+                line_total_cents,
+                vat_key,
+                net_cents,
+                tax_cents
+        FROM transaction_lines
+        WHERE transaction_id = ?
+        ORDER BY id ASC",
+    )?;
+    let mut payment_stmt = conn.prepare(
+        "SELECT method, amount_cents FROM transaction_payments WHERE transaction_id = ? ORDER BY id ASC",
+    )?;
+    let mut reference_stmt = conn.prepare(
+        "SELECT STRFTIME('%Y-%m-%dT%H:%M:%S', t.created_at, 'localtime'), cs.z_number
+        FROM transactions t
+        LEFT JOIN cash_sessions cs ON cs.id = t.cash_session_id
+        WHERE t.id = ?",
+    )?;
+    let mut signature_stmt = conn.prepare(
+        "SELECT d.rowid,
+                s.tse_transaction_number,
+                s.start_time,
+                s.finish_time,
+                s.process_type,
+                s.signature_counter,
+                s.signature,
+                s.error,
+                s.process_data
+        FROM transaction_signatures s
+        LEFT JOIN tse_devices d ON d.serial_number = s.tse_serial
+        WHERE s.transaction_id = ?",
+    )?;
+    let mut device_stmt = conn.prepare(
+        "SELECT DISTINCT d.rowid, d.serial_number, d.signature_algorithm, d.time_format, d.public_key
+        FROM tse_devices d
+        JOIN transaction_signatures s ON s.tse_serial = d.serial_number
+        JOIN transactions t ON t.id = s.transaction_id
+        WHERE t.cash_session_id = ?
+        ORDER BY d.rowid ASC",
+    )?;
+    // vat.csv lists the rates the lines were actually booked with, which may be
+    // historical ones, rather than today's rates.
+    let mut vat_rate_stmt = conn.prepare(
+        "SELECT DISTINCT tl.vat_key, COALESCE(tl.vat_rate_basis_points, 0)
+        FROM transaction_lines tl
+        JOIN transactions t ON t.id = tl.transaction_id
+        WHERE t.cash_session_id = ? AND tl.vat_key IS NOT NULL
+        ORDER BY tl.vat_key ASC",
+    )?;
+
+    for closing in closings {
+        let z = || {
+            vec![
+                kasse.clone(),
+                closing.created.clone(),
+                closing.z_number.to_string(),
+            ]
+        };
+        let with_z = |values: Vec<String>| {
+            let mut row = z();
+            row.extend(values);
+            row
+        };
+
+        let transactions = transaction_stmt
+            .query_map([closing.session_id], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, i64>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, Option<i64>>(3)?,
+                    row.get::<_, Option<String>>(4)?,
+                    row.get::<_, Option<i64>>(5)?,
+                    row.get::<_, Option<String>>(6)?,
+                    row.get::<_, Option<i64>>(7)?,
+                    row.get::<_, Option<String>>(8)?,
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut sales_by_vat: Vec<(i64, i64, i64, i64)> = Vec::new();
+        let mut payment_totals: Vec<(PaymentMethod, i64)> = Vec::new();
+
+        for (id, total_cents, created, user_id, user_name, member_id, member_name, cancels, note) in
+            &transactions
+        {
+            let storno = cancels.is_some();
+            bons.push(with_z(vec![
+                id.to_string(),
+                id.to_string(),
+                "Beleg".into(),
+                String::new(),
+                String::new(),
+                if storno { "1" } else { "0" }.into(),
+                created.clone(),
+                created.clone(),
+                user_id.map(|id| id.to_string()).unwrap_or_default(),
+                user_name.clone().unwrap_or_default(),
+                format_amount(*total_cents),
+                member_name.clone().unwrap_or_default(),
+                member_id.map(|id| id.to_string()).unwrap_or_default(),
+                if member_id.is_some() { "Mitglied" } else { "" }.into(),
+                String::new(),
+                String::new(),
+                String::new(),
+                String::new(),
+                String::new(),
+                note.clone().unwrap_or_default(),
+            ]));
+
+            let mut bon_vat_totals: Vec<(i64, i64, i64, i64)> = Vec::new();
+            let bon_lines = line_stmt
+                .query_map([id], |row| {
+                    Ok((
+                        row.get::<_, Option<i64>>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, Option<i64>>(2)?,
+                        row.get::<_, Option<String>>(3)?,
+                        row.get::<_, i64>(4)?,
+                        row.get::<_, i64>(5)?,
+                        row.get::<_, i64>(6)?,
+                        (
+                            row.get::<_, Option<i64>>(7)?,
+                            row.get::<_, Option<i64>>(8)?,
+                            row.get::<_, Option<i64>>(9)?,
+                        ),
+                    ))
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+            for (index, (product_id, name, type_id, type_name, unit, quantity, total, vat)) in
+                bon_lines.iter().enumerate()
+            {
+                let position = (index + 1).to_string();
+                let vat_key = vat::line_vat_key(vat.0)
+                    .map_err(|e| format!("Bon {}, Position {}: {}", id, position, e))?;
+                let (net, tax) = (vat.1.unwrap_or(*total), vat.2.unwrap_or(0));
+                lines.push(with_z(vec![
+                    id.to_string(),
+                    position.clone(),
+                    String::new(),
+                    name.clone(),
+                    String::new(),
+                    "Umsatz".into(),
+                    String::new(),
+                    "0".into(),
+                    if storno { "1" } else { "0" }.into(),
+                    "0".into(),
+                    product_id.map(|id| id.to_string()).unwrap_or_default(),
+                    String::new(),
+                    type_id.map(|id| id.to_string()).unwrap_or_default(),
+                    type_name.clone().unwrap_or_default(),
+                    format!("{}.000", quantity),
+                    "1.000".into(),
+                    "Stück".into(),
+                    format_amount(*unit),
+                ]));
+                line_vat.push(with_z(vec![
+                    id.to_string(),
+                    position.clone(),
+                    vat_key.to_string(),
+                    format_amount(*total),
+                    format_amount(net),
+                    format_amount(tax),
+                ]));
+                itemamounts.push(with_z(vec![
+                    id.to_string(),
+                    position,
+                    "base_amount".into(),
+                    format_amount(*total),
+                    format_amount(net),
+                    format_amount(tax),
+                ]));
+                add_vat(&mut bon_vat_totals, vat_key, *total, net, tax);
+            }
+            if bon_lines.is_empty() {
+                return Err(format!("Bon {} hat keine Positionen.", id).into());
+            }
+            for (key, gross, net, tax) in &bon_vat_totals {
+                bon_vat.push(with_z(vec![
+                    id.to_string(),
+                    key.to_string(),
+                    format_amount(*gross),
+                    format_amount(*net),
+                    format_amount(*tax),
+                ]));
+                add_vat(&mut sales_by_vat, *key, *gross, *net, *tax);
+            }
+
+            let bon_payments = payment_stmt
+                .query_map([id], |row| {
+                    Ok((row.get::<_, PaymentMethod>(0)?, row.get::<_, i64>(1)?))
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+            for (method, amount_cents) in bon_payments {
+                datapayment.push(with_z(vec![
+                    id.to_string(),
+                    payment_type(method).into(),
+                    method.label().into(),
+                    currency.to_string(),
+                    format_amount(amount_cents),
+                    format_amount(amount_cents),
+                ]));
+                match payment_totals.iter_mut().find(|(m, _)| *m == method) {
+                    Some((_, total)) => *total += amount_cents,
+                    None => payment_totals.push((method, amount_cents)),
+                }
+            }
+
+            if let Some(original) = cancels {
+                let (date, z_number): (String, Option<i64>) =
+                    reference_stmt.query_row([original], |row| Ok((row.get(0)?, row.get(1)?)))?;
+                references.push(with_z(vec![
+                    id.to_string(),
+                    String::new(),
+                    "Transaktion".into(),
+                    "Storno".into(),
+                    date,
+                    kasse.clone(),
+                    z_number.map(|z| z.to_string()).unwrap_or_default(),
+                    original.to_string(),
+                ]));
+            }
+
+            let signature = signature_stmt
+                .query_row([id], |row| {
+                    Ok((
+                        row.get::<_, Option<i64>>(0)?,
+                        row.get::<_, Option<i64>>(1)?,
+                        row.get::<_, Option<String>>(2)?,
+                        row.get::<_, Option<String>>(3)?,
+                        row.get::<_, String>(4)?,
+                        row.get::<_, Option<i64>>(5)?,
+                        row.get::<_, Option<String>>(6)?,
+                        row.get::<_, Option<String>>(7)?,
+                        row.get::<_, String>(8)?,
+                    ))
+                })
+                .optional()?;
+            if let Some((
+                tse_id,
+                number,
+                start,
+                finish,
+                process_type,
+                counter,
+                signature,
+                error,
+                process_data,
+            )) = signature
+            {
+                let text = |value: Option<i64>| value.map(|v| v.to_string()).unwrap_or_default();
+                bon_tse.push(with_z(vec![
+                    id.to_string(),
+                    text(tse_id),
+                    text(number),
+                    start.unwrap_or_default(),
+                    finish.unwrap_or_default(),
+                    process_type,
+                    text(counter),
+                    signature.unwrap_or_default(),
+                    error.unwrap_or_default(),
+                    process_data,
+                ]));
+            }
+        }
+
+        for (key, gross, net, tax) in &sales_by_vat {
+            businesscases.push(with_z(vec![
+                "Umsatz".into(),
+                String::new(),
+                "0".into(),
+                key.to_string(),
+                format_amount(*gross),
+                format_amount(*net),
+                format_amount(*tax),
+            ]));
+        }
+        let movements = conn
+            .prepare(
+                "SELECT kind, COALESCE(SUM(amount_cents), 0)
+                FROM cash_movements WHERE cash_session_id = ?
+                GROUP BY kind ORDER BY kind",
+            )?
+            .query_map(params![closing.session_id], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        let has_cash_movements = !movements.is_empty();
+        let mut cash_movement_cents = 0;
+        for (kind, total) in movements {
+            let (gv_typ, signed) = if kind == "cash_in" {
+                ("Einzahlung", total)
+            } else {
+                ("Auszahlung", -total)
+            };
+            cash_movement_cents += signed;
+            businesscases.push(with_z(vec![
+                gv_typ.into(),
+                String::new(),
+                "0".into(),
+                vat::VAT_KEY_NOT_TAXABLE.to_string(),
+                format_amount(signed),
+                format_amount(signed),
+                format_amount(0),
+            ]));
+        }
+
+        let mut total_payments = 0;
+        let mut cash_payments = cash_movement_cents;
+        for (method, total) in &payment_totals {
+            total_payments += total;
+            if *method == PaymentMethod::Cash {
+                cash_payments += total;
+            }
+            payments.push(with_z(vec![
+                payment_type(*method).into(),
+                method.label().into(),
+                format_amount(*total),
+            ]));
+        }
+        cash_per_currency.push(with_z(vec![
+            currency.to_string(),
+            format_amount(cash_payments),
+        ]));
+
+        let start_id = transactions
+            .first()
+            .map(|t| t.0.to_string())
+            .unwrap_or_default();
+        let end_id = transactions
+            .last()
+            .map(|t| t.0.to_string())
+            .unwrap_or_default();
+        closing_table.push(with_z(vec![
+            closing.business_day.clone(),
+            TAXONOMY_VERSION.into(),
+            start_id,
+            end_id,
+            business.name.clone(),
+            business.street.clone(),
+            business.postal_code.clone(),
+            business.city.clone(),
+            business.country.clone(),
+            business.tax_number.clone(),
+            business.vat_id.clone(),
+            format_amount(total_payments + cash_movement_cents),
+            format_amount(cash_payments),
+        ]));
+        location.push(with_z(vec![
+            business.name.clone(),
+            business.street.clone(),
+            business.postal_code.clone(),
+            business.city.clone(),
+            business.country.clone(),
+            business.vat_id.clone(),
+        ]));
+        cashregister.push(with_z(vec![
+            "Valkyrie Development".into(),
+            "boulderado-tauri".into(),
+            kasse.clone(),
+            "boulderado-tauri".into(),
+            env!("CARGO_PKG_VERSION").into(),
+            currency.to_string(),
+            "0".into(),
+        ]));
+        let devices = device_stmt
+            .query_map([closing.session_id], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, String>(3)?,
+                    row.get::<_, String>(4)?,
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        for (tse_id, serial, algorithm, time_format, public_key) in devices {
+            tse.push(with_z(vec![
+                tse_id.to_string(),
+                serial,
+                algorithm,
+                time_format,
+                "UTF-8".into(),
+                public_key,
+                String::new(),
+                String::new(),
+            ]));
+        }
+        let mut vat_rates = vat_rate_stmt
+            .query_map([closing.session_id], |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        if has_cash_movements
+            && !vat_rates
+                .iter()
+                .any(|(key, _)| *key == vat::VAT_KEY_NOT_TAXABLE)
+        {
+            vat_rates.push((vat::VAT_KEY_NOT_TAXABLE, 0));
+            vat_rates.sort();
+        }
+        for pair in vat_rates.windows(2) {
+            if pair[0].0 == pair[1].0 {
+                return Err(format!(
+                    "Z{}: Steuerschlüssel {} wurde mit mehreren Steuersätzen gebucht.",
+                    closing.z_number, pair[0].0
+                )
+                .into());
+            }
+        }
+        for (key, rate_basis_points) in vat_rates {
+            let description = VAT_KEY_DESCRIPTIONS
+                .iter()
+                .find(|(k, _)| *k == key)
+                .map(|(_, description)| *description)
+                .unwrap_or_default();
+            vat.push(with_z(vec![
+                key.to_string(),
+                format!("{}.{:02}", rate_basis_points / 100, rate_basis_points % 100),
+                description.into(),
+            ]));
+        }
+    }
+
+    Ok(vec![
+        bons,
+        bon_vat,
+        allocation,
+        datapayment,
+        references,
+        bon_tse,
+        lines,
+        line_vat,
+        itemamounts,
+        subitems,
+        closing_table,
+        location,
+        cashregister,
+        slaves,
+        agencies,
+        vat,
+        tse,
+        businesscases,
+        payments,
+        cash_per_currency,
+    ])
+}
+
+fn add_vat(totals: &mut Vec<(i64, i64, i64, i64)>, key: i64, gross: i64, net: i64, tax: i64) {
+    match totals.iter_mut().find(|(k, _, _, _)| *k == key) {
+        Some((_, g, n, t)) => {
+            *g += gross;
+            *n += net;
+            *t += tax;
+        }
+        None => totals.push((key, gross, net, tax)),
+    }
+}
+
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn index_xml(business: &BusinessSettings, tables: &[Table], from: &str, to: &str) -> String {
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\r\n");
+    xml.push_str("<!DOCTYPE DataSet SYSTEM \"gdpdu-01-09-2004.dtd\">\r\n");
+    xml.push_str("<DataSet>\r\n  <Version>1.0</Version>\r\n");
+    xml.push_str(&format!(
+        "  <DataSupplier>\r\n    <Name>{}</Name>\r\n    <Location>{}</Location>\r\n    <Comment>DSFinV-K {}</Comment>\r\n  </DataSupplier>\r\n",
+        xml_escape(&business.name),
+        xml_escape(format!("{} {}", business.postal_code, business.city).trim()),
+        TAXONOMY_VERSION
+    ));
+    xml.push_str("  <Media>\r\n    <Name>DSFinV-K</Name>\r\n");
+    for table in tables {
+        xml.push_str("    <Table>\r\n");
+        xml.push_str(&format!("      <URL>{}</URL>\r\n", table.file));
+        xml.push_str(&format!("      <Name>{}</Name>\r\n", table.name));
+        xml.push_str(&format!(
+            "      <Description>{}</Description>\r\n",
+            xml_escape(table.description)
+        ));
+        xml.push_str(&format!(
+            "      <Validity>\r\n        <Range>\r\n          <From>{}</From>\r\n          <To>{}</To>\r\n        </Range>\r\n        <Format>YYYY-MM-DD</Format>\r\n      </Validity>\r\n",
+            from, to
+        ));
+        xml.push_str("      <DecimalSymbol>.</DecimalSymbol>\r\n");
+        xml.push_str("      <DigitGroupingSymbol>,</DigitGroupingSymbol>\r\n");
+        xml.push_str("      <VariableLength>\r\n");
+        xml.push_str("        <ColumnDelimiter>;</ColumnDelimiter>\r\n");
+        xml.push_str("        <RecordDelimiter>&#13;&#10;</RecordDelimiter>\r\n");
+        xml.push_str("        <TextEncapsulator>\"</TextEncapsulator>\r\n");
+        for (name, column) in table.columns {
+            let kind = match column {
+                Column::Text(max) => format!("<AlphaNumeric/><MaxLength>{}</MaxLength>", max),
+                Column::Integer => "<Numeric/>".to_string(),
+                Column::Decimal(accuracy) => {
+                    format!("<Numeric><Accuracy>{}</Accuracy></Numeric>", accuracy)
+                }
+            };
+            xml.push_str(&format!(
+                "        <VariableColumn>\r\n          <Name>{}</Name>\r\n          {}\r\n        </VariableColumn>\r\n",
+                name, kind
+            ));
+        }
+        xml.push_str("      </VariableLength>\r\n");
+        xml.push_str("    </Table>\r\n");
+    }
+    xml.push_str("  </Media>\r\n</DataSet>\r\n");
+    xml
+}
@@ -0,0 +1,62 @@
+use rusqlite::{Connection, OptionalExtension};
+
+use crate::format_amount;
+
+/// Member whose balance is charged and topped up on behalf of `member_id`: the
+/// payer of a household with a shared wallet, otherwise the member itself.
+pub fn balance_account(conn: &Connection, member_id: i64) -> rusqlite::Result<i64> {
+    let payer: Option<i64> = conn
+        .query_row(
+            "SELECT h.payer_member_id
+            FROM household_members hm
+            JOIN households h ON h.id = hm.household_id
+            WHERE hm.member_id = ? AND h.shared_balance = 1 AND h.payer_member_id IS NOT NULL",
+            [member_id],
+            |row| row.get(0),
+        )
+        .optional()?;
+    Ok(payer.unwrap_or(member_id))
+}
+
+/// Refuses a shared wallet while a linked member other than the payer still
+/// holds a balance of their own, which would otherwise be stranded.
+pub fn check_shared_wallet(conn: &Connection, household_id: i64) -> Result<(), String> {
+    match stray_balance(conn, household_id).map_err(|e| e.to_string())? {
+        Some((member_name, balance_cents)) => Err(format!(
+            "{} hat noch ein eigenes Guthaben von {}. Bitte zuerst auf den Zahler umbuchen.",
+            member_name,
+            format_amount(balance_cents)
+        )),
+        None => Ok(()),
+    }
+}
+
+fn stray_balance(conn: &Connection, household_id: i64) -> rusqlite::Result<Option<(String, i64)>> {
+    conn.query_row(
+        "SELECT m.first_name || ' ' || m.last_name, m.balance_cents
+        FROM household_members hm
+        JOIN households h ON h.id = hm.household_id
+        JOIN members m ON m.id = hm.member_id
+        WHERE hm.household_id = ?
+          AND m.id IS NOT h.payer_member_id
+          AND m.balance_cents <> 0
+        ORDER BY m.last_name, m.first_name
+        LIMIT 1",
+        [household_id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+    .optional()
+}
+
+/// Household the member belongs to; a member can be linked to at most one.
+pub fn membership_of(conn: &Connection, member_id: i64) -> rusqlite::Result<Option<(i64, String)>> {
+    conn.query_row(
+        "SELECT h.id, h.name
+        FROM household_members hm
+        JOIN households h ON h.id = hm.household_id
+        WHERE hm.member_id = ?",
+        [member_id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+    .optional()
+}
@@ -2,14 +2,48 @@ use argon2::{password_hash::SaltString, Argon2, PasswordHash, PasswordHasher, Pa
 use rand_core::OsRng;
 use rusqlite::{params, Connection, OptionalExtension};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::{collections::HashSet, fs, path::PathBuf, sync::Mutex};
 use tauri::{path::BaseDirectory, Manager, State};
 
-struct DatabasePath(PathBuf);
+mod archive;
+mod backup;
+mod calendar;
+mod cards;
+mod certifications;
+mod checkin;
+mod dsfinvk;
+mod households;
+mod member_search;
+mod migrations;
+mod receipt;
+mod sepa;
+pub mod tse;
+mod vat;
+mod waivers;
+
+use tse::{SimulatorTse, Tse, TseInfo, PROCESS_TYPE_RECEIPT};
+use vat::VAT_KEY_NOT_TAXABLE;
+
+struct DatabasePath(Mutex<PathBuf>);
 
 impl DatabasePath {
+    fn new(path: PathBuf) -> Self {
+        Self(Mutex::new(path))
+    }
+
+    fn path(&self) -> PathBuf {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, PathBuf>, String> {
+        self.0
+            .lock()
+            .map_err(|_| "Datenbankpfad ist nicht verfügbar".to_string())
+    }
+
     fn connect(&self) -> rusqlite::Result<Connection> {
-        let conn = Connection::open(&self.0)?;
+        let conn = Connection::open(self.path())?;
         conn.execute_batch("PRAGMA foreign_keys = ON; PRAGMA journal_mode = WAL;")?;
         Ok(conn)
     }
@@ -26,6 +60,9 @@ struct ProductRecord {
     note: Option<String>,
     product_type_id: Option<i64>,
     product_type_name: Option<String>,
+    tax_rate_id: Option<i64>,
+    tax_rate_name: Option<String>,
+    grants_entry: bool,
 }
 
 #[derive(Deserialize)]
@@ -38,6 +75,12 @@ struct ProductPayload {
     icon: Option<String>,
     note: Option<String>,
     product_type_id: Option<i64>,
+    tax_rate_id: Option<i64>,
+    /// An omitted tax rate keeps the stored one on update; this removes it.
+    #[serde(default)]
+    clear_tax_rate: bool,
+    #[serde(default)]
+    grants_entry: bool,
 }
 
 #[derive(Serialize)]
@@ -48,16 +91,316 @@ struct TransactionRecord {
     quantity: i64,
     total_cents: i64,
     description: Option<String>,
+    member_id: Option<i64>,
+    user_id: Option<i64>,
+    cash_session_id: Option<i64>,
+    cancels_transaction_id: Option<i64>,
+    cancelled_by_transaction_id: Option<i64>,
+    created_at: String,
+    lines: Vec<TransactionLineRecord>,
+    payments: Vec<TransactionPaymentRecord>,
+    signature: Option<TransactionSignatureRecord>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TransactionLineRecord {
+    id: i64,
+    transaction_id: i64,
+    product_id: Option<i64>,
+    product_name: String,
+    product_type_id: Option<i64>,
+    product_type_name: Option<String>,
+    unit_price_cents: i64,
+    quantity: i64,
+    line_total_cents: i64,
+    tax_rate_id: Option<i64>,
+    tax_rate_name: Option<String>,
+    vat_key: Option<i64>,
+    vat_rate_basis_points: Option<i64>,
+    net_cents: Option<i64>,
+    tax_cents: Option<i64>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TransactionPaymentRecord {
+    id: i64,
+    transaction_id: i64,
+    method: PaymentMethod,
+    amount_cents: i64,
+    member_id: Option<i64>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TransactionSignatureRecord {
+    tse_serial: Option<String>,
+    tse_transaction_number: Option<i64>,
+    signature_counter: Option<i64>,
+    start_time: Option<String>,
+    finish_time: Option<String>,
+    process_type: String,
+    process_data: String,
+    signature: Option<String>,
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PrintReceiptPayload {
+    transaction_id: i64,
+    copy: Option<bool>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SaveReceiptPdfPayload {
+    transaction_id: i64,
+    target_dir: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PendingTseSignatureRecord {
+    id: i64,
+    transaction_id: i64,
+    tse_serial: String,
+    tse_transaction_number: i64,
+    signature_counter: i64,
+    finish_time: String,
+    process_data: String,
+    signature: String,
+    error: String,
     created_at: String,
+    resolved_at: Option<String>,
+    resolution: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ResolvePendingTseSignaturePayload {
+    id: i64,
+    resolution: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ExportTsePayload {
+    target_dir: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MoveDatabasePayload {
+    target_path: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DatabaseMoveRecord {
+    previous_path: String,
+    path: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RestoreBackupPayload {
+    file_name: String,
 }
 
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
-struct TransactionPayload {
+struct ExportBackupArchivePayload {
+    target_dir: String,
+    password: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RestoreBackupArchivePayload {
+    archive_path: String,
+    password: String,
+    restore_settings: Option<bool>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CancelTransactionPayload {
+    transaction_id: i64,
+    reason: String,
+    credit_override: Option<CreditOverridePayload>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct JournalVerification {
+    entry_count: i64,
+    last_hash: Option<String>,
+    valid: bool,
+    issues: Vec<JournalIssue>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct JournalIssue {
+    entry_id: Option<i64>,
+    transaction_id: Option<i64>,
+    problem: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct JournalPayload {
+    entry_type: String,
+    transaction_id: i64,
     product_id: Option<i64>,
     quantity: i64,
     total_cents: i64,
     description: Option<String>,
+    member_id: Option<i64>,
+    user_id: Option<i64>,
+    cash_session_id: Option<i64>,
+    cancels_transaction_id: Option<i64>,
+    created_at: String,
+    lines: Vec<JournalLine>,
+    payments: Vec<JournalPayment>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct JournalLine {
+    product_name: String,
+    unit_price_cents: i64,
+    quantity: i64,
+    line_total_cents: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    vat_key: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    vat_rate_basis_points: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    net_cents: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tax_cents: Option<i64>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct JournalPayment {
+    method: PaymentMethod,
+    amount_cents: i64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PaymentTotalRecord {
+    method: PaymentMethod,
+    label: String,
+    payment_count: i64,
+    total_cents: i64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CashSessionRecord {
+    id: i64,
+    status: String,
+    opened_by: i64,
+    opened_by_name: Option<String>,
+    opened_at: String,
+    opening_float_cents: i64,
+    closed_by: Option<i64>,
+    closed_at: Option<String>,
+    counted_cash_cents: Option<i64>,
+    expected_cash_cents: Option<i64>,
+    difference_cents: Option<i64>,
+    z_number: Option<i64>,
+    notes: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CashMovementRecord {
+    id: i64,
+    cash_session_id: i64,
+    kind: String,
+    amount_cents: i64,
+    reason: Option<String>,
+    user_id: i64,
+    created_at: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CashReport {
+    kind: String,
+    cash_session_id: i64,
+    z_number: Option<i64>,
+    opened_by: i64,
+    opened_at: String,
+    closed_by: Option<i64>,
+    closed_at: Option<String>,
+    created_at: String,
+    opening_float_cents: i64,
+    transaction_count: i64,
+    sales_total_cents: i64,
+    cash_in_cents: i64,
+    cash_out_cents: i64,
+    payments: Vec<PaymentReconciliation>,
+    #[serde(default)]
+    vat: Vec<VatSummaryRecord>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PaymentReconciliation {
+    method: PaymentMethod,
+    label: String,
+    payment_count: i64,
+    sales_cents: i64,
+    expected_cents: i64,
+    counted_cents: Option<i64>,
+    difference_cents: Option<i64>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OpenCashSessionPayload {
+    opening_float_cents: i64,
+    notes: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CashMovementPayload {
+    kind: String,
+    amount_cents: i64,
+    reason: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CloseCashSessionPayload {
+    counted: Vec<PaymentPayload>,
+    notes: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ExportDsfinvkPayload {
+    from_date: Option<String>,
+    to_date: Option<String>,
+    z_numbers: Option<Vec<i64>>,
+    target_dir: String,
+    zip: Option<bool>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DsfinvkExportRecord {
+    path: String,
+    closing_count: usize,
+    transaction_count: usize,
 }
 
 #[derive(Serialize)]
@@ -66,6 +409,8 @@ struct ProductTypeRecord {
     id: i64,
     name: String,
     color: Option<String>,
+    tax_rate_id: Option<i64>,
+    tax_rate_name: Option<String>,
     created_at: String,
     updated_at: String,
 }
@@ -76,6 +421,70 @@ struct ProductTypePayload {
     id: Option<i64>,
     name: String,
     color: Option<String>,
+    tax_rate_id: Option<i64>,
+    #[serde(default)]
+    clear_tax_rate: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TaxRateRecord {
+    id: i64,
+    name: String,
+    vat_key: i64,
+    is_default: bool,
+    current_rate_basis_points: Option<i64>,
+    periods: Vec<TaxRatePeriodRecord>,
+    created_at: String,
+    updated_at: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TaxRatePeriodRecord {
+    id: i64,
+    tax_rate_id: i64,
+    rate_basis_points: i64,
+    valid_from: String,
+    valid_to: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TaxRatePayload {
+    id: Option<i64>,
+    name: String,
+    vat_key: i64,
+    #[serde(default)]
+    is_default: bool,
+    periods: Vec<TaxRatePeriodPayload>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TaxRatePeriodPayload {
+    rate_basis_points: i64,
+    valid_from: String,
+    valid_to: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct VatSummaryRecord {
+    vat_key: Option<i64>,
+    tax_rate_name: Option<String>,
+    rate_basis_points: Option<i64>,
+    line_count: i64,
+    gross_cents: i64,
+    net_cents: i64,
+    tax_cents: i64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct VatSummaryPayload {
+    date: Option<String>,
+    cash_session_id: Option<i64>,
 }
 
 #[derive(Serialize)]
@@ -89,6 +498,11 @@ struct MemberRecord {
     status: String,
     notes: Option<String>,
     balance_cents: i64,
+    credit_limit_cents: Option<i64>,
+    iban: Option<String>,
+    bic: Option<String>,
+    debit_returned_at: Option<String>,
+    birth_date: Option<String>,
     created_at: String,
     updated_at: String,
 }
@@ -103,7 +517,68 @@ struct MemberPayload {
     phone: Option<String>,
     status: Option<String>,
     notes: Option<String>,
-    balance_cents: Option<i64>,
+    iban: Option<String>,
+    bic: Option<String>,
+    birth_date: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MemberSearchRequest {
+    query: Option<String>,
+    status: Option<String>,
+    membership_id: Option<i64>,
+    expiring_within_days: Option<u32>,
+    #[serde(default)]
+    negative_balance: bool,
+    cursor: Option<String>,
+    limit: Option<u32>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct MemberSearchPage {
+    items: Vec<MemberRecord>,
+    total_count: i64,
+    next_cursor: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct BalanceEntryRecord {
+    id: i64,
+    member_id: i64,
+    kind: String,
+    amount_cents: i64,
+    balance_after_cents: i64,
+    transaction_id: Option<i64>,
+    reason: Option<String>,
+    user_id: Option<i64>,
+    user_name: Option<String>,
+    created_at: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TopUpBalancePayload {
+    member_id: i64,
+    amount_cents: i64,
+    payment_method: PaymentMethod,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AdjustBalancePayload {
+    member_id: i64,
+    amount_cents: i64,
+    reason: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MemberCreditLimitPayload {
+    member_id: i64,
+    credit_limit_cents: Option<i64>,
 }
 
 #[derive(Serialize)]
@@ -115,10 +590,20 @@ struct MembershipRecord {
     price_cents: Option<i64>,
     duration_days: Option<i64>,
     max_uses: Option<i64>,
+    tax_rate_id: Option<i64>,
+    allowed_weekdays: Option<Vec<u32>>,
+    time_windows: Vec<TimeWindow>,
     created_at: String,
     updated_at: String,
 }
 
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TimeWindow {
+    start_time: String,
+    end_time: String,
+}
+
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct MembershipPayload {
@@ -128,83 +613,431 @@ struct MembershipPayload {
     price_cents: Option<i64>,
     duration_days: Option<i64>,
     max_uses: Option<i64>,
+    tax_rate_id: Option<i64>,
+    #[serde(default)]
+    clear_tax_rate: bool,
+    allowed_weekdays: Option<Vec<u32>>,
+    #[serde(default)]
+    time_windows: Vec<TimeWindow>,
 }
 
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
 struct CheckinRecord {
     id: i64,
-    member_id: i64,
+    member_id: Option<i64>,
     member_name: String,
     membership_name: Option<String>,
+    reentry: bool,
+    guest: bool,
+    waiver_reference: Option<String>,
     created_at: String,
 }
 
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
-struct CheckinPayload {
-    member_id: i64,
+struct CardCheckinPayload {
+    token: String,
+    member_membership_id: Option<i64>,
+    #[serde(default)]
+    rope_area: bool,
 }
 
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
-struct MemberMembershipRecord {
+struct CertificationRecord {
     id: i64,
     member_id: i64,
-    membership_id: i64,
-    membership_name: String,
-    remaining_uses: Option<i64>,
-    start_date: Option<String>,
-    end_date: Option<String>,
+    kind: String,
+    issued_on: String,
+    expires_on: Option<String>,
+    issued_by: Option<i64>,
+    issued_by_name: Option<String>,
+    notes: Option<String>,
+    revoked_at: Option<String>,
+    revoke_reason: Option<String>,
     created_at: String,
 }
 
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
-struct AssignMemberMembershipPayload {
+struct CertificationPayload {
     member_id: i64,
-    membership_id: i64,
+    kind: String,
+    issued_on: Option<String>,
+    expires_on: Option<String>,
+    notes: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RevokeCertificationPayload {
+    certification_id: i64,
+    reason: String,
 }
 
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
-struct RoleRecord {
+struct HouseholdRecord {
     id: i64,
     name: String,
+    payer_member_id: Option<i64>,
+    payer_name: Option<String>,
+    shared_balance: bool,
+    shared_memberships: bool,
+    wallet_balance_cents: Option<i64>,
+    members: Vec<HouseholdMemberRecord>,
+    created_at: String,
 }
 
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
-struct UserRecord {
-    id: i64,
-    username: String,
-    display_name: String,
-    role: String,
-    active: bool,
-    created_at: String,
-    updated_at: String,
+struct HouseholdMemberRecord {
+    member_id: i64,
+    first_name: String,
+    last_name: String,
+    balance_cents: i64,
 }
 
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
-struct UserPayload {
+struct HouseholdPayload {
     id: Option<i64>,
-    username: String,
-    display_name: String,
-    password: Option<String>,
-    role_id: i64,
-    active: bool,
+    name: String,
+    payer_member_id: Option<i64>,
+    #[serde(default)]
+    shared_balance: bool,
+    #[serde(default)]
+    shared_memberships: bool,
 }
 
 #[derive(Deserialize)]
-struct LoginPayload {
-    username: String,
-    password: String,
+#[serde(rename_all = "camelCase")]
+struct HouseholdMemberPayload {
+    household_id: i64,
+    member_id: i64,
 }
 
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
-struct LoginResponse {
+struct MemberCardRecord {
+    id: i64,
+    member_id: i64,
+    token: String,
+    kind: String,
+    status: String,
+    label: Option<String>,
+    status_changed_at: Option<String>,
+    created_at: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MemberCardPayload {
+    member_id: i64,
+    token: Option<String>,
+    kind: String,
+    label: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MemberCardStatusPayload {
+    card_id: i64,
+    status: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GuestEntryRecord {
+    transaction_id: i64,
+    transaction_line_id: i64,
+    product_name: String,
+    quantity: i64,
+    used: i64,
+    sold_at: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GuestCheckinPayload {
+    transaction_line_id: i64,
+    guest_name: Option<String>,
+    waiver_reference: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CheckinStatisticsRequest {
+    from: String,
+    to: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CheckinStatisticsRecord {
+    date: String,
+    member_checkins: i64,
+    guest_checkins: i64,
+    reentries: i64,
+    unique_members: i64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CheckinPayload {
+    member_id: i64,
+    member_membership_id: Option<i64>,
+    #[serde(default)]
+    rope_area: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CheckinResult {
+    checkin_id: i64,
+    member_id: i64,
+    member_name: String,
+    member_membership_id: Option<i64>,
+    membership_name: Option<String>,
+    reentry: bool,
+    remaining_uses: Option<i64>,
+    certifications: Vec<certifications::CertificationSummary>,
+    warnings: Vec<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct WaiverTextRecord {
+    id: i64,
+    version: String,
+    body: String,
+    created_at: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct WaiverTextPayload {
+    version: String,
+    body: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct WaiverRecord {
+    id: i64,
+    member_id: i64,
+    waiver_text_id: i64,
+    version: String,
+    signed_on: String,
+    signer_name: String,
+    guardian_name: Option<String>,
+    has_signature: bool,
+    recorded_by: Option<i64>,
+    created_at: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct WaiverPayload {
+    member_id: i64,
+    waiver_text_id: Option<i64>,
+    signed_on: Option<String>,
+    signer_name: String,
+    guardian_name: Option<String>,
+    signature: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct WaiverGapRecord {
+    member_id: i64,
+    member_name: String,
+    email: Option<String>,
+    status: String,
+    signed_version: Option<String>,
+    signed_on: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct MemberMembershipRecord {
+    id: i64,
+    member_id: i64,
+    membership_id: i64,
+    membership_name: String,
+    remaining_uses: Option<i64>,
+    start_date: Option<String>,
+    end_date: Option<String>,
+    frozen_since: Option<String>,
+    renewed_from_id: Option<i64>,
+    created_at: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AssignMemberMembershipPayload {
+    member_id: i64,
+    membership_id: i64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MemberMembershipChangePayload {
+    member_membership_id: i64,
+    reason: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ExtendMemberMembershipPayload {
+    member_membership_id: i64,
+    days: i64,
+    reason: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ContractRecord {
+    id: i64,
+    member_id: i64,
+    member_name: String,
+    membership_id: i64,
+    membership_name: String,
+    amount_cents: i64,
+    interval_months: i64,
+    mandate_reference: String,
+    mandate_signed_on: String,
+    start_date: String,
+    end_date: Option<String>,
+    cancelled_at: Option<String>,
+    next_due_date: String,
+    created_at: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ContractPayload {
+    id: Option<i64>,
+    member_id: i64,
+    membership_id: i64,
+    amount_cents: i64,
+    interval_months: i64,
+    mandate_reference: String,
+    mandate_signed_on: String,
+    start_date: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CancelContractPayload {
+    contract_id: i64,
+    end_date: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BillingRunPayload {
+    until: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct BillingRunRecord {
+    invoice_count: i64,
+    total_cents: i64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ContractInvoiceRecord {
+    id: i64,
+    contract_id: i64,
+    member_id: i64,
+    member_name: String,
+    period_start: String,
+    period_end: String,
+    due_date: String,
+    amount_cents: i64,
+    status: String,
+    end_to_end_id: Option<String>,
+    sequence_type: Option<String>,
+    return_reason: Option<String>,
+    returned_at: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ContractInvoicesRequest {
+    status: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ExportSepaPayload {
+    target_dir: String,
+    collection_date: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ImportSepaReturnsPayload {
+    file_path: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct MemberMembershipEventRecord {
+    id: i64,
+    member_membership_id: i64,
+    kind: String,
+    days: Option<i64>,
+    previous_end_date: Option<String>,
+    new_end_date: Option<String>,
+    reason: Option<String>,
+    user_name: Option<String>,
+    created_at: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RoleRecord {
+    id: i64,
+    name: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct UserRecord {
+    id: i64,
+    username: String,
+    display_name: String,
+    role: String,
+    active: bool,
+    created_at: String,
+    updated_at: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UserPayload {
+    id: Option<i64>,
+    username: String,
+    display_name: String,
+    password: Option<String>,
+    role_id: i64,
+    active: bool,
+}
+
+#[derive(Deserialize)]
+struct LoginPayload {
+    username: String,
+    password: String,
+}
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct LoginResponse {
     id: i64,
     username: String,
     display_name: String,
@@ -228,7 +1061,8 @@ struct BucketRecord {
 struct BucketItemRecord {
     id: i64,
     bucket_id: i64,
-    product_id: i64,
+    product_id: Option<i64>,
+    membership_id: Option<i64>,
     product_name: String,
     quantity: i64,
     price_cents: i64,
@@ -264,6 +1098,14 @@ struct AddBucketItemPayload {
     quantity: Option<i64>,
 }
 
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AddBucketMembershipPayload {
+    bucket_id: i64,
+    membership_id: i64,
+    quantity: Option<i64>,
+}
+
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct BucketIdPayload {
@@ -277,6 +1119,112 @@ struct CheckoutBucketPayload {
     member_id: Option<i64>,
     use_balance: bool,
     payment_method: Option<String>,
+    payments: Option<Vec<PaymentPayload>>,
+    credit_override: Option<CreditOverridePayload>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CreditOverridePayload {
+    username: String,
+    password: String,
+    reason: String,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(tag = "code", rename_all = "camelCase")]
+enum CheckoutError {
+    Failed {
+        message: String,
+    },
+    #[serde(rename_all = "camelCase")]
+    InsufficientBalance {
+        message: String,
+        member_id: i64,
+        balance_cents: i64,
+        credit_limit_cents: i64,
+        required_cents: i64,
+        shortfall_cents: i64,
+    },
+}
+
+impl From<String> for CheckoutError {
+    fn from(message: String) -> Self {
+        CheckoutError::Failed { message }
+    }
+}
+
+impl From<&str> for CheckoutError {
+    fn from(message: &str) -> Self {
+        CheckoutError::Failed {
+            message: message.to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PaymentPayload {
+    method: PaymentMethod,
+    amount_cents: i64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+enum PaymentMethod {
+    Cash,
+    Card,
+    Balance,
+}
+
+impl PaymentMethod {
+    const ALL: [PaymentMethod; 3] = [
+        PaymentMethod::Cash,
+        PaymentMethod::Card,
+        PaymentMethod::Balance,
+    ];
+
+    fn code(self) -> &'static str {
+        match self {
+            PaymentMethod::Cash => "cash",
+            PaymentMethod::Card => "card",
+            PaymentMethod::Balance => "balance",
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            PaymentMethod::Cash => "Bar",
+            PaymentMethod::Card => "Karte",
+            PaymentMethod::Balance => "Guthaben",
+        }
+    }
+
+    fn from_code(code: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|method| method.code() == code)
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        Self::ALL.into_iter().find(|method| {
+            method.code().eq_ignore_ascii_case(value) || method.label().eq_ignore_ascii_case(value)
+        })
+    }
+}
+
+impl rusqlite::types::ToSql for PaymentMethod {
+    fn to_sql(&self) -> rusqlite::Result<rusqlite::types::ToSqlOutput<'_>> {
+        Ok(self.code().into())
+    }
+}
+
+impl rusqlite::types::FromSql for PaymentMethod {
+    fn column_result(value: rusqlite::types::ValueRef<'_>) -> rusqlite::types::FromSqlResult<Self> {
+        let code = value.as_str()?;
+        PaymentMethod::from_code(code).ok_or_else(|| {
+            rusqlite::types::FromSqlError::Other(format!("unknown payment method {code}").into())
+        })
+    }
 }
 
 struct DefaultProduct {
@@ -416,14 +1364,54 @@ const DEFAULT_PRODUCTS: &[DefaultProduct] = &[
     },
 ];
 
-// Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
-#[tauri::command]
-fn greet(name: &str) -> String {
+struct DefaultTaxRate {
+    name: &'static str,
+    vat_key: i64,
+    is_default: bool,
+    periods: &'static [(i64, &'static str, Option<&'static str>)],
+}
+
+const DEFAULT_TAX_RATES: &[DefaultTaxRate] = &[
+    DefaultTaxRate {
+        name: "Regelsatz",
+        vat_key: 1,
+        is_default: true,
+        periods: &[
+            (1900, "2007-01-01", Some("2020-06-30")),
+            (1600, "2020-07-01", Some("2020-12-31")),
+            (1900, "2021-01-01", None),
+        ],
+    },
+    DefaultTaxRate {
+        name: "Ermäßigt",
+        vat_key: 2,
+        is_default: false,
+        periods: &[
+            (700, "1983-07-01", Some("2020-06-30")),
+            (500, "2020-07-01", Some("2020-12-31")),
+            (700, "2021-01-01", None),
+        ],
+    },
+    DefaultTaxRate {
+        name: "Steuerfrei",
+        vat_key: 6,
+        is_default: false,
+        periods: &[(0, "1970-01-01", None)],
+    },
+];
+
+// Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
+#[tauri::command]
+fn greet(name: &str) -> String {
     format!("Hello, {}! You've been greeted from Rust!", name)
 }
 
 #[tauri::command]
-fn list_products(db: State<DatabasePath>) -> Result<Vec<ProductRecord>, String> {
+fn list_products(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+) -> Result<Vec<ProductRecord>, String> {
+    session.require(Role::User)?;
     let conn = db.connect().map_err(|e| e.to_string())?;
     let mut stmt = conn
         .prepare(
@@ -434,9 +1422,13 @@ fn list_products(db: State<DatabasePath>) -> Result<Vec<ProductRecord>, String>
                 p.icon,
                 p.note,
                 p.product_type_id,
-                pt.name as product_type_name
+                pt.name as product_type_name,
+                p.tax_rate_id,
+                tr.name as tax_rate_name,
+                p.grants_entry
             FROM products p
             LEFT JOIN product_types pt ON pt.id = p.product_type_id
+            LEFT JOIN tax_rates tr ON tr.id = p.tax_rate_id
             ORDER BY p.name COLLATE NOCASE",
         )
         .map_err(|e| e.to_string())?;
@@ -452,6 +1444,9 @@ fn list_products(db: State<DatabasePath>) -> Result<Vec<ProductRecord>, String>
                 note: row.get(5)?,
                 product_type_id: row.get(6)?,
                 product_type_name: row.get(7)?,
+                tax_rate_id: row.get(8)?,
+                tax_rate_name: row.get(9)?,
+                grants_entry: row.get(10)?,
             })
         })
         .map_err(|e| e.to_string())?;
@@ -461,11 +1456,16 @@ fn list_products(db: State<DatabasePath>) -> Result<Vec<ProductRecord>, String>
 }
 
 #[tauri::command]
-fn save_product(db: State<DatabasePath>, payload: ProductPayload) -> Result<i64, String> {
+fn save_product(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    payload: ProductPayload,
+) -> Result<i64, String> {
+    session.require(Role::Manager)?;
     let conn = db.connect().map_err(|e| e.to_string())?;
     if let Some(id) = payload.id {
         conn.execute(
-            "UPDATE products SET name = ?, price_cents = ?, accent = ?, icon = ?, note = ?, product_type_id = ? WHERE id = ?",
+            "UPDATE products SET name = ?, price_cents = ?, accent = ?, icon = ?, note = ?, product_type_id = ?, tax_rate_id = CASE WHEN ? THEN NULL ELSE COALESCE(?, tax_rate_id) END, grants_entry = ? WHERE id = ?",
             params![
                 payload.name,
                 payload.price_cents,
@@ -473,6 +1473,9 @@ fn save_product(db: State<DatabasePath>, payload: ProductPayload) -> Result<i64,
                 payload.icon,
                 payload.note,
                 payload.product_type_id,
+                payload.clear_tax_rate,
+                payload.tax_rate_id,
+                payload.grants_entry,
                 id
             ],
         )
@@ -480,14 +1483,16 @@ fn save_product(db: State<DatabasePath>, payload: ProductPayload) -> Result<i64,
         Ok(id)
     } else {
         conn.execute(
-            "INSERT INTO products (name, price_cents, accent, icon, note, product_type_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            "INSERT INTO products (name, price_cents, accent, icon, note, product_type_id, tax_rate_id, grants_entry) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
             params![
                 payload.name,
                 payload.price_cents,
                 payload.accent,
                 payload.icon,
                 payload.note,
-                payload.product_type_id
+                payload.product_type_id,
+                payload.tax_rate_id,
+                payload.grants_entry
             ],
         )
         .map_err(|e| e.to_string())?;
@@ -496,7 +1501,12 @@ fn save_product(db: State<DatabasePath>, payload: ProductPayload) -> Result<i64,
 }
 
 #[tauri::command]
-fn delete_product(db: State<DatabasePath>, id: i64) -> Result<(), String> {
+fn delete_product(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    id: i64,
+) -> Result<(), String> {
+    session.require(Role::Manager)?;
     let conn = db.connect().map_err(|e| e.to_string())?;
     conn.execute("DELETE FROM products WHERE id = ?", [id])
         .map_err(|e| e.to_string())
@@ -504,51 +1514,38 @@ fn delete_product(db: State<DatabasePath>, id: i64) -> Result<(), String> {
 }
 
 #[tauri::command]
-fn record_transaction(db: State<DatabasePath>, payload: TransactionPayload) -> Result<(), String> {
-    let conn = db.connect().map_err(|e| e.to_string())?;
-    conn.execute(
-        "INSERT INTO transactions (product_id, quantity, total_cents, description) \
-        VALUES (?1, ?2, ?3, ?4)",
-        params![
-            payload.product_id,
-            payload.quantity.max(1),
-            payload.total_cents,
-            payload.description
-        ],
-    )
-    .map_err(|e| e.to_string())?;
-    Ok(())
-}
-
-#[tauri::command]
-fn list_transactions(db: State<DatabasePath>) -> Result<Vec<TransactionRecord>, String> {
+fn list_transactions(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+) -> Result<Vec<TransactionRecord>, String> {
+    session.require(Role::User)?;
     let conn = db.connect().map_err(|e| e.to_string())?;
     let mut stmt = conn
-        .prepare(
-            "SELECT id, product_id, quantity, total_cents, description, created_at \
-            FROM transactions ORDER BY id DESC LIMIT 50",
-        )
+        .prepare(&format!(
+            "{} ORDER BY t.id DESC LIMIT 50",
+            TRANSACTION_SELECT
+        ))
         .map_err(|e| e.to_string())?;
 
     let rows = stmt
-        .query_map([], |row| {
-            Ok(TransactionRecord {
-                id: row.get(0)?,
-                product_id: row.get(1)?,
-                quantity: row.get(2)?,
-                total_cents: row.get(3)?,
-                description: row.get(4)?,
-                created_at: row.get(5)?,
-            })
-        })
+        .query_map([], map_transaction_row)
         .map_err(|e| e.to_string())?;
 
-    rows.collect::<Result<Vec<_>, _>>()
-        .map_err(|e| e.to_string())
+    let mut records = rows
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    attach_transaction_lines(&conn, &mut records).map_err(|e| e.to_string())?;
+    attach_transaction_payments(&conn, &mut records).map_err(|e| e.to_string())?;
+    attach_transaction_signatures(&conn, &mut records).map_err(|e| e.to_string())?;
+    Ok(records)
 }
 
 #[tauri::command]
-fn list_buckets(db: State<DatabasePath>) -> Result<Vec<BucketRecord>, String> {
+fn list_buckets(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+) -> Result<Vec<BucketRecord>, String> {
+    session.require(Role::User)?;
     let conn = db.connect().map_err(|e| e.to_string())?;
     let mut stmt = conn
         .prepare(
@@ -591,8 +1588,10 @@ fn list_buckets(db: State<DatabasePath>) -> Result<Vec<BucketRecord>, String> {
 #[tauri::command]
 fn create_bucket(
     db: State<DatabasePath>,
+    session: State<SessionState>,
     _payload: Option<CreateBucketPayload>,
 ) -> Result<i64, String> {
+    session.require(Role::User)?;
     let conn = db.connect().map_err(|e| e.to_string())?;
     let name = generate_bucket_name(&conn).map_err(|e| e.to_string())?;
     conn.execute("INSERT INTO buckets (name) VALUES (?1)", params![name])
@@ -601,7 +1600,12 @@ fn create_bucket(
 }
 
 #[tauri::command]
-fn rename_bucket(db: State<DatabasePath>, payload: RenameBucketPayload) -> Result<(), String> {
+fn rename_bucket(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    payload: RenameBucketPayload,
+) -> Result<(), String> {
+    session.require(Role::User)?;
     let conn = db.connect().map_err(|e| e.to_string())?;
     conn.execute(
         "UPDATE buckets SET name = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?",
@@ -614,8 +1618,10 @@ fn rename_bucket(db: State<DatabasePath>, payload: RenameBucketPayload) -> Resul
 #[tauri::command]
 fn get_bucket_items(
     db: State<DatabasePath>,
+    session: State<SessionState>,
     payload: BucketItemsRequest,
 ) -> Result<Vec<BucketItemRecord>, String> {
+    session.require(Role::User)?;
     let conn = db.connect().map_err(|e| e.to_string())?;
     let mut stmt = conn
         .prepare(
@@ -624,6 +1630,7 @@ fn get_bucket_items(
             bi.id,
             bi.bucket_id,
             bi.product_id,
+            bi.membership_id,
             bi.product_name,
             bi.quantity,
             bi.price_cents,
@@ -645,13 +1652,14 @@ fn get_bucket_items(
                 id: row.get(0)?,
                 bucket_id: row.get(1)?,
                 product_id: row.get(2)?,
-                product_name: row.get(3)?,
-                quantity: row.get(4)?,
-                price_cents: row.get(5)?,
-                line_total_cents: row.get(6)?,
-                accent: row.get(7)?,
-                icon: row.get(8)?,
-                note: row.get(9)?,
+                membership_id: row.get(3)?,
+                product_name: row.get(4)?,
+                quantity: row.get(5)?,
+                price_cents: row.get(6)?,
+                line_total_cents: row.get(7)?,
+                accent: row.get(8)?,
+                icon: row.get(9)?,
+                note: row.get(10)?,
             })
         })
         .map_err(|e| e.to_string())?;
@@ -663,8 +1671,10 @@ fn get_bucket_items(
 #[tauri::command]
 fn add_product_to_bucket(
     db: State<DatabasePath>,
+    session: State<SessionState>,
     payload: AddBucketItemPayload,
 ) -> Result<(), String> {
+    session.require(Role::User)?;
     let conn = db.connect().map_err(|e| e.to_string())?;
 
     let product: Option<(String, i64)> = conn
@@ -709,7 +1719,64 @@ fn add_product_to_bucket(
 }
 
 #[tauri::command]
-fn close_bucket(db: State<DatabasePath>, payload: BucketIdPayload) -> Result<(), String> {
+fn add_membership_to_bucket(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    payload: AddBucketMembershipPayload,
+) -> Result<(), String> {
+    session.require(Role::User)?;
+    let conn = db.connect().map_err(|e| e.to_string())?;
+
+    let membership: Option<(String, Option<i64>)> = conn
+        .query_row(
+            "SELECT membership_type, price_cents FROM memberships WHERE id = ?",
+            [payload.membership_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+
+    let (membership_name, price_cents) =
+        membership.ok_or_else(|| "Mitgliedschaft nicht gefunden".to_string())?;
+    let price_cents =
+        price_cents.ok_or_else(|| format!("Für {} ist kein Preis hinterlegt.", membership_name))?;
+    let quantity = payload.quantity.unwrap_or(1).max(1);
+
+    conn.execute(
+        "
+        INSERT INTO bucket_items (bucket_id, membership_id, product_name, quantity, price_cents)
+        VALUES (?1, ?2, ?3, ?4, ?5)
+        ON CONFLICT(bucket_id, membership_id) DO UPDATE SET
+            quantity = bucket_items.quantity + excluded.quantity,
+            price_cents = excluded.price_cents,
+            product_name = excluded.product_name
+        ",
+        params![
+            payload.bucket_id,
+            payload.membership_id,
+            membership_name,
+            quantity,
+            price_cents
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+
+    conn.execute(
+        "UPDATE buckets SET updated_at = CURRENT_TIMESTAMP WHERE id = ?",
+        [payload.bucket_id],
+    )
+    .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+#[tauri::command]
+fn close_bucket(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    payload: BucketIdPayload,
+) -> Result<(), String> {
+    session.require(Role::User)?;
     let conn = db.connect().map_err(|e| e.to_string())?;
     let updated = conn
         .execute(
@@ -720,17 +1787,28 @@ fn close_bucket(db: State<DatabasePath>, payload: BucketIdPayload) -> Result<(),
     if updated == 0 {
         return Err("Bucket nicht gefunden".into());
     }
-    conn.execute("DELETE FROM bucket_items WHERE bucket_id = ?", [payload.bucket_id])
-        .map_err(|e| e.to_string())?;
+    conn.execute(
+        "DELETE FROM bucket_items WHERE bucket_id = ?",
+        [payload.bucket_id],
+    )
+    .map_err(|e| e.to_string())?;
     Ok(())
 }
 
 #[tauri::command]
-fn delete_bucket(db: State<DatabasePath>, payload: BucketIdPayload) -> Result<(), String> {
+fn delete_bucket(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    payload: BucketIdPayload,
+) -> Result<(), String> {
+    session.require(Role::User)?;
     let mut conn = db.connect().map_err(|e| e.to_string())?;
     let tx = conn.transaction().map_err(|e| e.to_string())?;
-    tx.execute("DELETE FROM bucket_items WHERE bucket_id = ?", [payload.bucket_id])
-        .map_err(|e| e.to_string())?;
+    tx.execute(
+        "DELETE FROM bucket_items WHERE bucket_id = ?",
+        [payload.bucket_id],
+    )
+    .map_err(|e| e.to_string())?;
     let removed = tx
         .execute("DELETE FROM buckets WHERE id = ?", [payload.bucket_id])
         .map_err(|e| e.to_string())?;
@@ -740,11 +1818,206 @@ fn delete_bucket(db: State<DatabasePath>, payload: BucketIdPayload) -> Result<()
     tx.commit().map_err(|e| e.to_string())
 }
 
+/// Copies the bucket into sale lines with the tax rate valid today. Lines without a
+/// rate of their own fall back to the default rate; a line without any rate is refused.
+fn insert_sale_lines(conn: &Connection, transaction_id: i64, bucket_id: i64) -> Result<(), String> {
+    conn.execute(
+        "
+        INSERT INTO transaction_lines (
+            transaction_id,
+            product_id,
+            membership_id,
+            product_name,
+            product_type_id,
+            product_type_name,
+            unit_price_cents,
+            quantity,
+            line_total_cents,
+            tax_rate_id,
+            tax_rate_name,
+            vat_key,
+            vat_rate_basis_points,
+            net_cents,
+            tax_cents
+        )
+        SELECT ?1,
+               product_id,
+               membership_id,
+               product_name,
+               product_type_id,
+               product_type_name,
+               price_cents,
+               quantity,
+               line_total_cents,
+               tax_rate_id,
+               tax_rate_name,
+               vat_key,
+               rate_basis_points,
+               CAST(ROUND(line_total_cents * 10000.0 / (10000 + rate_basis_points)) AS INTEGER),
+               line_total_cents
+                   - CAST(ROUND(line_total_cents * 10000.0 / (10000 + rate_basis_points)) AS INTEGER)
+        FROM (
+            SELECT bi.id,
+                   bi.product_id,
+                   bi.membership_id,
+                   bi.product_name,
+                   p.product_type_id,
+                   pt.name AS product_type_name,
+                   bi.price_cents,
+                   bi.quantity,
+                   bi.quantity * bi.price_cents AS line_total_cents,
+                   tr.id AS tax_rate_id,
+                   tr.name AS tax_rate_name,
+                   tr.vat_key,
+                   (
+                       SELECT rate_basis_points FROM tax_rate_periods
+                       WHERE tax_rate_id = tr.id
+                         AND valid_from <= DATE('now', 'localtime')
+                         AND (valid_to IS NULL OR valid_to >= DATE('now', 'localtime'))
+                       ORDER BY valid_from DESC
+                       LIMIT 1
+                   ) AS rate_basis_points
+            FROM bucket_items bi
+            LEFT JOIN products p ON p.id = bi.product_id
+            LEFT JOIN product_types pt ON pt.id = p.product_type_id
+            LEFT JOIN memberships ms ON ms.id = bi.membership_id
+            LEFT JOIN tax_rates tr ON tr.id = COALESCE(
+                p.tax_rate_id,
+                pt.tax_rate_id,
+                ms.tax_rate_id,
+                (SELECT id FROM tax_rates WHERE is_default = 1)
+            )
+            WHERE bi.bucket_id = ?2
+        )
+        ORDER BY id ASC
+        ",
+        params![transaction_id, bucket_id],
+    )
+    .map_err(|e| e.to_string())?;
+
+    let missing_rate: Option<(String, bool)> = conn
+        .query_row(
+            "SELECT product_name, tax_rate_id IS NULL FROM transaction_lines
+            WHERE transaction_id = ? AND vat_rate_basis_points IS NULL
+            LIMIT 1",
+            [transaction_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    match missing_rate {
+        Some((product_name, true)) => Err(format!(
+            "Für {} ist kein Steuersatz hinterlegt und kein Standardsteuersatz festgelegt.",
+            product_name
+        )),
+        Some((product_name, false)) => Err(format!(
+            "Für {} ist heute kein gültiger Steuersatz hinterlegt.",
+            product_name
+        )),
+        None => Ok(()),
+    }
+}
+
+struct CreditApproval {
+    member_id: i64,
+    shortfall_cents: i64,
+    credit_limit_cents: i64,
+    reason: String,
+    approved_by: i64,
+}
+
+/// Checks a debit from a member's balance against the credit limit. Going
+/// below the limit needs a manager's approval with a reason.
+fn approve_balance_debit(
+    conn: &Connection,
+    member_id: i64,
+    debit_cents: i64,
+    default_limit_cents: i64,
+    approval: Option<&CreditOverridePayload>,
+) -> Result<Option<CreditApproval>, CheckoutError> {
+    let (current_balance, member_limit): (i64, Option<i64>) = conn
+        .query_row(
+            "SELECT balance_cents, credit_limit_cents FROM members WHERE id = ?",
+            [member_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?
+        .ok_or("Mitglied nicht gefunden")?;
+    let credit_limit_cents = member_limit.unwrap_or(default_limit_cents);
+    let shortfall_cents = debit_cents - (current_balance + credit_limit_cents);
+    if shortfall_cents <= 0 {
+        return Ok(None);
+    }
+    let Some(approval) = approval else {
+        return Err(CheckoutError::InsufficientBalance {
+            message: format!(
+                "Guthaben reicht nicht aus, es fehlen {}.",
+                format_amount(shortfall_cents)
+            ),
+            member_id,
+            balance_cents: current_balance,
+            credit_limit_cents,
+            required_cents: debit_cents,
+            shortfall_cents,
+        });
+    };
+    let reason = approval.reason.trim();
+    if reason.is_empty() {
+        return Err("Bitte einen Grund für die Freigabe angeben.".into());
+    }
+    let approved_by = verify_manager_approval(conn, &approval.username, &approval.password)?;
+    Ok(Some(CreditApproval {
+        member_id,
+        shortfall_cents,
+        credit_limit_cents,
+        reason: reason.to_string(),
+        approved_by,
+    }))
+}
+
+fn record_credit_override(
+    conn: &Connection,
+    transaction_id: i64,
+    approval: &CreditApproval,
+    requested_by: i64,
+) -> Result<(), String> {
+    conn.execute(
+        "INSERT INTO credit_overrides (
+            transaction_id,
+            member_id,
+            shortfall_cents,
+            credit_limit_cents,
+            reason,
+            requested_by,
+            approved_by
+        )
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+            transaction_id,
+            approval.member_id,
+            approval.shortfall_cents,
+            approval.credit_limit_cents,
+            approval.reason,
+            requested_by,
+            approval.approved_by
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
 #[tauri::command]
 fn checkout_bucket(
     db: State<DatabasePath>,
+    session: State<SessionState>,
+    settings: State<SettingsState>,
+    tse: State<TseState>,
     payload: CheckoutBucketPayload,
-) -> Result<(), String> {
+) -> Result<i64, CheckoutError> {
+    let user = session.require(Role::User)?;
+    let settings = settings.get()?;
+    let client_id = settings.business.cash_register_id;
     let mut conn = db.connect().map_err(|e| e.to_string())?;
     let tx = conn.transaction().map_err(|e| e.to_string())?;
 
@@ -756,8 +2029,7 @@ fn checkout_bucket(
         )
         .optional()
         .map_err(|e| e.to_string())?;
-    let (bucket_name, status) =
-        bucket.ok_or_else(|| "Bucket nicht gefunden".to_string())?;
+    let (bucket_name, status) = bucket.ok_or_else(|| "Bucket nicht gefunden".to_string())?;
     if status.as_str() != "open" {
         return Err("Bucket ist nicht mehr offen.".into());
     }
@@ -773,36 +2045,131 @@ fn checkout_bucket(
         return Err("Bucket ist leer.".into());
     }
 
-    if payload.use_balance {
+    let sold_memberships: Vec<(i64, i64)> = {
+        let mut stmt = tx
+            .prepare(
+                "SELECT membership_id, quantity FROM bucket_items
+                WHERE bucket_id = ? AND membership_id IS NOT NULL
+                ORDER BY id ASC",
+            )
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map([payload.bucket_id], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(|e| e.to_string())?;
+        rows.collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?
+    };
+    if !sold_memberships.is_empty() && payload.member_id.is_none() {
+        return Err("Mitglied auswählen, um eine Mitgliedschaft zu verkaufen.".into());
+    }
+
+    let payments = resolve_checkout_payments(&payload, total_cents)?;
+    let balance_cents: i64 = payments
+        .iter()
+        .filter(|(method, _)| *method == PaymentMethod::Balance)
+        .map(|(_, amount)| amount)
+        .sum();
+    let balance_account = if balance_cents > 0 {
         let member_id = payload
             .member_id
-            .ok_or_else(|| "Mitglied auswählen, um Guthaben zu verwenden.".to_string())?;
-        tx.execute(
-            "UPDATE members SET balance_cents = balance_cents - ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?",
-            params![total_cents, member_id],
+            .ok_or("Mitglied auswählen, um Guthaben zu verwenden.")?;
+        Some(households::balance_account(&tx, member_id).map_err(|e| e.to_string())?)
+    } else {
+        None
+    };
+    let credit_override = match balance_account {
+        Some(member_id) => approve_balance_debit(
+            &tx,
+            member_id,
+            balance_cents,
+            settings.credit_limit_cents,
+            payload.credit_override.as_ref(),
+        )?,
+        None => None,
+    };
+
+    let method = payments
+        .iter()
+        .map(|(method, _)| method.label())
+        .collect::<Vec<_>>()
+        .join(" + ");
+
+    let item_count: i64 = tx
+        .query_row(
+            "SELECT COALESCE(SUM(quantity), 0) FROM bucket_items WHERE bucket_id = ?",
+            [payload.bucket_id],
+            |row| row.get(0),
         )
         .map_err(|e| e.to_string())?;
-    }
 
-    let method = if payload.use_balance {
-        "Guthaben".to_string()
-    } else {
-        payload
-            .payment_method
-            .unwrap_or_else(|| "Bar".to_string())
-    };
+    let cash_session_id = require_cash_session(&tx)?;
 
     tx.execute(
-        "INSERT INTO transactions (product_id, quantity, total_cents, description) VALUES (NULL, 1, ?, ?)",
+        "INSERT INTO transactions (product_id, quantity, total_cents, description, member_id, user_id, cash_session_id)
+        VALUES (NULL, ?1, ?2, ?3, ?4, ?5, ?6)",
         params![
+            item_count,
             total_cents,
-            format!("{} bezahlt ({})", bucket_name, method)
+            format!("{} bezahlt ({})", bucket_name, method),
+            payload.member_id,
+            user.id,
+            cash_session_id
         ],
     )
     .map_err(|e| e.to_string())?;
+    let transaction_id = tx.last_insert_rowid();
+
+    insert_sale_lines(&tx, transaction_id, payload.bucket_id)?;
+
+    if let Some(member_id) = payload.member_id {
+        for (membership_id, quantity) in &sold_memberships {
+            for _ in 0..*quantity {
+                grant_membership(
+                    &tx,
+                    member_id,
+                    *membership_id,
+                    Some(transaction_id),
+                    Some(user.id),
+                )?;
+            }
+        }
+    }
 
-    tx.execute("DELETE FROM bucket_items WHERE bucket_id = ?", [payload.bucket_id])
+    for (method, amount_cents) in &payments {
+        let member_id = if *method == PaymentMethod::Balance {
+            balance_account.or(payload.member_id)
+        } else {
+            None
+        };
+        tx.execute(
+            "INSERT INTO transaction_payments (transaction_id, method, amount_cents, member_id) VALUES (?1, ?2, ?3, ?4)",
+            params![transaction_id, method, amount_cents, member_id],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    if let Some(approval) = &credit_override {
+        record_credit_override(&tx, transaction_id, approval, user.id)?;
+    }
+    if let Some(member_id) = balance_account {
+        record_balance_entry(
+            &tx,
+            member_id,
+            "purchase",
+            -balance_cents,
+            Some(transaction_id),
+            None,
+            Some(user.id),
+        )
         .map_err(|e| e.to_string())?;
+    }
+
+    append_journal_entry(&tx, transaction_id, "sale").map_err(|e| e.to_string())?;
+
+    tx.execute(
+        "DELETE FROM bucket_items WHERE bucket_id = ?",
+        [payload.bucket_id],
+    )
+    .map_err(|e| e.to_string())?;
 
     tx.execute(
         "UPDATE buckets SET status = 'closed', updated_at = CURRENT_TIMESTAMP WHERE id = ?",
@@ -810,18 +2177,24 @@ fn checkout_bucket(
     )
     .map_err(|e| e.to_string())?;
 
-    tx.commit().map_err(|e| e.to_string())
+    sign_and_commit(tx, &db, tse.device(), &client_id, transaction_id)?;
+    Ok(transaction_id)
 }
 
 #[tauri::command]
-fn list_product_types(db: State<DatabasePath>) -> Result<Vec<ProductTypeRecord>, String> {
+fn list_product_types(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+) -> Result<Vec<ProductTypeRecord>, String> {
+    session.require(Role::User)?;
     let conn = db.connect().map_err(|e| e.to_string())?;
     let mut stmt = conn
         .prepare(
             "
-        SELECT id, name, color, created_at, updated_at
-        FROM product_types
-        ORDER BY name COLLATE NOCASE
+        SELECT pt.id, pt.name, pt.color, pt.tax_rate_id, tr.name, pt.created_at, pt.updated_at
+        FROM product_types pt
+        LEFT JOIN tax_rates tr ON tr.id = pt.tax_rate_id
+        ORDER BY pt.name COLLATE NOCASE
         ",
         )
         .map_err(|e| e.to_string())?;
@@ -832,8 +2205,10 @@ fn list_product_types(db: State<DatabasePath>) -> Result<Vec<ProductTypeRecord>,
                 id: row.get(0)?,
                 name: row.get(1)?,
                 color: row.get(2)?,
-                created_at: row.get(3)?,
-                updated_at: row.get(4)?,
+                tax_rate_id: row.get(3)?,
+                tax_rate_name: row.get(4)?,
+                created_at: row.get(5)?,
+                updated_at: row.get(6)?,
             })
         })
         .map_err(|e| e.to_string())?;
@@ -843,19 +2218,30 @@ fn list_product_types(db: State<DatabasePath>) -> Result<Vec<ProductTypeRecord>,
 }
 
 #[tauri::command]
-fn save_product_type(db: State<DatabasePath>, payload: ProductTypePayload) -> Result<i64, String> {
+fn save_product_type(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    payload: ProductTypePayload,
+) -> Result<i64, String> {
+    session.require(Role::Manager)?;
     let conn = db.connect().map_err(|e| e.to_string())?;
     if let Some(id) = payload.id {
         conn.execute(
-            "UPDATE product_types SET name = ?, color = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?",
-            params![payload.name, payload.color, id],
+            "UPDATE product_types SET name = ?, color = ?, tax_rate_id = CASE WHEN ? THEN NULL ELSE COALESCE(?, tax_rate_id) END, updated_at = CURRENT_TIMESTAMP WHERE id = ?",
+            params![
+                payload.name,
+                payload.color,
+                payload.clear_tax_rate,
+                payload.tax_rate_id,
+                id
+            ],
         )
         .map_err(|e| e.to_string())?;
         Ok(id)
     } else {
         conn.execute(
-            "INSERT INTO product_types (name, color) VALUES (?1, ?2)",
-            params![payload.name, payload.color],
+            "INSERT INTO product_types (name, color, tax_rate_id) VALUES (?1, ?2, ?3)",
+            params![payload.name, payload.color, payload.tax_rate_id],
         )
         .map_err(|e| e.to_string())?;
         Ok(conn.last_insert_rowid())
@@ -863,7 +2249,12 @@ fn save_product_type(db: State<DatabasePath>, payload: ProductTypePayload) -> Re
 }
 
 #[tauri::command]
-fn delete_product_type(db: State<DatabasePath>, id: i64) -> Result<(), String> {
+fn delete_product_type(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    id: i64,
+) -> Result<(), String> {
+    session.require(Role::Manager)?;
     let conn = db.connect().map_err(|e| e.to_string())?;
     conn.execute("DELETE FROM product_types WHERE id = ?", [id])
         .map_err(|e| e.to_string())
@@ -871,134 +2262,3492 @@ fn delete_product_type(db: State<DatabasePath>, id: i64) -> Result<(), String> {
 }
 
 #[tauri::command]
-fn list_members(db: State<DatabasePath>) -> Result<Vec<MemberRecord>, String> {
+fn list_tax_rates(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+) -> Result<Vec<TaxRateRecord>, String> {
+    session.require(Role::User)?;
     let conn = db.connect().map_err(|e| e.to_string())?;
     let mut stmt = conn
         .prepare(
             "
-        SELECT m.id,
-               m.first_name,
-               m.last_name,
-               m.email,
-               m.phone,
-               m.status,
-               m.notes,
-               m.balance_cents,
-               m.created_at,
-               m.updated_at
-        FROM members m
-        ORDER BY m.last_name COLLATE NOCASE, m.first_name COLLATE NOCASE
+        SELECT tr.id,
+               tr.name,
+               tr.vat_key,
+               tr.is_default,
+               (
+                   SELECT rate_basis_points FROM tax_rate_periods
+                   WHERE tax_rate_id = tr.id
+                     AND valid_from <= DATE('now', 'localtime')
+                     AND (valid_to IS NULL OR valid_to >= DATE('now', 'localtime'))
+                   ORDER BY valid_from DESC
+                   LIMIT 1
+               ),
+               tr.created_at,
+               tr.updated_at
+        FROM tax_rates tr
+        ORDER BY tr.vat_key ASC, tr.name COLLATE NOCASE
         ",
         )
         .map_err(|e| e.to_string())?;
-
     let rows = stmt
         .query_map([], |row| {
-            Ok(MemberRecord {
+            Ok(TaxRateRecord {
                 id: row.get(0)?,
-                first_name: row.get(1)?,
-                last_name: row.get(2)?,
-                email: row.get(3)?,
-                phone: row.get(4)?,
-                status: row.get(5)?,
-                notes: row.get(6)?,
-                balance_cents: row.get(7)?,
-                created_at: row.get(8)?,
-                updated_at: row.get(9)?,
+                name: row.get(1)?,
+                vat_key: row.get(2)?,
+                is_default: row.get(3)?,
+                current_rate_basis_points: row.get(4)?,
+                periods: Vec::new(),
+                created_at: row.get(5)?,
+                updated_at: row.get(6)?,
             })
         })
         .map_err(|e| e.to_string())?;
-
-    rows.collect::<Result<Vec<_>, _>>()
-        .map_err(|e| e.to_string())
-}
-
-#[tauri::command]
-fn save_member(db: State<DatabasePath>, payload: MemberPayload) -> Result<i64, String> {
-    let conn = db.connect().map_err(|e| e.to_string())?;
-    if let Some(id) = payload.id {
-        conn.execute(
-            "UPDATE members
-            SET first_name = ?,
-                last_name = ?,
-                email = ?,
-                phone = ?,
-                status = ?,
-                notes = ?,
-                balance_cents = ?,
-                updated_at = CURRENT_TIMESTAMP
-            WHERE id = ?",
-            params![
-                payload.first_name,
-                payload.last_name,
-                payload.email,
-                payload.phone,
-                payload.status.unwrap_or_else(|| "active".into()),
-                payload.notes,
-                payload.balance_cents.unwrap_or(0),
-                id
-            ],
-        )
+    let mut records = rows
+        .collect::<Result<Vec<_>, _>>()
         .map_err(|e| e.to_string())?;
-        Ok(id)
-    } else {
-        conn.execute(
-            "INSERT INTO members (first_name, last_name, email, phone, status, notes, balance_cents)
-            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
-            params![
-                payload.first_name,
-                payload.last_name,
-                payload.email,
-                payload.phone,
-                payload.status.unwrap_or_else(|| "active".into()),
-                payload.notes,
-                payload.balance_cents.unwrap_or(0)
-            ],
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, tax_rate_id, rate_basis_points, valid_from, valid_to
+            FROM tax_rate_periods WHERE tax_rate_id = ? ORDER BY valid_from ASC",
         )
         .map_err(|e| e.to_string())?;
-        Ok(conn.last_insert_rowid())
+    for record in records.iter_mut() {
+        let rows = stmt
+            .query_map([record.id], |row| {
+                Ok(TaxRatePeriodRecord {
+                    id: row.get(0)?,
+                    tax_rate_id: row.get(1)?,
+                    rate_basis_points: row.get(2)?,
+                    valid_from: row.get(3)?,
+                    valid_to: row.get(4)?,
+                })
+            })
+            .map_err(|e| e.to_string())?;
+        record.periods = rows
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
     }
+    Ok(records)
 }
 
 #[tauri::command]
-fn delete_member(db: State<DatabasePath>, id: i64) -> Result<(), String> {
-    let conn = db.connect().map_err(|e| e.to_string())?;
-    conn.execute("DELETE FROM members WHERE id = ?", [id])
-        .map_err(|e| e.to_string())
-        .map(|_| ())
-}
-
-#[tauri::command]
-fn list_memberships(db: State<DatabasePath>) -> Result<Vec<MembershipRecord>, String> {
-    let conn = db.connect().map_err(|e| e.to_string())?;
-    let mut stmt = conn
+fn save_tax_rate(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    payload: TaxRatePayload,
+) -> Result<i64, String> {
+    session.require(Role::Manager)?;
+    let name = payload.name.trim();
+    if name.is_empty() {
+        return Err("Name des Steuersatzes fehlt.".into());
+    }
+    if !(1..=7).contains(&payload.vat_key) {
+        return Err("Ungültiger DSFinV-K-Steuerschlüssel.".into());
+    }
+    if payload.periods.is_empty() {
+        return Err("Mindestens einen Gültigkeitszeitraum angeben.".into());
+    }
+
+    let mut conn = db.connect().map_err(|e| e.to_string())?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+
+    let mut periods = Vec::with_capacity(payload.periods.len());
+    for period in &payload.periods {
+        if !(0..=10000).contains(&period.rate_basis_points) {
+            return Err("Steuersatz muss zwischen 0 und 100 % liegen.".into());
+        }
+        let valid_from = normalize_date(&tx, &period.valid_from)?;
+        let valid_to = match period.valid_to.as_deref().map(str::trim) {
+            Some(value) if !value.is_empty() => Some(normalize_date(&tx, value)?),
+            _ => None,
+        };
+        if let Some(valid_to) = &valid_to {
+            if *valid_to < valid_from {
+                return Err("Gültig-bis liegt vor Gültig-ab.".into());
+            }
+        }
+        periods.push((period.rate_basis_points, valid_from, valid_to));
+    }
+    periods.sort_by(|a, b| a.1.cmp(&b.1));
+    for pair in periods.windows(2) {
+        let overlaps = match &pair[0].2 {
+            Some(valid_to) => *valid_to >= pair[1].1,
+            None => true,
+        };
+        if overlaps {
+            return Err(format!(
+                "Gültigkeitszeiträume überschneiden sich ab {}.",
+                pair[1].1
+            ));
+        }
+    }
+
+    let id = if let Some(id) = payload.id {
+        let updated = tx
+            .execute(
+                "UPDATE tax_rates SET name = ?, vat_key = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?",
+                params![name, payload.vat_key, id],
+            )
+            .map_err(|e| e.to_string())?;
+        if updated == 0 {
+            return Err("Steuersatz nicht gefunden".into());
+        }
+        tx.execute("DELETE FROM tax_rate_periods WHERE tax_rate_id = ?", [id])
+            .map_err(|e| e.to_string())?;
+        id
+    } else {
+        tx.execute(
+            "INSERT INTO tax_rates (name, vat_key) VALUES (?1, ?2)",
+            params![name, payload.vat_key],
+        )
+        .map_err(|e| e.to_string())?;
+        tx.last_insert_rowid()
+    };
+    if payload.is_default {
+        tx.execute(
+            "UPDATE tax_rates SET is_default = (id = ?) WHERE is_default = 1 OR id = ?",
+            [id, id],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+
+    for (rate_basis_points, valid_from, valid_to) in periods {
+        tx.execute(
+            "INSERT INTO tax_rate_periods (tax_rate_id, rate_basis_points, valid_from, valid_to) VALUES (?1, ?2, ?3, ?4)",
+            params![id, rate_basis_points, valid_from, valid_to],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(id)
+}
+
+#[tauri::command]
+fn delete_tax_rate(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    id: i64,
+) -> Result<(), String> {
+    session.require(Role::Manager)?;
+    let conn = db.connect().map_err(|e| e.to_string())?;
+    conn.execute("DELETE FROM tax_rates WHERE id = ?", [id])
+        .map_err(|e| e.to_string())
+        .map(|_| ())
+}
+
+#[tauri::command]
+fn get_vat_summary(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    payload: VatSummaryPayload,
+) -> Result<Vec<VatSummaryRecord>, String> {
+    session.require(Role::User)?;
+    let conn = db.connect().map_err(|e| e.to_string())?;
+    match (payload.cash_session_id, payload.date) {
+        (Some(cash_session_id), _) => {
+            load_vat_summary(&conn, "t.cash_session_id = ?", &[&cash_session_id])
+        }
+        (None, Some(date)) => {
+            let date = normalize_date(&conn, &date)?;
+            load_vat_summary(&conn, "DATE(t.created_at, 'localtime') = ?", &[&date])
+        }
+        (None, None) => load_vat_summary(
+            &conn,
+            "DATE(t.created_at, 'localtime') = DATE('now', 'localtime')",
+            &[],
+        ),
+    }
+    .map_err(|e| e.to_string())
+}
+
+const MEMBER_COLUMNS: &str = "
+        m.id,
+        m.first_name,
+        m.last_name,
+        m.email,
+        m.phone,
+        m.status,
+        m.notes,
+        m.balance_cents,
+        m.credit_limit_cents,
+        m.iban,
+        m.bic,
+        m.debit_returned_at,
+        m.birth_date,
+        m.created_at,
+        m.updated_at";
+
+fn map_member(row: &rusqlite::Row) -> rusqlite::Result<MemberRecord> {
+    Ok(MemberRecord {
+        id: row.get(0)?,
+        first_name: row.get(1)?,
+        last_name: row.get(2)?,
+        email: row.get(3)?,
+        phone: row.get(4)?,
+        status: row.get(5)?,
+        notes: row.get(6)?,
+        balance_cents: row.get(7)?,
+        credit_limit_cents: row.get(8)?,
+        iban: row.get(9)?,
+        bic: row.get(10)?,
+        debit_returned_at: row.get(11)?,
+        birth_date: row.get(12)?,
+        created_at: row.get(13)?,
+        updated_at: row.get(14)?,
+    })
+}
+
+#[tauri::command]
+fn list_members(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+) -> Result<Vec<MemberRecord>, String> {
+    session.require(Role::User)?;
+    let conn = db.connect().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM members m ORDER BY m.last_name COLLATE NOCASE, m.first_name COLLATE NOCASE",
+            MEMBER_COLUMNS
+        ))
+        .map_err(|e| e.to_string())?;
+
+    let rows = stmt.query_map([], map_member).map_err(|e| e.to_string())?;
+
+    rows.collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())
+}
+
+const MEMBER_SEARCH_FILTER: &str = "
+    FROM members m
+    WHERE (:match IS NULL OR m.id IN (
+            SELECT rowid FROM member_search WHERE member_search MATCH :match
+        ))
+      AND (:status IS NULL OR m.status = :status)
+      AND (:membership_id IS NULL OR EXISTS (
+            SELECT 1 FROM member_memberships mm
+            WHERE mm.member_id = m.id
+              AND mm.membership_id = :membership_id
+              AND (mm.end_date IS NULL OR mm.end_date >= DATE('now', 'localtime'))
+        ))
+      AND (:expiring IS NULL OR EXISTS (
+            SELECT 1 FROM member_memberships mm
+            WHERE mm.member_id = m.id
+              AND mm.end_date BETWEEN DATE('now', 'localtime') AND DATE('now', 'localtime', :expiring)
+              AND NOT EXISTS (SELECT 1 FROM member_memberships r WHERE r.renewed_from_id = mm.id)
+        ))
+      AND (:negative_balance = 0 OR m.balance_cents < 0)";
+
+#[tauri::command]
+fn search_members(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    payload: MemberSearchRequest,
+) -> Result<MemberSearchPage, String> {
+    session.require(Role::User)?;
+    let limit = payload.limit.unwrap_or(member_search::DEFAULT_LIMIT);
+    if limit == 0 || limit > member_search::MAX_LIMIT {
+        return Err(format!(
+            "Seitengröße muss zwischen 1 und {} liegen",
+            member_search::MAX_LIMIT
+        ));
+    }
+    let cursor = payload
+        .cursor
+        .as_deref()
+        .filter(|cursor| !cursor.is_empty())
+        .map(member_search::Cursor::decode)
+        .transpose()?;
+    let fts = payload
+        .query
+        .as_deref()
+        .and_then(member_search::match_expression);
+    let status = payload
+        .status
+        .as_deref()
+        .map(str::trim)
+        .filter(|status| !status.is_empty());
+    let expiring = payload
+        .expiring_within_days
+        .map(|days| format!("+{} days", days));
+    let filter: [(&str, &dyn rusqlite::ToSql); 5] = [
+        (":match", &fts),
+        (":status", &status),
+        (":membership_id", &payload.membership_id),
+        (":expiring", &expiring),
+        (":negative_balance", &payload.negative_balance),
+    ];
+
+    let conn = db.connect().map_err(|e| e.to_string())?;
+    let total_count: i64 = conn
+        .query_row(
+            &format!("SELECT COUNT(*) {}", MEMBER_SEARCH_FILTER),
+            &filter[..],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+
+    let (after_last, after_first, after_id) = match &cursor {
+        Some(cursor) => (
+            Some(cursor.last_name.as_str()),
+            Some(cursor.first_name.as_str()),
+            Some(cursor.id),
+        ),
+        None => (None, None, None),
+    };
+    let fetch = i64::from(limit) + 1;
+    let mut params = filter.to_vec();
+    params.extend_from_slice(&[
+        (":after_last", &after_last as &dyn rusqlite::ToSql),
+        (":after_first", &after_first),
+        (":after_id", &after_id),
+        (":fetch", &fetch),
+    ]);
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} {}
+              AND (:after_id IS NULL
+                   OR (m.last_name COLLATE NOCASE, m.first_name COLLATE NOCASE, m.id)
+                      > (:after_last, :after_first, :after_id))
+            ORDER BY m.last_name COLLATE NOCASE, m.first_name COLLATE NOCASE, m.id
+            LIMIT :fetch",
+            MEMBER_COLUMNS, MEMBER_SEARCH_FILTER
+        ))
+        .map_err(|e| e.to_string())?;
+    let mut items = stmt
+        .query_map(&params[..], map_member)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    let next_cursor = if items.len() > limit as usize {
+        items.truncate(limit as usize);
+        items.last().map(|member| {
+            member_search::Cursor {
+                last_name: member.last_name.clone(),
+                first_name: member.first_name.clone(),
+                id: member.id,
+            }
+            .encode()
+        })
+    } else {
+        None
+    };
+    Ok(MemberSearchPage {
+        items,
+        total_count,
+        next_cursor,
+    })
+}
+
+#[tauri::command]
+fn save_member(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    payload: MemberPayload,
+) -> Result<i64, String> {
+    session.require(Role::User)?;
+    let iban = match payload.iban.as_deref().map(str::trim) {
+        Some(iban) if !iban.is_empty() => Some(sepa::normalize_iban(iban)?),
+        _ => None,
+    };
+    let bic = match payload.bic.as_deref().map(str::trim) {
+        Some(bic) if !bic.is_empty() => Some(sepa::normalize_bic(bic)?),
+        _ => None,
+    };
+    let mut conn = db.connect().map_err(|e| e.to_string())?;
+    let birth_date = match payload.birth_date.as_deref().map(str::trim) {
+        Some(date) if !date.is_empty() => {
+            if !is_iso_date(&conn, date)? {
+                return Err("Geburtsdatum muss im Format JJJJ-MM-TT angegeben werden".into());
+            }
+            Some(date.to_string())
+        }
+        _ => None,
+    };
+    if let Some(id) = payload.id {
+        let updated = conn
+            .execute(
+                "UPDATE members
+                SET first_name = ?,
+                    last_name = ?,
+                    email = ?,
+                    phone = ?,
+                    status = ?,
+                    notes = ?,
+                    iban = ?,
+                    bic = ?,
+                    birth_date = ?,
+                    updated_at = CURRENT_TIMESTAMP
+                WHERE id = ?",
+                params![
+                    payload.first_name,
+                    payload.last_name,
+                    payload.email,
+                    payload.phone,
+                    payload.status.unwrap_or_else(|| "active".into()),
+                    payload.notes,
+                    iban,
+                    bic,
+                    birth_date,
+                    id
+                ],
+            )
+            .map_err(|e| e.to_string())?;
+        if updated == 0 {
+            return Err("Mitglied nicht gefunden".into());
+        }
+        Ok(id)
+    } else {
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        tx.execute(
+            "INSERT INTO members (first_name, last_name, email, phone, status, notes, iban, bic, birth_date)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            params![
+                payload.first_name,
+                payload.last_name,
+                payload.email,
+                payload.phone,
+                payload.status.unwrap_or_else(|| "active".into()),
+                payload.notes,
+                iban,
+                bic,
+                birth_date
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+        let member_id = tx.last_insert_rowid();
+        tx.execute(
+            "INSERT INTO member_cards (member_id, token, kind) VALUES (?1, ?2, 'qr')",
+            params![member_id, cards::generate_token()],
+        )
+        .map_err(|e| e.to_string())?;
+        tx.commit().map_err(|e| e.to_string())?;
+        Ok(member_id)
+    }
+}
+
+const MEMBER_CARD_COLUMNS: &str =
+    "id, member_id, token, kind, status, label, status_changed_at, created_at";
+
+fn map_member_card(row: &rusqlite::Row) -> rusqlite::Result<MemberCardRecord> {
+    Ok(MemberCardRecord {
+        id: row.get(0)?,
+        member_id: row.get(1)?,
+        token: row.get(2)?,
+        kind: row.get(3)?,
+        status: row.get(4)?,
+        label: row.get(5)?,
+        status_changed_at: row.get(6)?,
+        created_at: row.get(7)?,
+    })
+}
+
+#[tauri::command]
+fn list_member_cards(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    member_id: i64,
+) -> Result<Vec<MemberCardRecord>, String> {
+    session.require(Role::User)?;
+    let conn = db.connect().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM member_cards WHERE member_id = ? ORDER BY status = 'active' DESC, created_at DESC, id DESC",
+            MEMBER_CARD_COLUMNS
+        ))
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([member_id], map_member_card)
+        .map_err(|e| e.to_string())?;
+
+    rows.collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn add_member_card(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    payload: MemberCardPayload,
+) -> Result<MemberCardRecord, String> {
+    session.require(Role::User)?;
+    if !cards::KINDS.contains(&payload.kind.as_str()) {
+        return Err("Unbekannter Kartentyp".into());
+    }
+    let token = match payload.token.as_deref().map(str::trim) {
+        Some(token) if !token.is_empty() => cards::normalize_token(token)?,
+        _ if payload.kind == "qr" => cards::generate_token(),
+        _ => return Err("Kartennummer fehlt".into()),
+    };
+    let label = payload
+        .label
+        .map(|label| label.trim().to_string())
+        .filter(|label| !label.is_empty());
+    let conn = db.connect().map_err(|e| e.to_string())?;
+    let owner = conn
+        .query_row(
+            "SELECT member_id FROM member_cards WHERE token = ?",
+            [&token],
+            |row| row.get::<_, i64>(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    if let Some(owner) = owner {
+        return Err(if owner == payload.member_id {
+            "Karte ist diesem Mitglied bereits zugeordnet".into()
+        } else {
+            "Karte ist bereits einem anderen Mitglied zugeordnet".into()
+        });
+    }
+    conn.execute(
+        "INSERT INTO member_cards (member_id, token, kind, label) VALUES (?1, ?2, ?3, ?4)",
+        params![payload.member_id, token, payload.kind, label],
+    )
+    .map_err(|e| e.to_string())?;
+    conn.query_row(
+        &format!(
+            "SELECT {} FROM member_cards WHERE id = ?",
+            MEMBER_CARD_COLUMNS
+        ),
+        [conn.last_insert_rowid()],
+        map_member_card,
+    )
+    .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn set_member_card_status(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    payload: MemberCardStatusPayload,
+) -> Result<(), String> {
+    if !cards::STATUSES.contains(&payload.status.as_str()) {
+        return Err("Unbekannter Kartenstatus".into());
+    }
+    // Lost cards may be reported at the front desk; blocking and reactivating needs a manager.
+    session.require(if payload.status == "lost" {
+        Role::User
+    } else {
+        Role::Manager
+    })?;
+    let conn = db.connect().map_err(|e| e.to_string())?;
+    let updated = conn
+        .execute(
+            "UPDATE member_cards
+            SET status = ?1,
+                status_changed_at = CASE WHEN status = ?1 THEN status_changed_at ELSE CURRENT_TIMESTAMP END,
+                updated_at = CURRENT_TIMESTAMP
+            WHERE id = ?2",
+            params![payload.status, payload.card_id],
+        )
+        .map_err(|e| e.to_string())?;
+    if updated == 0 {
+        return Err("Karte nicht gefunden".into());
+    }
+    Ok(())
+}
+
+#[tauri::command]
+fn member_card_qr_svg(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    card_id: i64,
+) -> Result<String, String> {
+    session.require(Role::User)?;
+    let conn = db.connect().map_err(|e| e.to_string())?;
+    let token: String = conn
+        .query_row(
+            "SELECT token FROM member_cards WHERE id = ?",
+            [card_id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?
+        .ok_or("Karte nicht gefunden")?;
+    cards::qr_svg(&token)
+}
+
+#[tauri::command]
+fn delete_member(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    id: i64,
+) -> Result<(), String> {
+    session.require(Role::Manager)?;
+    let conn = db.connect().map_err(|e| e.to_string())?;
+    let balance_cents: i64 = conn
+        .query_row(
+            "SELECT balance_cents FROM members WHERE id = ?",
+            [id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?
+        .unwrap_or(0);
+    if balance_cents != 0 {
+        return Err(format!(
+            "Mitglied hat noch ein Guthaben von {} und kann nicht gelöscht werden.",
+            format_amount(balance_cents)
+        ));
+    }
+    let has_ledger: bool = conn
+        .query_row(
+            "SELECT EXISTS(SELECT 1 FROM balance_entries WHERE member_id = ?)",
+            [id],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    if has_ledger {
+        return Err(
+            "Mitglied hat Guthabenbuchungen, die aufbewahrt werden müssen, und kann nicht gelöscht werden."
+                .into(),
+        );
+    }
+    conn.execute("DELETE FROM members WHERE id = ?", [id])
+        .map_err(|e| e.to_string())
+        .map(|_| ())
+}
+
+#[tauri::command]
+fn list_balance_entries(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    member_id: i64,
+) -> Result<Vec<BalanceEntryRecord>, String> {
+    session.require(Role::User)?;
+    let conn = db.connect().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(
+            "
+        SELECT be.id,
+               be.member_id,
+               be.kind,
+               be.amount_cents,
+               SUM(be.amount_cents) OVER (ORDER BY be.id),
+               be.transaction_id,
+               be.reason,
+               be.user_id,
+               u.display_name,
+               be.created_at
+        FROM balance_entries be
+        LEFT JOIN users u ON u.id = be.user_id
+        WHERE be.member_id = ?
+        ORDER BY be.id DESC
+        ",
+        )
+        .map_err(|e| e.to_string())?;
+
+    let rows = stmt
+        .query_map([member_id], |row| {
+            Ok(BalanceEntryRecord {
+                id: row.get(0)?,
+                member_id: row.get(1)?,
+                kind: row.get(2)?,
+                amount_cents: row.get(3)?,
+                balance_after_cents: row.get(4)?,
+                transaction_id: row.get(5)?,
+                reason: row.get(6)?,
+                user_id: row.get(7)?,
+                user_name: row.get(8)?,
+                created_at: row.get(9)?,
+            })
+        })
+        .map_err(|e| e.to_string())?;
+
+    rows.collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn top_up_balance(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    settings: State<SettingsState>,
+    tse: State<TseState>,
+    payload: TopUpBalancePayload,
+) -> Result<i64, String> {
+    let user = session.require(Role::User)?;
+    if payload.amount_cents <= 0 {
+        return Err("Aufladebetrag muss größer als 0 sein.".into());
+    }
+    if payload.payment_method == PaymentMethod::Balance {
+        return Err("Guthaben kann nicht mit Guthaben aufgeladen werden.".into());
+    }
+    let client_id = settings.get()?.business.cash_register_id;
+    let mut conn = db.connect().map_err(|e| e.to_string())?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+
+    let member_name: String = tx
+        .query_row(
+            "SELECT first_name || ' ' || last_name FROM members WHERE id = ?",
+            [payload.member_id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "Mitglied nicht gefunden".to_string())?;
+    let account_id =
+        households::balance_account(&tx, payload.member_id).map_err(|e| e.to_string())?;
+    let cash_session_id = require_cash_session(&tx)?;
+
+    tx.execute(
+        "INSERT INTO transactions (product_id, quantity, total_cents, description, member_id, user_id, cash_session_id)
+        VALUES (NULL, 1, ?1, ?2, ?3, ?4, ?5)",
+        params![
+            payload.amount_cents,
+            format!(
+                "Guthaben-Aufladung {} ({})",
+                member_name,
+                payload.payment_method.label()
+            ),
+            payload.member_id,
+            user.id,
+            cash_session_id
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+    let transaction_id = tx.last_insert_rowid();
+
+    tx.execute(
+        "INSERT INTO transaction_lines (
+            transaction_id,
+            product_name,
+            unit_price_cents,
+            quantity,
+            line_total_cents,
+            vat_key,
+            vat_rate_basis_points,
+            net_cents,
+            tax_cents
+        )
+        VALUES (?1, 'Guthaben-Aufladung', ?2, 1, ?2, ?3, 0, ?2, 0)",
+        params![transaction_id, payload.amount_cents, VAT_KEY_NOT_TAXABLE],
+    )
+    .map_err(|e| e.to_string())?;
+    tx.execute(
+        "INSERT INTO transaction_payments (transaction_id, method, amount_cents) VALUES (?1, ?2, ?3)",
+        params![transaction_id, payload.payment_method, payload.amount_cents],
+    )
+    .map_err(|e| e.to_string())?;
+    record_balance_entry(
+        &tx,
+        account_id,
+        "top_up",
+        payload.amount_cents,
+        Some(transaction_id),
+        None,
+        Some(user.id),
+    )
+    .map_err(|e| e.to_string())?;
+
+    append_journal_entry(&tx, transaction_id, "sale").map_err(|e| e.to_string())?;
+    sign_and_commit(tx, &db, tse.device(), &client_id, transaction_id)?;
+    Ok(transaction_id)
+}
+
+#[tauri::command]
+fn adjust_balance(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    payload: AdjustBalancePayload,
+) -> Result<i64, String> {
+    let user = session.require(Role::Manager)?;
+    if payload.amount_cents == 0 {
+        return Err("Korrekturbetrag darf nicht 0 sein.".into());
+    }
+    let reason = payload.reason.trim();
+    if reason.is_empty() {
+        return Err("Bitte einen Grund für die Korrektur angeben.".into());
+    }
+    let conn = db.connect().map_err(|e| e.to_string())?;
+    let exists: bool = conn
+        .query_row(
+            "SELECT EXISTS(SELECT 1 FROM members WHERE id = ?)",
+            [payload.member_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    if !exists {
+        return Err("Mitglied nicht gefunden".into());
+    }
+    let account_id =
+        households::balance_account(&conn, payload.member_id).map_err(|e| e.to_string())?;
+    record_balance_entry(
+        &conn,
+        account_id,
+        "correction",
+        payload.amount_cents,
+        None,
+        Some(reason),
+        Some(user.id),
+    )
+    .map_err(|e| e.to_string())?;
+    conn.query_row(
+        "SELECT balance_cents FROM members WHERE id = ?",
+        [account_id],
+        |row| row.get(0),
+    )
+    .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn set_member_credit_limit(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    payload: MemberCreditLimitPayload,
+) -> Result<(), String> {
+    session.require(Role::Manager)?;
+    if payload.credit_limit_cents.is_some_and(|limit| limit < 0) {
+        return Err("Kreditlimit darf nicht negativ sein.".into());
+    }
+    let conn = db.connect().map_err(|e| e.to_string())?;
+    let updated = conn
+        .execute(
+            "UPDATE members SET credit_limit_cents = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?",
+            params![payload.credit_limit_cents, payload.member_id],
+        )
+        .map_err(|e| e.to_string())?;
+    if updated == 0 {
+        return Err("Mitglied nicht gefunden".into());
+    }
+    Ok(())
+}
+
+#[tauri::command]
+fn list_memberships(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+) -> Result<Vec<MembershipRecord>, String> {
+    session.require(Role::User)?;
+    let conn = db.connect().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(
+            "
+        SELECT id,
+               membership_type,
+               notes,
+               price_cents,
+               duration_days,
+               max_uses,
+               tax_rate_id,
+               allowed_weekdays,
+               created_at,
+               updated_at
+        FROM memberships
+        ORDER BY created_at DESC
+        ",
+        )
+        .map_err(|e| e.to_string())?;
+
+    let rows = stmt
+        .query_map([], |row| {
+            Ok(MembershipRecord {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                description: row.get(2)?,
+                price_cents: row.get(3)?,
+                duration_days: row.get(4)?,
+                max_uses: row.get(5)?,
+                tax_rate_id: row.get(6)?,
+                allowed_weekdays: row
+                    .get::<_, Option<String>>(7)?
+                    .map(|days| checkin::parse_weekdays(&days)),
+                time_windows: Vec::new(),
+                created_at: row.get(8)?,
+                updated_at: row.get(9)?,
+            })
+        })
+        .map_err(|e| e.to_string())?;
+
+    let mut memberships = rows
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    let mut windows = conn
+        .prepare(
+            "SELECT start_time, end_time FROM membership_time_windows
+            WHERE membership_id = ?
+            ORDER BY start_time",
+        )
+        .map_err(|e| e.to_string())?;
+    for membership in &mut memberships {
+        membership.time_windows = windows
+            .query_map([membership.id], |row| {
+                Ok(TimeWindow {
+                    start_time: row.get(0)?,
+                    end_time: row.get(1)?,
+                })
+            })
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(memberships)
+}
+
+#[tauri::command]
+fn save_membership(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    payload: MembershipPayload,
+) -> Result<i64, String> {
+    session.require(Role::Manager)?;
+    let allowed_weekdays = match &payload.allowed_weekdays {
+        Some(days) if days.is_empty() => {
+            return Err("Mindestens ein Wochentag muss erlaubt sein".into())
+        }
+        Some(days) => {
+            if days.iter().any(|day| !(1..=7).contains(day)) {
+                return Err("Wochentage müssen zwischen 1 (Montag) und 7 (Sonntag) liegen".into());
+            }
+            let mut days = days.clone();
+            days.sort_unstable();
+            days.dedup();
+            Some(
+                days.iter()
+                    .map(|day| day.to_string())
+                    .collect::<Vec<_>>()
+                    .join(","),
+            )
+        }
+        None => None,
+    };
+    for window in &payload.time_windows {
+        if !checkin::is_valid_time(&window.start_time) || !checkin::is_valid_time(&window.end_time)
+        {
+            return Err("Uhrzeiten müssen im Format HH:MM angegeben werden".into());
+        }
+        if window.start_time >= window.end_time {
+            return Err("Zeitfenster muss vor seinem Ende beginnen".into());
+        }
+    }
+
+    let mut conn = db.connect().map_err(|e| e.to_string())?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let id = if let Some(id) = payload.id {
+        tx.execute(
+            "UPDATE memberships
+            SET membership_type = ?, notes = ?, price_cents = ?, duration_days = ?, max_uses = ?, tax_rate_id = CASE WHEN ? THEN NULL ELSE COALESCE(?, tax_rate_id) END, allowed_weekdays = ?, updated_at = CURRENT_TIMESTAMP
+            WHERE id = ?",
+            params![
+                payload.name,
+                payload.description,
+                payload.price_cents,
+                payload.duration_days,
+                payload.max_uses,
+                payload.clear_tax_rate,
+                payload.tax_rate_id,
+                allowed_weekdays,
+                id
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+        tx.execute(
+            "DELETE FROM membership_time_windows WHERE membership_id = ?",
+            [id],
+        )
+        .map_err(|e| e.to_string())?;
+        id
+    } else {
+        tx.execute(
+            "INSERT INTO memberships (membership_type, notes, price_cents, duration_days, max_uses, tax_rate_id, allowed_weekdays)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                payload.name,
+                payload.description,
+                payload.price_cents,
+                payload.duration_days,
+                payload.max_uses,
+                payload.tax_rate_id,
+                allowed_weekdays
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+        tx.last_insert_rowid()
+    };
+    for window in &payload.time_windows {
+        tx.execute(
+            "INSERT INTO membership_time_windows (membership_id, start_time, end_time) VALUES (?1, ?2, ?3)",
+            params![id, window.start_time, window.end_time],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(id)
+}
+
+#[tauri::command]
+fn delete_membership(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    id: i64,
+) -> Result<(), String> {
+    session.require(Role::Manager)?;
+    let conn = db.connect().map_err(|e| e.to_string())?;
+    conn.execute("DELETE FROM memberships WHERE id = ?", [id])
+        .map_err(|e| e.to_string())
+        .map(|_| ())
+}
+
+#[tauri::command]
+fn list_member_memberships(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+) -> Result<Vec<MemberMembershipRecord>, String> {
+    session.require(Role::User)?;
+    let conn = db.connect().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(
+            "
+        SELECT mm.id,
+               mm.member_id,
+               mm.membership_id,
+               ms.membership_type,
+               mm.remaining_uses,
+               mm.start_date,
+               mm.end_date,
+               mm.frozen_since,
+               mm.renewed_from_id,
+               mm.created_at
+        FROM member_memberships mm
+        JOIN memberships ms ON ms.id = mm.membership_id
+        ORDER BY mm.member_id, mm.created_at DESC
+        ",
+        )
+        .map_err(|e| e.to_string())?;
+
+    let rows = stmt
+        .query_map([], |row| {
+            Ok(MemberMembershipRecord {
+                id: row.get(0)?,
+                member_id: row.get(1)?,
+                membership_id: row.get(2)?,
+                membership_name: row.get(3)?,
+                remaining_uses: row.get(4)?,
+                start_date: row.get(5)?,
+                end_date: row.get(6)?,
+                frozen_since: row.get(7)?,
+                renewed_from_id: row.get(8)?,
+                created_at: row.get(9)?,
+            })
+        })
+        .map_err(|e| e.to_string())?;
+
+    rows.collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn assign_member_membership(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    payload: AssignMemberMembershipPayload,
+) -> Result<i64, String> {
+    let user = session.require(Role::User)?;
+    let conn = db.connect().map_err(|e| e.to_string())?;
+    grant_membership(
+        &conn,
+        payload.member_id,
+        payload.membership_id,
+        None,
+        Some(user.id),
+    )
+}
+
+fn grant_membership(
+    conn: &Connection,
+    member_id: i64,
+    membership_id: i64,
+    transaction_id: Option<i64>,
+    user_id: Option<i64>,
+) -> Result<i64, String> {
+    let (duration_days, max_uses): (Option<i64>, Option<i64>) = conn
+        .query_row(
+            "SELECT duration_days, max_uses FROM memberships WHERE id = ?",
+            [membership_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .map_err(|_| "Mitgliedschaft nicht gefunden".to_string())?;
+
+    let end_interval = duration_days.map(|days| format!("+{} days", days));
+
+    conn.execute(
+        "INSERT INTO member_memberships (member_id, membership_id, remaining_uses, start_date, end_date, transaction_id)
+        VALUES (?1, ?2, ?3, DATE('now','localtime'), CASE WHEN ?4 IS NOT NULL THEN DATE('now','localtime', ?4) ELSE NULL END, ?5)",
+        params![member_id, membership_id, max_uses, end_interval, transaction_id],
+    )
+    .map_err(|e| e.to_string())?;
+    let id = conn.last_insert_rowid();
+
+    let end_date: Option<String> = conn
+        .query_row(
+            "SELECT end_date FROM member_memberships WHERE id = ?",
+            [id],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    record_membership_event(
+        conn,
+        id,
+        "assigned",
+        None,
+        end_date.as_deref(),
+        None,
+        user_id,
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(id)
+}
+
+fn record_membership_event(
+    conn: &Connection,
+    member_membership_id: i64,
+    kind: &str,
+    previous_end_date: Option<&str>,
+    new_end_date: Option<&str>,
+    reason: Option<&str>,
+    user_id: Option<i64>,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO member_membership_events (
+            member_membership_id,
+            kind,
+            days,
+            previous_end_date,
+            new_end_date,
+            reason,
+            user_id
+        )
+        VALUES (
+            ?1,
+            ?2,
+            CAST(julianday(?4) - julianday(?3) AS INTEGER),
+            ?3,
+            ?4,
+            ?5,
+            ?6
+        )",
+        params![
+            member_membership_id,
+            kind,
+            previous_end_date,
+            new_end_date,
+            reason,
+            user_id
+        ],
+    )?;
+    Ok(())
+}
+
+/// Moves the end date of an entry and of every renewal chained to it.
+fn shift_member_membership(
+    conn: &Connection,
+    member_membership_id: i64,
+    kind: &str,
+    days: i64,
+    reason: Option<&str>,
+    user_id: i64,
+) -> Result<String, String> {
+    let previous_end_date: String = conn
+        .query_row(
+            "SELECT end_date FROM member_memberships WHERE id = ?",
+            [member_membership_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    let new_end_date: String = conn
+        .query_row(
+            "UPDATE member_memberships
+            SET end_date = DATE(end_date, ?1 || ' days')
+            WHERE id = ?2
+            RETURNING end_date",
+            params![format!("{:+}", days), member_membership_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    record_membership_event(
+        conn,
+        member_membership_id,
+        kind,
+        Some(&previous_end_date),
+        Some(&new_end_date),
+        reason,
+        Some(user_id),
+    )
+    .map_err(|e| e.to_string())?;
+
+    let renewal: Option<i64> = conn
+        .query_row(
+            "SELECT id FROM member_memberships WHERE renewed_from_id = ? AND end_date IS NOT NULL",
+            [member_membership_id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    if let Some(renewal_id) = renewal {
+        conn.execute(
+            "UPDATE member_memberships SET start_date = DATE(start_date, ?1 || ' days') WHERE id = ?2",
+            params![format!("{:+}", days), renewal_id],
+        )
+        .map_err(|e| e.to_string())?;
+        let reason = format!("Anschluss an #{}", member_membership_id);
+        shift_member_membership(conn, renewal_id, "shifted", days, Some(&reason), user_id)?;
+    }
+    Ok(new_end_date)
+}
+
+fn load_member_membership_state(
+    conn: &Connection,
+    member_membership_id: i64,
+) -> Result<(Option<String>, Option<String>), String> {
+    conn.query_row(
+        "SELECT end_date, frozen_since FROM member_memberships WHERE id = ?",
+        [member_membership_id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+    .optional()
+    .map_err(|e| e.to_string())?
+    .ok_or_else(|| "Mitgliedschaft nicht gefunden".to_string())
+}
+
+#[tauri::command]
+fn renew_member_membership(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    payload: MemberMembershipChangePayload,
+) -> Result<i64, String> {
+    // Renewing here grants a paid period without a sale, so it is a manager's free grant.
+    let user = session.require(Role::Manager)?;
+    let mut conn = db.connect().map_err(|e| e.to_string())?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+
+    let (member_id, membership_id, previous_end_date, duration_days, max_uses): (
+        i64,
+        i64,
+        Option<String>,
+        Option<i64>,
+        Option<i64>,
+    ) = tx
+        .query_row(
+            "SELECT mm.member_id, mm.membership_id, mm.end_date, ms.duration_days, ms.max_uses
+            FROM member_memberships mm
+            JOIN memberships ms ON ms.id = mm.membership_id
+            WHERE mm.id = ?",
+            [payload.member_membership_id],
+            |row| {
+                Ok((
+                    row.get(0)?,
+                    row.get(1)?,
+                    row.get(2)?,
+                    row.get(3)?,
+                    row.get(4)?,
+                ))
+            },
+        )
+        .optional()
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "Mitgliedschaft nicht gefunden".to_string())?;
+    let already_renewed: Option<i64> = tx
+        .query_row(
+            "SELECT id FROM member_memberships WHERE renewed_from_id = ?",
+            [payload.member_membership_id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    if let Some(renewal_id) = already_renewed {
+        return Err(format!(
+            "Mitgliedschaft wurde bereits verlängert (#{}).",
+            renewal_id
+        ));
+    }
+
+    // The new period starts the day after the previous one ends, or today if it has already expired.
+    let end_interval = duration_days.map(|days| format!("+{} days", days));
+    tx.execute(
+        "INSERT INTO member_memberships (member_id, membership_id, remaining_uses, start_date, end_date, renewed_from_id)
+        VALUES (
+            ?1,
+            ?2,
+            ?3,
+            MAX(COALESCE(DATE(?4, '+1 day'), ''), DATE('now', 'localtime')),
+            CASE WHEN ?5 IS NOT NULL
+                THEN DATE(MAX(COALESCE(DATE(?4, '+1 day'), ''), DATE('now', 'localtime')), ?5)
+                ELSE NULL
+            END,
+            ?6
+        )",
+        params![
+            member_id,
+            membership_id,
+            max_uses,
+            previous_end_date,
+            end_interval,
+            payload.member_membership_id
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+    let id = tx.last_insert_rowid();
+    let (end_date, _) = load_member_membership_state(&tx, id)?;
+    let reason = payload
+        .reason
+        .as_deref()
+        .map(str::trim)
+        .filter(|reason| !reason.is_empty())
+        .map(str::to_string)
+        .unwrap_or_else(|| format!("Anschluss an #{}", payload.member_membership_id));
+    let reason = format!("Kostenlos verlängert: {}", reason);
+    record_membership_event(
+        &tx,
+        id,
+        "renewed",
+        previous_end_date.as_deref(),
+        end_date.as_deref(),
+        Some(&reason),
+        Some(user.id),
+    )
+    .map_err(|e| e.to_string())?;
+
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(id)
+}
+
+#[tauri::command]
+fn freeze_member_membership(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    payload: MemberMembershipChangePayload,
+) -> Result<(), String> {
+    let user = session.require(Role::User)?;
+    let mut conn = db.connect().map_err(|e| e.to_string())?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+
+    let (end_date, frozen_since) = load_member_membership_state(&tx, payload.member_membership_id)?;
+    if frozen_since.is_some() {
+        return Err("Mitgliedschaft ist bereits pausiert.".into());
+    }
+    let Some(end_date) = end_date else {
+        return Err("Mitgliedschaften ohne Enddatum können nicht pausiert werden.".into());
+    };
+    let expired: bool = tx
+        .query_row(
+            "SELECT DATE(?) < DATE('now', 'localtime')",
+            [&end_date],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    if expired {
+        return Err("Abgelaufene Mitgliedschaften können nicht pausiert werden.".into());
+    }
+
+    tx.execute(
+        "UPDATE member_memberships SET frozen_since = DATE('now', 'localtime') WHERE id = ?",
+        [payload.member_membership_id],
+    )
+    .map_err(|e| e.to_string())?;
+    record_membership_event(
+        &tx,
+        payload.member_membership_id,
+        "frozen",
+        Some(&end_date),
+        Some(&end_date),
+        payload.reason.as_deref().map(str::trim),
+        Some(user.id),
+    )
+    .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn unfreeze_member_membership(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    payload: MemberMembershipChangePayload,
+) -> Result<Option<String>, String> {
+    let user = session.require(Role::User)?;
+    let mut conn = db.connect().map_err(|e| e.to_string())?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+
+    let (end_date, frozen_since) = load_member_membership_state(&tx, payload.member_membership_id)?;
+    let frozen_since =
+        frozen_since.ok_or_else(|| "Mitgliedschaft ist nicht pausiert.".to_string())?;
+    let paused_days: i64 = tx
+        .query_row(
+            "SELECT CAST(julianday(DATE('now', 'localtime')) - julianday(DATE(?)) AS INTEGER)",
+            [&frozen_since],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+
+    tx.execute(
+        "UPDATE member_memberships SET frozen_since = NULL WHERE id = ?",
+        [payload.member_membership_id],
+    )
+    .map_err(|e| e.to_string())?;
+    let reason = payload.reason.as_deref().map(str::trim);
+    let end_date = if paused_days > 0 {
+        Some(shift_member_membership(
+            &tx,
+            payload.member_membership_id,
+            "unfrozen",
+            paused_days,
+            reason,
+            user.id,
+        )?)
+    } else {
+        record_membership_event(
+            &tx,
+            payload.member_membership_id,
+            "unfrozen",
+            end_date.as_deref(),
+            end_date.as_deref(),
+            reason,
+            Some(user.id),
+        )
+        .map_err(|e| e.to_string())?;
+        end_date
+    };
+
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(end_date)
+}
+
+#[tauri::command]
+fn extend_member_membership(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    payload: ExtendMemberMembershipPayload,
+) -> Result<String, String> {
+    let user = session.require(Role::Manager)?;
+    let reason = payload.reason.trim();
+    if reason.is_empty() {
+        return Err("Grund für die Verlängerung angeben.".into());
+    }
+    if payload.days <= 0 {
+        return Err("Anzahl der Tage muss größer als 0 sein.".into());
+    }
+    let mut conn = db.connect().map_err(|e| e.to_string())?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+
+    let (end_date, _) = load_member_membership_state(&tx, payload.member_membership_id)?;
+    if end_date.is_none() {
+        return Err("Mitgliedschaft hat kein Enddatum.".into());
+    }
+    let new_end_date = shift_member_membership(
+        &tx,
+        payload.member_membership_id,
+        "extended",
+        payload.days,
+        Some(reason),
+        user.id,
+    )?;
+
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(new_end_date)
+}
+
+#[tauri::command]
+fn list_member_membership_events(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    member_membership_id: i64,
+) -> Result<Vec<MemberMembershipEventRecord>, String> {
+    session.require(Role::User)?;
+    let conn = db.connect().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(
+            "
+        SELECT e.id,
+               e.member_membership_id,
+               e.kind,
+               e.days,
+               e.previous_end_date,
+               e.new_end_date,
+               e.reason,
+               u.display_name,
+               e.created_at
+        FROM member_membership_events e
+        LEFT JOIN users u ON u.id = e.user_id
+        WHERE e.member_membership_id = ?
+        ORDER BY e.id DESC
+        ",
+        )
+        .map_err(|e| e.to_string())?;
+
+    let rows = stmt
+        .query_map([member_membership_id], |row| {
+            Ok(MemberMembershipEventRecord {
+                id: row.get(0)?,
+                member_membership_id: row.get(1)?,
+                kind: row.get(2)?,
+                days: row.get(3)?,
+                previous_end_date: row.get(4)?,
+                new_end_date: row.get(5)?,
+                reason: row.get(6)?,
+                user_name: row.get(7)?,
+                created_at: row.get(8)?,
+            })
+        })
+        .map_err(|e| e.to_string())?;
+
+    rows.collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn delete_member_membership(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    id: i64,
+) -> Result<(), String> {
+    session.require(Role::Manager)?;
+    let conn = db.connect().map_err(|e| e.to_string())?;
+    conn.execute("DELETE FROM member_memberships WHERE id = ?", [id])
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[tauri::command]
+fn list_contracts(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+) -> Result<Vec<ContractRecord>, String> {
+    session.require(Role::User)?;
+    let conn = db.connect().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(
+            "
+        SELECT c.id,
+               c.member_id,
+               m.first_name || ' ' || m.last_name,
+               c.membership_id,
+               ms.membership_type,
+               c.amount_cents,
+               c.interval_months,
+               c.mandate_reference,
+               c.mandate_signed_on,
+               c.start_date,
+               c.end_date,
+               c.cancelled_at,
+               c.next_due_date,
+               c.created_at
+        FROM membership_contracts c
+        JOIN members m ON m.id = c.member_id
+        JOIN memberships ms ON ms.id = c.membership_id
+        ORDER BY m.last_name COLLATE NOCASE, m.first_name COLLATE NOCASE, c.id
+        ",
+        )
+        .map_err(|e| e.to_string())?;
+
+    let rows = stmt
+        .query_map([], |row| {
+            Ok(ContractRecord {
+                id: row.get(0)?,
+                member_id: row.get(1)?,
+                member_name: row.get(2)?,
+                membership_id: row.get(3)?,
+                membership_name: row.get(4)?,
+                amount_cents: row.get(5)?,
+                interval_months: row.get(6)?,
+                mandate_reference: row.get(7)?,
+                mandate_signed_on: row.get(8)?,
+                start_date: row.get(9)?,
+                end_date: row.get(10)?,
+                cancelled_at: row.get(11)?,
+                next_due_date: row.get(12)?,
+                created_at: row.get(13)?,
+            })
+        })
+        .map_err(|e| e.to_string())?;
+
+    rows.collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn save_contract(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    payload: ContractPayload,
+) -> Result<i64, String> {
+    session.require(Role::Manager)?;
+    if payload.amount_cents <= 0 {
+        return Err("Betrag muss größer als 0 sein.".into());
+    }
+    if ![1, 3, 6, 12].contains(&payload.interval_months) {
+        return Err("Abrechnungsintervall muss 1, 3, 6 oder 12 Monate betragen.".into());
+    }
+    let mandate_reference = sepa::validate_mandate_reference(&payload.mandate_reference)?;
+    let conn = db.connect().map_err(|e| e.to_string())?;
+    for (label, value) in [
+        ("Datum der Mandatsunterschrift", &payload.mandate_signed_on),
+        ("Vertragsbeginn", &payload.start_date),
+    ] {
+        if !is_iso_date(&conn, value)? {
+            return Err(format!("{} ist kein gültiges Datum (JJJJ-MM-TT).", label));
+        }
+    }
+    let iban: Option<String> = conn
+        .query_row(
+            "SELECT iban FROM members WHERE id = ?",
+            [payload.member_id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "Mitglied nicht gefunden".to_string())?;
+    if iban.is_none() {
+        return Err("Für Lastschriften zuerst die IBAN beim Mitglied hinterlegen.".into());
+    }
+
+    if let Some(id) = payload.id {
+        let invoiced: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM contract_invoices WHERE contract_id = ?",
+                [id],
+                |row| row.get(0),
+            )
+            .map_err(|e| e.to_string())?;
+        // Once billed, the schedule is fixed; only amount and mandate data may change.
+        conn.execute(
+            "UPDATE membership_contracts
+            SET membership_id = ?1,
+                amount_cents = ?2,
+                interval_months = CASE WHEN ?7 = 0 THEN ?3 ELSE interval_months END,
+                mandate_reference = ?4,
+                mandate_signed_on = ?5,
+                start_date = CASE WHEN ?7 = 0 THEN ?6 ELSE start_date END,
+                next_due_date = CASE WHEN ?7 = 0 THEN ?6 ELSE next_due_date END,
+                updated_at = CURRENT_TIMESTAMP
+            WHERE id = ?8",
+            params![
+                payload.membership_id,
+                payload.amount_cents,
+                payload.interval_months,
+                mandate_reference,
+                payload.mandate_signed_on,
+                payload.start_date,
+                invoiced,
+                id
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+        Ok(id)
+    } else {
+        conn.execute(
+            "INSERT INTO membership_contracts (
+                member_id,
+                membership_id,
+                amount_cents,
+                interval_months,
+                mandate_reference,
+                mandate_signed_on,
+                start_date,
+                next_due_date
+            )
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?7)",
+            params![
+                payload.member_id,
+                payload.membership_id,
+                payload.amount_cents,
+                payload.interval_months,
+                mandate_reference,
+                payload.mandate_signed_on,
+                payload.start_date
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+        Ok(conn.last_insert_rowid())
+    }
+}
+
+#[tauri::command]
+fn cancel_contract(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    payload: CancelContractPayload,
+) -> Result<(), String> {
+    session.require(Role::Manager)?;
+    let conn = db.connect().map_err(|e| e.to_string())?;
+    if !is_iso_date(&conn, &payload.end_date)? {
+        return Err("Vertragsende ist kein gültiges Datum (JJJJ-MM-TT).".into());
+    }
+    let start_date: String = conn
+        .query_row(
+            "SELECT start_date FROM membership_contracts WHERE id = ?",
+            [payload.contract_id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "Vertrag nicht gefunden".to_string())?;
+    if payload.end_date < start_date {
+        return Err("Vertragsende liegt vor dem Vertragsbeginn.".into());
+    }
+    conn.execute(
+        "UPDATE membership_contracts
+        SET end_date = ?1, cancelled_at = CURRENT_TIMESTAMP, updated_at = CURRENT_TIMESTAMP
+        WHERE id = ?2",
+        params![payload.end_date, payload.contract_id],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[tauri::command]
+fn run_contract_billing(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    payload: BillingRunPayload,
+) -> Result<BillingRunRecord, String> {
+    let user = session.require(Role::Manager)?;
+    let mut conn = db.connect().map_err(|e| e.to_string())?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let until: String = match payload.until {
+        Some(until) => {
+            if !is_iso_date(&tx, &until)? {
+                return Err("Stichtag ist kein gültiges Datum (JJJJ-MM-TT).".into());
+            }
+            until
+        }
+        None => tx
+            .query_row("SELECT DATE('now', 'localtime')", [], |row| row.get(0))
+            .map_err(|e| e.to_string())?,
+    };
+
+    let mut record = BillingRunRecord {
+        invoice_count: 0,
+        total_cents: 0,
+    };
+    loop {
+        // Members with a returned debit are skipped until the return is cleared.
+        let due: Option<(i64, i64, i64, i64, i64, String)> = tx
+            .query_row(
+                "SELECT c.id,
+                        c.member_id,
+                        c.membership_id,
+                        c.amount_cents,
+                        c.interval_months,
+                        c.next_due_date
+                FROM membership_contracts c
+                JOIN members m ON m.id = c.member_id
+                WHERE c.next_due_date <= ?1
+                  AND (c.end_date IS NULL OR c.next_due_date <= c.end_date)
+                  AND m.debit_returned_at IS NULL
+                ORDER BY c.next_due_date ASC, c.id ASC
+                LIMIT 1",
+                [&until],
+                |row| {
+                    Ok((
+                        row.get(0)?,
+                        row.get(1)?,
+                        row.get(2)?,
+                        row.get(3)?,
+                        row.get(4)?,
+                        row.get(5)?,
+                    ))
+                },
+            )
+            .optional()
+            .map_err(|e| e.to_string())?;
+        let Some((contract_id, member_id, membership_id, amount_cents, interval_months, due_date)) =
+            due
+        else {
+            break;
+        };
+        let (start_date, invoiced): (String, i64) = tx
+            .query_row(
+                "SELECT c.start_date,
+                        (SELECT COUNT(*) FROM contract_invoices WHERE contract_id = c.id)
+                FROM membership_contracts c
+                WHERE c.id = ?",
+                [contract_id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .map_err(|e| e.to_string())?;
+        // Every due date is counted from the start date so that contracts starting
+        // at the end of a month do not drift (01-31, 02-29, 03-31, …).
+        let next_due_date = calendar::add_months(&start_date, (invoiced + 1) * interval_months)
+            .ok_or_else(|| format!("Vertrag #{} hat kein gültiges Startdatum.", contract_id))?;
+        let period_end: String = tx
+            .query_row("SELECT DATE(?, '-1 day')", [&next_due_date], |row| {
+                row.get(0)
+            })
+            .map_err(|e| e.to_string())?;
+
+        let max_uses: Option<i64> = tx
+            .query_row(
+                "SELECT max_uses FROM memberships WHERE id = ?",
+                [membership_id],
+                |row| row.get(0),
+            )
+            .map_err(|e| e.to_string())?;
+        tx.execute(
+            "INSERT INTO member_memberships (member_id, membership_id, remaining_uses, start_date, end_date)
+            VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![member_id, membership_id, max_uses, due_date, period_end],
+        )
+        .map_err(|e| e.to_string())?;
+        let member_membership_id = tx.last_insert_rowid();
+        record_membership_event(
+            &tx,
+            member_membership_id,
+            "assigned",
+            None,
+            Some(&period_end),
+            Some(&format!("Vertrag #{}", contract_id)),
+            Some(user.id),
+        )
+        .map_err(|e| e.to_string())?;
+
+        tx.execute(
+            "INSERT INTO contract_invoices (
+                contract_id,
+                member_id,
+                member_membership_id,
+                period_start,
+                period_end,
+                due_date,
+                amount_cents
+            )
+            VALUES (?1, ?2, ?3, ?4, ?5, ?4, ?6)",
+            params![
+                contract_id,
+                member_id,
+                member_membership_id,
+                due_date,
+                period_end,
+                amount_cents
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+        let invoice_id = tx.last_insert_rowid();
+        tx.execute(
+            "UPDATE contract_invoices SET end_to_end_id = ?1 WHERE id = ?2",
+            params![format!("BPOS-R{}", invoice_id), invoice_id],
+        )
+        .map_err(|e| e.to_string())?;
+        tx.execute(
+            "UPDATE membership_contracts
+            SET next_due_date = ?1, updated_at = CURRENT_TIMESTAMP
+            WHERE id = ?2",
+            params![next_due_date, contract_id],
+        )
+        .map_err(|e| e.to_string())?;
+
+        record.invoice_count += 1;
+        record.total_cents += amount_cents;
+    }
+
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(record)
+}
+
+#[tauri::command]
+fn list_contract_invoices(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    payload: ContractInvoicesRequest,
+) -> Result<Vec<ContractInvoiceRecord>, String> {
+    session.require(Role::Manager)?;
+    let conn = db.connect().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(
+            "
+        SELECT ci.id,
+               ci.contract_id,
+               ci.member_id,
+               m.first_name || ' ' || m.last_name,
+               ci.period_start,
+               ci.period_end,
+               ci.due_date,
+               ci.amount_cents,
+               ci.status,
+               ci.end_to_end_id,
+               ci.sequence_type,
+               ci.return_reason,
+               ci.returned_at
+        FROM contract_invoices ci
+        JOIN members m ON m.id = ci.member_id
+        WHERE ?1 IS NULL OR ci.status = ?1
+        ORDER BY ci.due_date DESC, ci.id DESC
+        ",
+        )
+        .map_err(|e| e.to_string())?;
+
+    let rows = stmt
+        .query_map([payload.status], |row| {
+            Ok(ContractInvoiceRecord {
+                id: row.get(0)?,
+                contract_id: row.get(1)?,
+                member_id: row.get(2)?,
+                member_name: row.get(3)?,
+                period_start: row.get(4)?,
+                period_end: row.get(5)?,
+                due_date: row.get(6)?,
+                amount_cents: row.get(7)?,
+                status: row.get(8)?,
+                end_to_end_id: row.get(9)?,
+                sequence_type: row.get(10)?,
+                return_reason: row.get(11)?,
+                returned_at: row.get(12)?,
+            })
+        })
+        .map_err(|e| e.to_string())?;
+
+    rows.collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn export_sepa_direct_debit(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    settings: State<SettingsState>,
+    payload: ExportSepaPayload,
+) -> Result<sepa::SepaExportRecord, String> {
+    let user = session.require(Role::Manager)?;
+    let target_dir = payload.target_dir.trim();
+    if target_dir.is_empty() {
+        return Err("Zielverzeichnis fehlt.".into());
+    }
+    let settings = settings.get()?;
+    let mut conn = db.connect().map_err(|e| e.to_string())?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let record = sepa::export(
+        &tx,
+        &settings.sepa,
+        &PathBuf::from(target_dir),
+        payload.collection_date.trim(),
+        user.id,
+    )?;
+    if let Err(error) = tx.commit() {
+        sepa::discard_export(&record);
+        return Err(error.to_string());
+    }
+    sepa::finish_export(&record)?;
+    Ok(record)
+}
+
+#[tauri::command]
+fn import_sepa_returns(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    payload: ImportSepaReturnsPayload,
+) -> Result<sepa::SepaReturnRecord, String> {
+    session.require(Role::Manager)?;
+    let mut conn = db.connect().map_err(|e| e.to_string())?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let record = sepa::import_returns(&tx, &PathBuf::from(payload.file_path.trim()))?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(record)
+}
+
+#[tauri::command]
+fn clear_debit_return_flag(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    member_id: i64,
+) -> Result<(), String> {
+    session.require(Role::Manager)?;
+    let conn = db.connect().map_err(|e| e.to_string())?;
+    let updated = conn
+        .execute(
+            "UPDATE members SET debit_returned_at = NULL, updated_at = CURRENT_TIMESTAMP WHERE id = ?",
+            [member_id],
+        )
+        .map_err(|e| e.to_string())?;
+    if updated == 0 {
+        return Err("Mitglied nicht gefunden".into());
+    }
+    Ok(())
+}
+
+fn is_iso_date(conn: &Connection, value: &str) -> Result<bool, String> {
+    conn.query_row("SELECT DATE(?1) IS ?1", [value], |row| row.get(0))
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn cancel_transaction(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    settings: State<SettingsState>,
+    tse: State<TseState>,
+    payload: CancelTransactionPayload,
+) -> Result<i64, CheckoutError> {
+    let user = session.require(Role::Manager)?;
+    let settings = settings.get()?;
+    let client_id = settings.business.cash_register_id;
+    let reason = payload.reason.trim();
+    if reason.is_empty() {
+        return Err("Grund für die Stornierung angeben.".into());
+    }
+
+    let mut conn = db.connect().map_err(|e| e.to_string())?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+
+    let original = tx
+        .query_row(
+            "SELECT product_id, quantity, total_cents, description, member_id, cancels_transaction_id
+            FROM transactions WHERE id = ?",
+            [payload.transaction_id],
+            |row| {
+                Ok((
+                    row.get::<_, Option<i64>>(0)?,
+                    row.get::<_, i64>(1)?,
+                    row.get::<_, i64>(2)?,
+                    row.get::<_, Option<String>>(3)?,
+                    row.get::<_, Option<i64>>(4)?,
+                    row.get::<_, Option<i64>>(5)?,
+                ))
+            },
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    let (product_id, quantity, total_cents, description, member_id, cancels_transaction_id) =
+        original.ok_or_else(|| "Transaktion nicht gefunden".to_string())?;
+    if cancels_transaction_id.is_some() {
+        return Err("Eine Stornobuchung kann nicht storniert werden.".into());
+    }
+    let already_cancelled = tx
+        .query_row(
+            "SELECT id FROM transactions WHERE cancels_transaction_id = ?",
+            [payload.transaction_id],
+            |row| row.get::<_, i64>(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    if already_cancelled.is_some() {
+        return Err("Transaktion wurde bereits storniert.".into());
+    }
+
+    // Reversing a top-up takes the amount back from the balance, which must not
+    // silently push the member below the credit limit.
+    let reversed_credits: Vec<(i64, i64)> = {
+        let mut stmt = tx
+            .prepare(
+                "SELECT member_id, SUM(amount_cents) FROM balance_entries
+                WHERE transaction_id = ?
+                GROUP BY member_id
+                HAVING SUM(amount_cents) > 0
+                ORDER BY member_id",
+            )
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map([payload.transaction_id], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .map_err(|e| e.to_string())?;
+        rows.collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?
+    };
+    let mut credit_approvals = Vec::new();
+    for (balance_member_id, credit_cents) in reversed_credits {
+        if let Some(approval) = approve_balance_debit(
+            &tx,
+            balance_member_id,
+            credit_cents,
+            settings.credit_limit_cents,
+            payload.credit_override.as_ref(),
+        )? {
+            credit_approvals.push(approval);
+        }
+    }
+
+    let cash_session_id = require_cash_session(&tx)?;
+    tx.execute(
+        "INSERT INTO transactions (product_id, quantity, total_cents, description, member_id, user_id, cash_session_id, cancels_transaction_id)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        params![
+            product_id,
+            -quantity,
+            -total_cents,
+            format!(
+                "Storno zu #{}: {} ({})",
+                payload.transaction_id,
+                description.unwrap_or_default(),
+                reason
+            ),
+            member_id,
+            user.id,
+            cash_session_id,
+            payload.transaction_id
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+    let storno_id = tx.last_insert_rowid();
+    for approval in &credit_approvals {
+        record_credit_override(&tx, storno_id, approval, user.id)?;
+    }
+
+    tx.execute(
+        "
+        INSERT INTO transaction_lines (
+            transaction_id,
+            product_id,
+            membership_id,
+            product_name,
+            product_type_id,
+            product_type_name,
+            unit_price_cents,
+            quantity,
+            line_total_cents,
+            tax_rate_id,
+            tax_rate_name,
+            vat_key,
+            vat_rate_basis_points,
+            net_cents,
+            tax_cents
+        )
+        SELECT ?1,
+               product_id,
+               membership_id,
+               product_name,
+               product_type_id,
+               product_type_name,
+               unit_price_cents,
+               -quantity,
+               -line_total_cents,
+               tax_rate_id,
+               tax_rate_name,
+               vat_key,
+               vat_rate_basis_points,
+               -net_cents,
+               -tax_cents
+        FROM transaction_lines
+        WHERE transaction_id = ?2
+        ORDER BY id ASC
+        ",
+        params![storno_id, payload.transaction_id],
+    )
+    .map_err(|e| e.to_string())?;
+
+    tx.execute(
+        "
+        INSERT INTO transaction_payments (transaction_id, method, amount_cents, member_id)
+        SELECT ?1, method, -amount_cents, member_id
+        FROM transaction_payments
+        WHERE transaction_id = ?2
+        ORDER BY id ASC
+        ",
+        params![storno_id, payload.transaction_id],
+    )
+    .map_err(|e| e.to_string())?;
+
+    let storno_reason = format!("Storno zu #{}", payload.transaction_id);
+    tx.execute(
+        "
+        INSERT INTO balance_entries (member_id, kind, amount_cents, transaction_id, reason, user_id)
+        SELECT member_id,
+               CASE kind WHEN 'purchase' THEN 'refund' ELSE kind END,
+               -amount_cents,
+               ?1,
+               ?3,
+               ?4
+        FROM balance_entries
+        WHERE transaction_id = ?2
+        ORDER BY id ASC
+        ",
+        params![storno_id, payload.transaction_id, storno_reason, user.id],
+    )
+    .map_err(|e| e.to_string())?;
+    // Sales from before the ledger only recorded the balance payment itself.
+    tx.execute(
+        "
+        INSERT INTO balance_entries (member_id, kind, amount_cents, transaction_id, reason, user_id)
+        SELECT member_id, 'refund', SUM(amount_cents), ?1, ?3, ?4
+        FROM transaction_payments
+        WHERE transaction_id = ?2
+          AND method = 'balance'
+          AND member_id IS NOT NULL
+          AND NOT EXISTS (SELECT 1 FROM balance_entries WHERE transaction_id = ?2)
+        GROUP BY member_id
+        ",
+        params![storno_id, payload.transaction_id, storno_reason, user.id],
+    )
+    .map_err(|e| e.to_string())?;
+
+    // Memberships sold with the cancelled sale stop being valid from today on.
+    tx.execute(
+        "UPDATE member_memberships SET end_date = DATE('now', 'localtime', '-1 day') WHERE transaction_id = ?",
+        [payload.transaction_id],
+    )
+    .map_err(|e| e.to_string())?;
+
+    append_journal_entry(&tx, storno_id, "storno").map_err(|e| e.to_string())?;
+    sign_and_commit(tx, &db, tse.device(), &client_id, storno_id)?;
+    Ok(storno_id)
+}
+
+#[tauri::command]
+fn get_receipt(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    settings: State<SettingsState>,
+    transaction_id: i64,
+) -> Result<receipt::Receipt, String> {
+    session.require(Role::User)?;
+    let settings = settings.get()?;
+    let conn = db.connect().map_err(|e| e.to_string())?;
+    receipt::load(
+        &conn,
+        &settings.business,
+        &settings.currency,
+        transaction_id,
+        true,
+    )
+}
+
+#[tauri::command]
+fn get_receipt_text(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    settings: State<SettingsState>,
+    transaction_id: i64,
+) -> Result<String, String> {
+    session.require(Role::User)?;
+    let settings = settings.get()?;
+    let conn = db.connect().map_err(|e| e.to_string())?;
+    let receipt = receipt::load(
+        &conn,
+        &settings.business,
+        &settings.currency,
+        transaction_id,
+        true,
+    )?;
+    Ok(receipt::render_text(
+        &receipt,
+        receipt::line_width(settings.printer.paper_width_mm),
+    ))
+}
+
+#[tauri::command]
+fn save_receipt_pdf(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    settings: State<SettingsState>,
+    payload: SaveReceiptPdfPayload,
+) -> Result<String, String> {
+    session.require(Role::Manager)?;
+    let target_dir = PathBuf::from(payload.target_dir.trim());
+    if target_dir.as_os_str().is_empty() {
+        return Err("Zielordner fehlt.".into());
+    }
+    if !target_dir.is_dir() {
+        return Err("Zielordner existiert nicht.".into());
+    }
+    let settings = settings.get()?;
+    let conn = db.connect().map_err(|e| e.to_string())?;
+    let receipt = receipt::load(
+        &conn,
+        &settings.business,
+        &settings.currency,
+        payload.transaction_id,
+        true,
+    )?;
+    let pdf = receipt::render_pdf(
+        &receipt,
+        receipt::line_width(settings.printer.paper_width_mm),
+    );
+    // The file name is fixed and never replaces an existing file, so the export
+    // cannot be pointed at the database or the settings.
+    let path = target_dir.join(format!("Beleg-{}.pdf", payload.transaction_id));
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&path)
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::AlreadyExists => {
+                format!("Datei {} existiert bereits.", path.display())
+            }
+            _ => e.to_string(),
+        })?;
+    std::io::Write::write_all(&mut file, &pdf).map_err(|e| e.to_string())?;
+    Ok(path.to_string_lossy().to_string())
+}
+
+#[tauri::command]
+fn print_receipt(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    settings: State<SettingsState>,
+    payload: PrintReceiptPayload,
+) -> Result<bool, String> {
+    session.require(Role::User)?;
+    let settings = settings.get()?;
+    if !settings.printer.enabled {
+        return Ok(false);
+    }
+    let conn = db.connect().map_err(|e| e.to_string())?;
+    let receipt = receipt::load(
+        &conn,
+        &settings.business,
+        &settings.currency,
+        payload.transaction_id,
+        payload.copy.unwrap_or(false),
+    )?;
+    let bytes = receipt::render_escpos(
+        &receipt,
+        receipt::line_width(settings.printer.paper_width_mm),
+    );
+    receipt::send(&settings.printer, &bytes)?;
+    Ok(true)
+}
+
+#[tauri::command]
+fn get_tse_info(session: State<SessionState>, tse: State<TseState>) -> Result<TseInfo, String> {
+    session.require(Role::User)?;
+    tse.device().info()
+}
+
+#[tauri::command]
+fn export_tse(
+    session: State<SessionState>,
+    tse: State<TseState>,
+    payload: ExportTsePayload,
+) -> Result<String, String> {
+    session.require(Role::Manager)?;
+    let target_dir = payload.target_dir.trim();
+    if target_dir.is_empty() {
+        return Err("Zielverzeichnis fehlt.".into());
+    }
+    tse.device()
+        .export(&PathBuf::from(target_dir))
+        .map(|path| path.to_string_lossy().to_string())
+}
+
+#[tauri::command]
+fn list_pending_tse_signatures(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+) -> Result<Vec<PendingTseSignatureRecord>, String> {
+    session.require(Role::Manager)?;
+    let conn = db.connect().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(
+            "
+        SELECT id,
+               transaction_id,
+               tse_serial,
+               tse_transaction_number,
+               signature_counter,
+               finish_time,
+               process_data,
+               signature,
+               error,
+               created_at,
+               resolved_at,
+               resolution
+        FROM pending_tse_signatures
+        ORDER BY resolved_at IS NOT NULL, signature_counter ASC
+        ",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok(PendingTseSignatureRecord {
+                id: row.get(0)?,
+                transaction_id: row.get(1)?,
+                tse_serial: row.get(2)?,
+                tse_transaction_number: row.get(3)?,
+                signature_counter: row.get(4)?,
+                finish_time: row.get(5)?,
+                process_data: row.get(6)?,
+                signature: row.get(7)?,
+                error: row.get(8)?,
+                created_at: row.get(9)?,
+                resolved_at: row.get(10)?,
+                resolution: row.get(11)?,
+            })
+        })
+        .map_err(|e| e.to_string())?;
+
+    rows.collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn resolve_pending_tse_signature(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    payload: ResolvePendingTseSignaturePayload,
+) -> Result<(), String> {
+    let user = session.require(Role::Manager)?;
+    let resolution = payload.resolution.trim();
+    if resolution.is_empty() {
+        return Err("Bitte die Klärung der Signatur beschreiben.".into());
+    }
+    let conn = db.connect().map_err(|e| e.to_string())?;
+    let updated = conn
+        .execute(
+            "UPDATE pending_tse_signatures
+            SET resolved_at = CURRENT_TIMESTAMP, resolved_by = ?1, resolution = ?2
+            WHERE id = ?3 AND resolved_at IS NULL",
+            params![user.id, resolution, payload.id],
+        )
+        .map_err(|e| e.to_string())?;
+    if updated == 0 {
+        return Err("Offene Signatur nicht gefunden oder bereits geklärt.".into());
+    }
+    Ok(())
+}
+
+#[tauri::command]
+fn verify_journal(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+) -> Result<JournalVerification, String> {
+    session.require(Role::Manager)?;
+    let conn = db.connect().map_err(|e| e.to_string())?;
+    verify_journal_chain(&conn).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn record_checkin(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    settings: State<SettingsState>,
+    payload: CheckinPayload,
+) -> Result<CheckinResult, checkin::CheckinError> {
+    session.require(Role::User)?;
+    let rules = settings.get()?.checkin;
+    let mut conn = db.connect().map_err(|e| e.to_string())?;
+    perform_checkin(&mut conn, &rules, &payload)
+}
+
+#[tauri::command]
+fn checkin_by_card(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    settings: State<SettingsState>,
+    payload: CardCheckinPayload,
+) -> Result<CheckinResult, checkin::CheckinError> {
+    session.require(Role::User)?;
+    let token = cards::normalize_token(&payload.token)?;
+    let rules = settings.get()?.checkin;
+    let mut conn = db.connect().map_err(|e| e.to_string())?;
+    let card = conn
+        .query_row(
+            "SELECT member_id, status FROM member_cards WHERE token = ?",
+            [&token],
+            |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    let (member_id, status) = card.ok_or("Karte ist keinem Mitglied zugeordnet")?;
+    if status != "active" {
+        return Err(checkin::CheckinError::rejected(vec![checkin::Rejection {
+            member_membership_id: None,
+            membership_name: None,
+            reason: cards::status_reason(&status).into(),
+        }]));
+    }
+    perform_checkin(
+        &mut conn,
+        &rules,
+        &CheckinPayload {
+            member_id,
+            member_membership_id: payload.member_membership_id,
+            rope_area: payload.rope_area,
+        },
+    )
+}
+
+fn perform_checkin(
+    conn: &mut Connection,
+    rules: &CheckinSettings,
+    payload: &CheckinPayload,
+) -> Result<CheckinResult, checkin::CheckinError> {
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let moment = checkin::now(&tx).map_err(|e| e.to_string())?;
+    let decision = checkin::decide(
+        &tx,
+        rules,
+        payload.member_id,
+        payload.member_membership_id,
+        &moment,
+    )?;
+    let member_name: String = tx
+        .query_row(
+            "SELECT first_name || ' ' || last_name FROM members WHERE id = ?",
+            [payload.member_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    let mut warnings = Vec::new();
+    if rules.waiver_enforcement != waivers::ENFORCE_OFF {
+        if let Some(reason) = waivers::issue(&tx, payload.member_id).map_err(|e| e.to_string())? {
+            if rules.waiver_enforcement == waivers::ENFORCE_REFUSE {
+                return Err(checkin::CheckinError::rejected(vec![checkin::Rejection {
+                    member_membership_id: None,
+                    membership_name: None,
+                    reason,
+                }]));
+            }
+            warnings.push(reason);
+        }
+    }
+    let belay = certifications::assess(&tx, payload.member_id, payload.rope_area)
+        .map_err(|e| e.to_string())?;
+    if payload.rope_area && rules.require_belay_for_rope {
+        if let Some(reason) = belay.rope_block {
+            return Err(checkin::CheckinError::rejected(vec![checkin::Rejection {
+                member_membership_id: None,
+                membership_name: None,
+                reason,
+            }]));
+        }
+    }
+    warnings.extend(belay.warnings);
+    let certifications = belay.valid;
+
+    let result = match decision {
+        checkin::Decision::Reentry {
+            membership_id,
+            member_membership_id,
+        } => {
+            tx.execute(
+                "INSERT INTO member_checkins (member_id, membership_id, member_membership_id, reentry) VALUES (?1, ?2, ?3, 1)",
+                params![payload.member_id, membership_id, member_membership_id],
+            )
+            .map_err(|e| e.to_string())?;
+            let (membership_name, remaining_uses) = match member_membership_id {
+                Some(id) => tx
+                    .query_row(
+                        "SELECT ms.membership_type, mm.remaining_uses
+                        FROM member_memberships mm
+                        JOIN memberships ms ON ms.id = mm.membership_id
+                        WHERE mm.id = ?",
+                        [id],
+                        |row| Ok((row.get(0)?, row.get(1)?)),
+                    )
+                    .optional()
+                    .map_err(|e| e.to_string())?
+                    .unwrap_or((None, None)),
+                None => (None, None),
+            };
+            CheckinResult {
+                checkin_id: tx.last_insert_rowid(),
+                member_id: payload.member_id,
+                member_name,
+                member_membership_id,
+                membership_name,
+                reentry: true,
+                remaining_uses,
+                certifications,
+                warnings,
+            }
+        }
+        checkin::Decision::Consume(candidate) => {
+            tx.execute(
+                "INSERT INTO member_checkins (member_id, membership_id, member_membership_id) VALUES (?1, ?2, ?3)",
+                params![payload.member_id, candidate.membership_id, candidate.id],
+            )
+            .map_err(|e| e.to_string())?;
+            let checkin_id = tx.last_insert_rowid();
+            let remaining_uses = candidate.remaining_uses.map(|uses| uses.saturating_sub(1));
+            if let Some(uses) = remaining_uses {
+                tx.execute(
+                    "UPDATE member_memberships SET remaining_uses = ? WHERE id = ?",
+                    params![uses, candidate.id],
+                )
+                .map_err(|e| e.to_string())?;
+            }
+            CheckinResult {
+                checkin_id,
+                member_id: payload.member_id,
+                member_name,
+                member_membership_id: Some(candidate.id),
+                membership_name: Some(candidate.membership_name),
+                reentry: false,
+                remaining_uses,
+                certifications,
+                warnings,
+            }
+        }
+    };
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(result)
+}
+
+#[tauri::command]
+fn delete_checkin(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    id: i64,
+) -> Result<(), String> {
+    session.require(Role::Manager)?;
+    let mut conn = db.connect().map_err(|e| e.to_string())?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let details = tx
+        .query_row(
+            "SELECT member_membership_id, reentry FROM member_checkins WHERE id = ?",
+            [id],
+            |row| Ok((row.get::<_, Option<i64>>(0)?, row.get::<_, bool>(1)?)),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+
+    let (member_membership_id, reentry) =
+        details.ok_or_else(|| "Check-in nicht gefunden".to_string())?;
+
+    // Re-entries never consumed a use, so there is nothing to give back.
+    if let Some(mm_id) = member_membership_id.filter(|_| !reentry) {
+        if let Some((remaining_uses, max_uses)) = tx
+            .query_row(
+                "SELECT remaining_uses, ms.max_uses
+                FROM member_memberships mm
+                JOIN memberships ms ON ms.id = mm.membership_id
+                WHERE mm.id = ?",
+                [mm_id],
+                |row| Ok((row.get::<_, Option<i64>>(0)?, row.get::<_, Option<i64>>(1)?)),
+            )
+            .optional()
+            .map_err(|e| e.to_string())?
+        {
+            if let Some(current) = remaining_uses {
+                let cap = max_uses.unwrap_or(i64::MAX);
+                let new_value = (current + 1).min(cap);
+                tx.execute(
+                    "UPDATE member_memberships SET remaining_uses = ? WHERE id = ?",
+                    params![new_value, mm_id],
+                )
+                .map_err(|e| e.to_string())?;
+            }
+        }
+    }
+
+    tx.execute("DELETE FROM member_checkins WHERE id = ?", [id])
+        .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn list_checkins_today(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+) -> Result<Vec<CheckinRecord>, String> {
+    session.require(Role::User)?;
+    let conn = db.connect().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(
+            "
+        SELECT c.id,
+               c.member_id,
+               COALESCE(m.first_name || ' ' || m.last_name, c.guest_name, 'Gast') AS member_name,
+               COALESCE(ms.membership_type, tl.product_name),
+               c.reentry,
+               c.transaction_line_id IS NOT NULL,
+               c.waiver_reference,
+               c.created_at
+        FROM member_checkins c
+        LEFT JOIN members m ON m.id = c.member_id
+        LEFT JOIN transaction_lines tl ON tl.id = c.transaction_line_id
+        LEFT JOIN memberships ms ON ms.id = c.membership_id
+        WHERE DATE(c.created_at, 'localtime') = DATE('now', 'localtime')
+        ORDER BY c.created_at DESC
+        ",
+        )
+        .map_err(|e| e.to_string())?;
+
+    let rows = stmt
+        .query_map([], |row| {
+            Ok(CheckinRecord {
+                id: row.get(0)?,
+                member_id: row.get(1)?,
+                member_name: row.get(2)?,
+                membership_name: row.get(3).ok(),
+                reentry: row.get(4)?,
+                guest: row.get(5)?,
+                waiver_reference: row.get(6)?,
+                created_at: row.get(7)?,
+            })
+        })
+        .map_err(|e| e.to_string())?;
+
+    rows.collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn list_waiver_texts(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+) -> Result<Vec<WaiverTextRecord>, String> {
+    session.require(Role::User)?;
+    let conn = db.connect().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare("SELECT id, version, body, created_at FROM waiver_texts ORDER BY id DESC")
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok(WaiverTextRecord {
+                id: row.get(0)?,
+                version: row.get(1)?,
+                body: row.get(2)?,
+                created_at: row.get(3)?,
+            })
+        })
+        .map_err(|e| e.to_string())?;
+
+    rows.collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn publish_waiver_text(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    payload: WaiverTextPayload,
+) -> Result<i64, String> {
+    let user = session.require(Role::Manager)?;
+    let version = payload.version.trim();
+    if version.is_empty() {
+        return Err("Version des Haftungsausschlusses fehlt".into());
+    }
+    if payload.body.trim().is_empty() {
+        return Err("Text des Haftungsausschlusses fehlt".into());
+    }
+    let conn = db.connect().map_err(|e| e.to_string())?;
+    let exists = conn
+        .query_row(
+            "SELECT 1 FROM waiver_texts WHERE version = ?",
+            [version],
+            |row| row.get::<_, i64>(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    if exists.is_some() {
+        return Err(format!("Version {} existiert bereits", version));
+    }
+    conn.execute(
+        "INSERT INTO waiver_texts (version, body, created_by) VALUES (?1, ?2, ?3)",
+        params![version, payload.body, user.id],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(conn.last_insert_rowid())
+}
+
+#[tauri::command]
+fn record_waiver(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    payload: WaiverPayload,
+) -> Result<i64, String> {
+    let user = session.require(Role::User)?;
+    let signer_name = payload.signer_name.trim();
+    if signer_name.is_empty() {
+        return Err("Name der unterschreibenden Person fehlt".into());
+    }
+    let guardian_name = payload
+        .guardian_name
+        .as_deref()
+        .map(str::trim)
+        .filter(|name| !name.is_empty());
+    let signature = match payload.signature.as_deref().map(str::trim) {
+        Some(value) if !value.is_empty() => Some(waivers::decode_signature(value)?),
+        _ => None,
+    };
+
+    let conn = db.connect().map_err(|e| e.to_string())?;
+    let waiver_text_id = match payload.waiver_text_id {
+        Some(id) => id,
+        None => {
+            waivers::current_text(&conn)
+                .map_err(|e| e.to_string())?
+                .ok_or("Es wurde noch kein Haftungsausschluss hinterlegt")?
+                .0
+        }
+    };
+    let signed_on = match payload.signed_on.as_deref().map(str::trim) {
+        Some(date) if !date.is_empty() => {
+            if !is_iso_date(&conn, date)? {
+                return Err("Datum muss im Format JJJJ-MM-TT angegeben werden".into());
+            }
+            date.to_string()
+        }
+        _ => conn
+            .query_row("SELECT DATE('now', 'localtime')", [], |row| row.get(0))
+            .map_err(|e| e.to_string())?,
+    };
+    let (future, minor): (bool, Option<bool>) = conn
+        .query_row(
+            "SELECT ?1 > DATE('now', 'localtime'),
+                    DATE(birth_date, '+18 years') > ?1
+            FROM members WHERE id = ?2",
+            params![signed_on, payload.member_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?
+        .ok_or("Mitglied nicht gefunden")?;
+    if future {
+        return Err("Unterschriftsdatum darf nicht in der Zukunft liegen".into());
+    }
+    if minor == Some(true) && guardian_name.is_none() {
+        return Err("Bei Minderjährigen muss ein Erziehungsberechtigter zustimmen".into());
+    }
+
+    conn.execute(
+        "INSERT INTO waivers (member_id, waiver_text_id, signed_on, signer_name, guardian_name, signature_mime, signature, recorded_by)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        params![
+            payload.member_id,
+            waiver_text_id,
+            signed_on,
+            signer_name,
+            guardian_name,
+            signature.as_ref().map(|s| s.mime.as_str()),
+            signature.as_ref().map(|s| s.data.as_slice()),
+            user.id
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(conn.last_insert_rowid())
+}
+
+#[tauri::command]
+fn list_member_waivers(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    member_id: i64,
+) -> Result<Vec<WaiverRecord>, String> {
+    session.require(Role::User)?;
+    let conn = db.connect().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(
+            "
+        SELECT w.id,
+               w.member_id,
+               w.waiver_text_id,
+               t.version,
+               w.signed_on,
+               w.signer_name,
+               w.guardian_name,
+               w.signature IS NOT NULL,
+               w.recorded_by,
+               w.created_at
+        FROM waivers w
+        JOIN waiver_texts t ON t.id = w.waiver_text_id
+        WHERE w.member_id = ?
+        ORDER BY w.signed_on DESC, w.id DESC
+        ",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([member_id], |row| {
+            Ok(WaiverRecord {
+                id: row.get(0)?,
+                member_id: row.get(1)?,
+                waiver_text_id: row.get(2)?,
+                version: row.get(3)?,
+                signed_on: row.get(4)?,
+                signer_name: row.get(5)?,
+                guardian_name: row.get(6)?,
+                has_signature: row.get(7)?,
+                recorded_by: row.get(8)?,
+                created_at: row.get(9)?,
+            })
+        })
+        .map_err(|e| e.to_string())?;
+
+    rows.collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn get_waiver_signature(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    waiver_id: i64,
+) -> Result<Option<String>, String> {
+    session.require(Role::User)?;
+    let conn = db.connect().map_err(|e| e.to_string())?;
+    let signature = conn
+        .query_row(
+            "SELECT signature_mime, signature FROM waivers WHERE id = ?",
+            [waiver_id],
+            |row| {
+                Ok((
+                    row.get::<_, Option<String>>(0)?,
+                    row.get::<_, Option<Vec<u8>>>(1)?,
+                ))
+            },
+        )
+        .optional()
+        .map_err(|e| e.to_string())?
+        .ok_or("Haftungsausschluss nicht gefunden")?;
+    Ok(match signature {
+        (Some(mime), Some(data)) => Some(waivers::encode_signature(&mime, &data)),
+        _ => None,
+    })
+}
+
+/// Active members without a signature for the current waiver text.
+#[tauri::command]
+fn list_waiver_gaps(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+) -> Result<Vec<WaiverGapRecord>, String> {
+    session.require(Role::User)?;
+    let conn = db.connect().map_err(|e| e.to_string())?;
+    let Some((current_id, _)) = waivers::current_text(&conn).map_err(|e| e.to_string())? else {
+        return Ok(Vec::new());
+    };
+    let mut stmt = conn
         .prepare(
             "
-        SELECT id,
-               membership_type,
-               notes,
-               price_cents,
-               duration_days,
-               max_uses,
-               created_at,
-               updated_at
-        FROM memberships
-        ORDER BY created_at DESC
+        SELECT m.id,
+               m.first_name || ' ' || m.last_name,
+               m.email,
+               t.version,
+               w.signed_on
+        FROM members m
+        LEFT JOIN waivers w ON w.id = (
+            SELECT id FROM waivers
+            WHERE member_id = m.id
+            ORDER BY waiver_text_id DESC, signed_on DESC, id DESC
+            LIMIT 1
+        )
+        LEFT JOIN waiver_texts t ON t.id = w.waiver_text_id
+        WHERE m.status = 'active'
+          AND NOT EXISTS (SELECT 1 FROM waivers c WHERE c.member_id = m.id AND c.waiver_text_id = ?1)
+        ORDER BY w.id IS NOT NULL, m.last_name COLLATE NOCASE, m.first_name COLLATE NOCASE
+        ",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([current_id], |row| {
+            let signed_version: Option<String> = row.get(3)?;
+            Ok(WaiverGapRecord {
+                member_id: row.get(0)?,
+                member_name: row.get(1)?,
+                email: row.get(2)?,
+                status: if signed_version.is_some() {
+                    "outdated".into()
+                } else {
+                    "missing".into()
+                },
+                signed_version,
+                signed_on: row.get(4)?,
+            })
+        })
+        .map_err(|e| e.to_string())?;
+
+    rows.collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn list_member_certifications(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    member_id: i64,
+) -> Result<Vec<CertificationRecord>, String> {
+    session.require(Role::User)?;
+    let conn = db.connect().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(
+            "
+        SELECT c.id,
+               c.member_id,
+               c.kind,
+               c.issued_on,
+               c.expires_on,
+               c.issued_by,
+               u.display_name,
+               c.notes,
+               c.revoked_at,
+               c.revoke_reason,
+               c.created_at
+        FROM member_certifications c
+        LEFT JOIN users u ON u.id = c.issued_by
+        WHERE c.member_id = ?
+        ORDER BY c.revoked_at IS NOT NULL, c.issued_on DESC, c.id DESC
+        ",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([member_id], |row| {
+            Ok(CertificationRecord {
+                id: row.get(0)?,
+                member_id: row.get(1)?,
+                kind: row.get(2)?,
+                issued_on: row.get(3)?,
+                expires_on: row.get(4)?,
+                issued_by: row.get(5)?,
+                issued_by_name: row.get(6)?,
+                notes: row.get(7)?,
+                revoked_at: row.get(8)?,
+                revoke_reason: row.get(9)?,
+                created_at: row.get(10)?,
+            })
+        })
+        .map_err(|e| e.to_string())?;
+
+    rows.collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn grant_certification(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    payload: CertificationPayload,
+) -> Result<i64, String> {
+    let user = session.require(Role::User)?;
+    if !certifications::KINDS.contains(&payload.kind.as_str()) {
+        return Err("Unbekannte Art des Sicherungsscheins".into());
+    }
+    let conn = db.connect().map_err(|e| e.to_string())?;
+    let issued_on = match payload.issued_on.as_deref().map(str::trim) {
+        Some(date) if !date.is_empty() => date.to_string(),
+        _ => conn
+            .query_row("SELECT DATE('now', 'localtime')", [], |row| row.get(0))
+            .map_err(|e| e.to_string())?,
+    };
+    let expires_on = payload
+        .expires_on
+        .as_deref()
+        .map(str::trim)
+        .filter(|date| !date.is_empty());
+    for date in std::iter::once(issued_on.as_str()).chain(expires_on) {
+        if !is_iso_date(&conn, date)? {
+            return Err("Datum muss im Format JJJJ-MM-TT angegeben werden".into());
+        }
+    }
+    if expires_on.is_some_and(|expires_on| expires_on <= issued_on.as_str()) {
+        return Err("Ablaufdatum muss nach dem Ausstellungsdatum liegen".into());
+    }
+    let exists = conn
+        .query_row(
+            "SELECT 1 FROM members WHERE id = ?",
+            [payload.member_id],
+            |row| row.get::<_, i64>(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    if exists.is_none() {
+        return Err("Mitglied nicht gefunden".into());
+    }
+    let notes = payload
+        .notes
+        .as_deref()
+        .map(str::trim)
+        .filter(|notes| !notes.is_empty());
+    conn.execute(
+        "INSERT INTO member_certifications (member_id, kind, issued_on, expires_on, issued_by, notes)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            payload.member_id,
+            payload.kind,
+            issued_on,
+            expires_on,
+            user.id,
+            notes
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(conn.last_insert_rowid())
+}
+
+#[tauri::command]
+fn revoke_certification(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    payload: RevokeCertificationPayload,
+) -> Result<(), String> {
+    let user = session.require(Role::Manager)?;
+    let reason = payload.reason.trim();
+    if reason.is_empty() {
+        return Err("Bitte einen Grund für den Entzug angeben".into());
+    }
+    let conn = db.connect().map_err(|e| e.to_string())?;
+    let updated = conn
+        .execute(
+            "UPDATE member_certifications
+            SET revoked_at = CURRENT_TIMESTAMP, revoked_by = ?1, revoke_reason = ?2
+            WHERE id = ?3 AND revoked_at IS NULL",
+            params![user.id, reason, payload.certification_id],
+        )
+        .map_err(|e| e.to_string())?;
+    if updated == 0 {
+        return Err("Sicherungsschein nicht gefunden oder bereits entzogen".into());
+    }
+    Ok(())
+}
+
+#[tauri::command]
+fn list_households(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+) -> Result<Vec<HouseholdRecord>, String> {
+    session.require(Role::User)?;
+    let conn = db.connect().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(
+            "
+        SELECT h.id,
+               h.name,
+               h.payer_member_id,
+               p.first_name || ' ' || p.last_name,
+               h.shared_balance,
+               h.shared_memberships,
+               CASE WHEN h.shared_balance = 1 THEN p.balance_cents END,
+               h.created_at
+        FROM households h
+        LEFT JOIN members p ON p.id = h.payer_member_id
+        ORDER BY h.name COLLATE NOCASE
+        ",
+        )
+        .map_err(|e| e.to_string())?;
+    let mut households = stmt
+        .query_map([], |row| {
+            Ok(HouseholdRecord {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                payer_member_id: row.get(2)?,
+                payer_name: row.get(3)?,
+                shared_balance: row.get(4)?,
+                shared_memberships: row.get(5)?,
+                wallet_balance_cents: row.get(6)?,
+                members: Vec::new(),
+                created_at: row.get(7)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    let mut members = conn
+        .prepare(
+            "SELECT m.id, m.first_name, m.last_name, m.balance_cents
+            FROM household_members hm
+            JOIN members m ON m.id = hm.member_id
+            WHERE hm.household_id = ?
+            ORDER BY m.last_name COLLATE NOCASE, m.first_name COLLATE NOCASE",
+        )
+        .map_err(|e| e.to_string())?;
+    for household in &mut households {
+        household.members = members
+            .query_map([household.id], |row| {
+                Ok(HouseholdMemberRecord {
+                    member_id: row.get(0)?,
+                    first_name: row.get(1)?,
+                    last_name: row.get(2)?,
+                    balance_cents: row.get(3)?,
+                })
+            })
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(households)
+}
+
+#[tauri::command]
+fn save_household(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    payload: HouseholdPayload,
+) -> Result<i64, String> {
+    session.require(Role::Manager)?;
+    let name = payload.name.trim();
+    if name.is_empty() {
+        return Err("Bitte einen Namen für den Haushalt angeben".into());
+    }
+    if payload.shared_balance && payload.payer_member_id.is_none() {
+        return Err("Für ein gemeinsames Guthaben muss ein Zahler festgelegt sein".into());
+    }
+    let mut conn = db.connect().map_err(|e| e.to_string())?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let household_id = match payload.id {
+        Some(id) => {
+            let updated = tx
+                .execute(
+                    "UPDATE households
+                    SET name = ?1, payer_member_id = ?2, shared_balance = ?3, shared_memberships = ?4,
+                        updated_at = CURRENT_TIMESTAMP
+                    WHERE id = ?5",
+                    params![
+                        name,
+                        payload.payer_member_id,
+                        payload.shared_balance,
+                        payload.shared_memberships,
+                        id
+                    ],
+                )
+                .map_err(|e| e.to_string())?;
+            if updated == 0 {
+                return Err("Haushalt nicht gefunden".into());
+            }
+            id
+        }
+        None => {
+            tx.execute(
+                "INSERT INTO households (name, payer_member_id, shared_balance, shared_memberships)
+                VALUES (?1, ?2, ?3, ?4)",
+                params![
+                    name,
+                    payload.payer_member_id,
+                    payload.shared_balance,
+                    payload.shared_memberships
+                ],
+            )
+            .map_err(|e| e.to_string())?;
+            tx.last_insert_rowid()
+        }
+    };
+
+    // The payer is linked automatically so a new household can be set up in one step.
+    if let Some(payer_id) = payload.payer_member_id {
+        match households::membership_of(&tx, payer_id).map_err(|e| e.to_string())? {
+            Some((id, _)) if id == household_id => {}
+            Some((_, other)) => {
+                return Err(format!("Zahler gehört bereits zum Haushalt {}", other));
+            }
+            None => {
+                tx.execute(
+                    "INSERT INTO household_members (household_id, member_id) VALUES (?1, ?2)",
+                    params![household_id, payer_id],
+                )
+                .map_err(|e| e.to_string())?;
+            }
+        }
+    }
+    if payload.shared_balance {
+        households::check_shared_wallet(&tx, household_id)?;
+    }
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(household_id)
+}
+
+#[tauri::command]
+fn delete_household(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    id: i64,
+) -> Result<(), String> {
+    session.require(Role::Manager)?;
+    let conn = db.connect().map_err(|e| e.to_string())?;
+    let deleted = conn
+        .execute("DELETE FROM households WHERE id = ?", [id])
+        .map_err(|e| e.to_string())?;
+    if deleted == 0 {
+        return Err("Haushalt nicht gefunden".into());
+    }
+    Ok(())
+}
+
+#[tauri::command]
+fn add_household_member(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    payload: HouseholdMemberPayload,
+) -> Result<(), String> {
+    session.require(Role::Manager)?;
+    let mut conn = db.connect().map_err(|e| e.to_string())?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let shared_balance: bool = tx
+        .query_row(
+            "SELECT shared_balance FROM households WHERE id = ?",
+            [payload.household_id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?
+        .ok_or("Haushalt nicht gefunden")?;
+    let exists = tx
+        .query_row(
+            "SELECT 1 FROM members WHERE id = ?",
+            [payload.member_id],
+            |row| row.get::<_, i64>(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    if exists.is_none() {
+        return Err("Mitglied nicht gefunden".into());
+    }
+    match households::membership_of(&tx, payload.member_id).map_err(|e| e.to_string())? {
+        Some((id, _)) if id == payload.household_id => return Ok(()),
+        Some((_, other)) => {
+            return Err(format!("Mitglied gehört bereits zum Haushalt {}", other));
+        }
+        None => {}
+    }
+    tx.execute(
+        "INSERT INTO household_members (household_id, member_id) VALUES (?1, ?2)",
+        params![payload.household_id, payload.member_id],
+    )
+    .map_err(|e| e.to_string())?;
+    if shared_balance {
+        households::check_shared_wallet(&tx, payload.household_id)?;
+    }
+    tx.commit().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn remove_household_member(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    payload: HouseholdMemberPayload,
+) -> Result<(), String> {
+    session.require(Role::Manager)?;
+    let conn = db.connect().map_err(|e| e.to_string())?;
+    let payer: Option<Option<i64>> = conn
+        .query_row(
+            "SELECT payer_member_id FROM households WHERE id = ?",
+            [payload.household_id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    if payer.ok_or("Haushalt nicht gefunden")? == Some(payload.member_id) {
+        return Err(
+            "Der Zahler kann nicht entfernt werden. Bitte zuerst einen anderen Zahler festlegen."
+                .into(),
+        );
+    }
+    let removed = conn
+        .execute(
+            "DELETE FROM household_members WHERE household_id = ?1 AND member_id = ?2",
+            params![payload.household_id, payload.member_id],
+        )
+        .map_err(|e| e.to_string())?;
+    if removed == 0 {
+        return Err("Mitglied gehört nicht zu diesem Haushalt".into());
+    }
+    Ok(())
+}
+
+/// Entry products sold today whose quantity has not been used up by guest check-ins.
+const OPEN_GUEST_ENTRIES: &str = "
+    SELECT tl.transaction_id,
+           tl.id,
+           tl.product_name,
+           tl.quantity,
+           (SELECT COUNT(*) FROM member_checkins c WHERE c.transaction_line_id = tl.id) AS used,
+           t.created_at
+    FROM transaction_lines tl
+    JOIN transactions t ON t.id = tl.transaction_id
+    JOIN products p ON p.id = tl.product_id
+    WHERE p.grants_entry = 1
+      AND tl.quantity > 0
+      AND t.cancels_transaction_id IS NULL
+      AND NOT EXISTS (SELECT 1 FROM transactions s WHERE s.cancels_transaction_id = t.id)
+      AND DATE(t.created_at, 'localtime') = DATE('now', 'localtime')
+";
+
+#[tauri::command]
+fn list_guest_entries(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+) -> Result<Vec<GuestEntryRecord>, String> {
+    session.require(Role::User)?;
+    let conn = db.connect().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(&format!(
+            "{} AND used < tl.quantity ORDER BY t.created_at DESC, tl.id",
+            OPEN_GUEST_ENTRIES
+        ))
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok(GuestEntryRecord {
+                transaction_id: row.get(0)?,
+                transaction_line_id: row.get(1)?,
+                product_name: row.get(2)?,
+                quantity: row.get(3)?,
+                used: row.get(4)?,
+                sold_at: row.get(5)?,
+            })
+        })
+        .map_err(|e| e.to_string())?;
+
+    rows.collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn record_guest_checkin(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    payload: GuestCheckinPayload,
+) -> Result<i64, String> {
+    session.require(Role::User)?;
+    let mut conn = db.connect().map_err(|e| e.to_string())?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let entry = tx
+        .query_row(
+            &format!("{} AND tl.id = ?", OPEN_GUEST_ENTRIES),
+            [payload.transaction_line_id],
+            |row| Ok((row.get::<_, i64>(3)?, row.get::<_, i64>(4)?)),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    let (quantity, used) = entry.ok_or_else(|| {
+        "Kein gültiger Eintritt: Position ist kein heute verkaufter, nicht stornierter Eintritt"
+            .to_string()
+    })?;
+    if used >= quantity {
+        return Err("Alle verkauften Eintritte dieser Position wurden bereits genutzt".into());
+    }
+
+    let guest_name = payload
+        .guest_name
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty());
+    let waiver_reference = payload
+        .waiver_reference
+        .map(|reference| reference.trim().to_string())
+        .filter(|reference| !reference.is_empty());
+    tx.execute(
+        "INSERT INTO member_checkins (transaction_line_id, guest_name, waiver_reference) VALUES (?1, ?2, ?3)",
+        params![payload.transaction_line_id, guest_name, waiver_reference],
+    )
+    .map_err(|e| e.to_string())?;
+    let id = tx.last_insert_rowid();
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(id)
+}
+
+#[tauri::command]
+fn checkin_statistics(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    payload: CheckinStatisticsRequest,
+) -> Result<Vec<CheckinStatisticsRecord>, String> {
+    session.require(Role::Manager)?;
+    let conn = db.connect().map_err(|e| e.to_string())?;
+    if !is_iso_date(&conn, &payload.from)? || !is_iso_date(&conn, &payload.to)? {
+        return Err("Datum muss im Format JJJJ-MM-TT angegeben werden".into());
+    }
+    let mut stmt = conn
+        .prepare(
+            "
+        SELECT DATE(created_at, 'localtime') AS day,
+               SUM(member_id IS NOT NULL AND reentry = 0),
+               SUM(member_id IS NULL),
+               SUM(reentry),
+               COUNT(DISTINCT member_id)
+        FROM member_checkins
+        WHERE DATE(created_at, 'localtime') BETWEEN ?1 AND ?2
+        GROUP BY day
+        ORDER BY day
         ",
         )
         .map_err(|e| e.to_string())?;
-
     let rows = stmt
-        .query_map([], |row| {
-            Ok(MembershipRecord {
-                id: row.get(0)?,
-                name: row.get(1)?,
-                description: row.get(2)?,
-                price_cents: row.get(3)?,
-                duration_days: row.get(4)?,
-                max_uses: row.get(5)?,
-                created_at: row.get(6)?,
-                updated_at: row.get(7)?,
+        .query_map(params![payload.from, payload.to], |row| {
+            Ok(CheckinStatisticsRecord {
+                date: row.get(0)?,
+                member_checkins: row.get(1)?,
+                guest_checkins: row.get(2)?,
+                reentries: row.get(3)?,
+                unique_members: row.get(4)?,
             })
         })
         .map_err(|e| e.to_string())?;
@@ -1008,297 +5757,393 @@ fn list_memberships(db: State<DatabasePath>) -> Result<Vec<MembershipRecord>, St
 }
 
 #[tauri::command]
-fn save_membership(db: State<DatabasePath>, payload: MembershipPayload) -> Result<i64, String> {
+fn list_transactions_today(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+) -> Result<Vec<TransactionRecord>, String> {
+    session.require(Role::User)?;
     let conn = db.connect().map_err(|e| e.to_string())?;
-    if let Some(id) = payload.id {
-        conn.execute(
-            "UPDATE memberships
-            SET membership_type = ?, notes = ?, price_cents = ?, duration_days = ?, max_uses = ?, updated_at = CURRENT_TIMESTAMP
-            WHERE id = ?",
-            params![
-                payload.name,
-                payload.description,
-                payload.price_cents,
-                payload.duration_days,
-                payload.max_uses,
-                id
-            ],
-        )
+    let mut stmt = conn
+        .prepare(&format!(
+            "{} WHERE DATE(t.created_at, 'localtime') = DATE('now', 'localtime') ORDER BY t.created_at DESC",
+            TRANSACTION_SELECT
+        ))
         .map_err(|e| e.to_string())?;
-        Ok(id)
-    } else {
-        conn.execute(
-            "INSERT INTO memberships (membership_type, notes, price_cents, duration_days, max_uses)
-            VALUES (?1, ?2, ?3, ?4, ?5)",
-            params![
-                payload.name,
-                payload.description,
-                payload.price_cents,
-                payload.duration_days,
-                payload.max_uses
-            ],
-        )
+
+    let rows = stmt
+        .query_map([], map_transaction_row)
         .map_err(|e| e.to_string())?;
-        Ok(conn.last_insert_rowid())
-    }
-}
 
-#[tauri::command]
-fn delete_membership(db: State<DatabasePath>, id: i64) -> Result<(), String> {
-    let conn = db.connect().map_err(|e| e.to_string())?;
-    conn.execute("DELETE FROM memberships WHERE id = ?", [id])
-        .map_err(|e| e.to_string())
-        .map(|_| ())
+    let mut records = rows
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    attach_transaction_lines(&conn, &mut records).map_err(|e| e.to_string())?;
+    attach_transaction_payments(&conn, &mut records).map_err(|e| e.to_string())?;
+    attach_transaction_signatures(&conn, &mut records).map_err(|e| e.to_string())?;
+    Ok(records)
 }
 
 #[tauri::command]
-fn list_member_memberships(db: State<DatabasePath>) -> Result<Vec<MemberMembershipRecord>, String> {
+fn list_payment_totals_today(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+) -> Result<Vec<PaymentTotalRecord>, String> {
+    session.require(Role::User)?;
     let conn = db.connect().map_err(|e| e.to_string())?;
     let mut stmt = conn
         .prepare(
             "
-        SELECT mm.id,
-               mm.member_id,
-               mm.membership_id,
-               ms.membership_type,
-               mm.remaining_uses,
-               mm.start_date,
-               mm.end_date,
-               mm.created_at
-        FROM member_memberships mm
-        JOIN memberships ms ON ms.id = mm.membership_id
-        ORDER BY mm.member_id, mm.created_at DESC
+        SELECT tp.method,
+               COUNT(*) AS payment_count,
+               COALESCE(SUM(tp.amount_cents), 0) AS total_cents
+        FROM transaction_payments tp
+        JOIN transactions t ON t.id = tp.transaction_id
+        WHERE DATE(t.created_at, 'localtime') = DATE('now', 'localtime')
+        GROUP BY tp.method
         ",
         )
         .map_err(|e| e.to_string())?;
 
     let rows = stmt
         .query_map([], |row| {
-            Ok(MemberMembershipRecord {
-                id: row.get(0)?,
-                member_id: row.get(1)?,
-                membership_id: row.get(2)?,
-                membership_name: row.get(3)?,
-                remaining_uses: row.get(4)?,
-                start_date: row.get(5)?,
-                end_date: row.get(6)?,
-                created_at: row.get(7)?,
-            })
+            Ok((
+                row.get::<_, PaymentMethod>(0)?,
+                row.get::<_, i64>(1)?,
+                row.get::<_, i64>(2)?,
+            ))
         })
         .map_err(|e| e.to_string())?;
+    let totals = rows
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
 
-    rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())
+    Ok(PaymentMethod::ALL
+        .into_iter()
+        .map(|method| {
+            let (payment_count, total_cents) = totals
+                .iter()
+                .find(|(m, _, _)| *m == method)
+                .map(|(_, count, total)| (*count, *total))
+                .unwrap_or((0, 0));
+            PaymentTotalRecord {
+                method,
+                label: method.label().to_string(),
+                payment_count,
+                total_cents,
+            }
+        })
+        .collect())
 }
 
 #[tauri::command]
-fn assign_member_membership(
+fn open_cash_session(
     db: State<DatabasePath>,
-    payload: AssignMemberMembershipPayload,
+    session: State<SessionState>,
+    payload: OpenCashSessionPayload,
 ) -> Result<i64, String> {
-    let conn = db.connect().map_err(|e| e.to_string())?;
-    let membership: (String, Option<i64>, Option<i64>) = conn
-        .query_row(
-            "SELECT membership_type, duration_days, max_uses FROM memberships WHERE id = ?",
-            [payload.membership_id],
-            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
-        )
-        .map_err(|_| "Mitgliedschaft nicht gefunden".to_string())?;
-
-    let duration_days = membership.1;
-    let max_uses = membership.2;
-    let end_interval = duration_days.map(|days| format!("+{} days", days));
-
-    conn.execute(
-        "INSERT INTO member_memberships (member_id, membership_id, remaining_uses, start_date, end_date)
-        VALUES (?1, ?2, ?3, DATE('now','localtime'), CASE WHEN ?4 IS NOT NULL THEN DATE('now','localtime', ?4) ELSE NULL END)",
-        params![payload.member_id, payload.membership_id, max_uses, end_interval],
+    let user = session.require(Role::User)?;
+    if payload.opening_float_cents < 0 {
+        return Err("Anfangsbestand darf nicht negativ sein.".into());
+    }
+    let mut conn = db.connect().map_err(|e| e.to_string())?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    if open_cash_session_id(&tx)
+        .map_err(|e| e.to_string())?
+        .is_some()
+    {
+        return Err("Es ist bereits eine Kassensitzung geöffnet.".into());
+    }
+    tx.execute(
+        "INSERT INTO cash_sessions (opened_by, opening_float_cents, notes) VALUES (?1, ?2, ?3)",
+        params![user.id, payload.opening_float_cents, payload.notes],
     )
     .map_err(|e| e.to_string())?;
-
-    Ok(conn.last_insert_rowid())
+    let id = tx.last_insert_rowid();
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(id)
 }
 
 #[tauri::command]
-fn delete_member_membership(db: State<DatabasePath>, id: i64) -> Result<(), String> {
+fn get_current_cash_session(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+) -> Result<Option<CashSessionRecord>, String> {
+    session.require(Role::User)?;
     let conn = db.connect().map_err(|e| e.to_string())?;
-    conn.execute("DELETE FROM member_memberships WHERE id = ?", [id])
-        .map_err(|e| e.to_string())?;
-    Ok(())
+    let id = open_cash_session_id(&conn).map_err(|e| e.to_string())?;
+    match id {
+        Some(id) => load_cash_session(&conn, id)
+            .map(Some)
+            .map_err(|e| e.to_string()),
+        None => Ok(None),
+    }
 }
 
 #[tauri::command]
-fn delete_transaction(db: State<DatabasePath>, id: i64) -> Result<(), String> {
+fn list_cash_sessions(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+) -> Result<Vec<CashSessionRecord>, String> {
+    session.require(Role::Manager)?;
     let conn = db.connect().map_err(|e| e.to_string())?;
-    conn.execute("DELETE FROM transactions WHERE id = ?", [id])
+    let mut stmt = conn
+        .prepare(&format!(
+            "{} ORDER BY cs.opened_at DESC LIMIT 100",
+            CASH_SESSION_SELECT
+        ))
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([], map_cash_session_row)
+        .map_err(|e| e.to_string())?;
+    rows.collect::<Result<Vec<_>, _>>()
         .map_err(|e| e.to_string())
-        .map(|_| ())
 }
 
 #[tauri::command]
-fn record_checkin(db: State<DatabasePath>, payload: CheckinPayload) -> Result<(), String> {
-    let conn = db.connect().map_err(|e| e.to_string())?;
-    let existing = conn
-        .query_row(
-            "SELECT 1 FROM member_checkins WHERE member_id = ? AND DATE(created_at, 'localtime') = DATE('now', 'localtime')",
-            [payload.member_id],
-            |row| row.get::<_, i64>(0),
-        )
-        .optional()
-        .map_err(|e| e.to_string())?;
-    if existing.is_some() {
-        return Err("Mitglied ist heute bereits eingecheckt".into());
+fn record_cash_movement(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    payload: CashMovementPayload,
+) -> Result<i64, String> {
+    let user = session.require(Role::User)?;
+    if payload.kind != "cash_in" && payload.kind != "cash_out" {
+        return Err(format!("Unbekannte Kassenbewegung: {}", payload.kind));
     }
-
-    let candidate = conn
-        .query_row(
-            "SELECT mm.id, mm.membership_id, mm.remaining_uses
-            FROM member_memberships mm
-            JOIN memberships ms ON ms.id = mm.membership_id
-            WHERE mm.member_id = ?
-              AND (mm.remaining_uses IS NULL OR mm.remaining_uses > 0)
-              AND (mm.end_date IS NULL OR DATE(mm.end_date) >= DATE('now','localtime'))
-            ORDER BY (mm.end_date IS NULL) ASC, mm.end_date ASC, mm.created_at ASC
-            LIMIT 1",
-            [payload.member_id],
-            |row| Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?, row.get::<_, Option<i64>>(2)?)),
-        )
-        .optional()
-        .map_err(|e| e.to_string())?;
-
-    let (member_membership_id, membership_id, remaining_uses) = candidate
-        .ok_or_else(|| "Keine aktive Mitgliedschaft gefunden".to_string())?;
-
+    if payload.amount_cents <= 0 {
+        return Err("Betrag muss größer als 0 sein.".into());
+    }
+    let conn = db.connect().map_err(|e| e.to_string())?;
+    let session_id = require_cash_session(&conn)?;
     conn.execute(
-        "INSERT INTO member_checkins (member_id, membership_id, member_membership_id) VALUES (?1, ?2, ?3)",
-        params![payload.member_id, membership_id, member_membership_id],
+        "INSERT INTO cash_movements (cash_session_id, kind, amount_cents, reason, user_id) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            session_id,
+            payload.kind,
+            payload.amount_cents,
+            payload.reason,
+            user.id
+        ],
     )
     .map_err(|e| e.to_string())?;
+    Ok(conn.last_insert_rowid())
+}
 
-    if let Some(uses) = remaining_uses {
-        let new_uses = uses.saturating_sub(1);
-        conn.execute(
-            "UPDATE member_memberships SET remaining_uses = ? WHERE id = ?",
-            params![new_uses, member_membership_id],
+#[tauri::command]
+fn list_cash_movements(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    cash_session_id: i64,
+) -> Result<Vec<CashMovementRecord>, String> {
+    session.require(Role::User)?;
+    let conn = db.connect().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, cash_session_id, kind, amount_cents, reason, user_id, created_at \
+            FROM cash_movements WHERE cash_session_id = ? ORDER BY id ASC",
         )
         .map_err(|e| e.to_string())?;
-    }
+    let rows = stmt
+        .query_map([cash_session_id], |row| {
+            Ok(CashMovementRecord {
+                id: row.get(0)?,
+                cash_session_id: row.get(1)?,
+                kind: row.get(2)?,
+                amount_cents: row.get(3)?,
+                reason: row.get(4)?,
+                user_id: row.get(5)?,
+                created_at: row.get(6)?,
+            })
+        })
+        .map_err(|e| e.to_string())?;
+    rows.collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())
+}
 
-    Ok(())
+#[tauri::command]
+fn cash_session_x_report(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+) -> Result<CashReport, String> {
+    session.require(Role::User)?;
+    let conn = db.connect().map_err(|e| e.to_string())?;
+    let session_id = require_cash_session(&conn)?;
+    build_cash_report(&conn, session_id, "X", None).map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-fn delete_checkin(db: State<DatabasePath>, id: i64) -> Result<(), String> {
+fn close_cash_session(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    payload: CloseCashSessionPayload,
+) -> Result<CashReport, String> {
+    let user = session.require(Role::User)?;
+    if payload.counted.iter().any(|c| c.amount_cents < 0) {
+        return Err("Gezählte Beträge dürfen nicht negativ sein.".into());
+    }
+    if !payload
+        .counted
+        .iter()
+        .any(|c| c.method == PaymentMethod::Cash)
+    {
+        return Err("Gezählten Bargeldbestand angeben.".into());
+    }
+
     let mut conn = db.connect().map_err(|e| e.to_string())?;
     let tx = conn.transaction().map_err(|e| e.to_string())?;
-    let details = tx
+    let session_id = require_cash_session(&tx)?;
+
+    let z_number: i64 = tx
         .query_row(
-            "SELECT member_membership_id, membership_id FROM member_checkins WHERE id = ?",
-            [id],
-            |row| Ok((row.get::<_, Option<i64>>(0)?, row.get::<_, Option<i64>>(1)?)),
+            "SELECT COALESCE(MAX(z_number), 0) + 1 FROM cash_sessions",
+            [],
+            |row| row.get(0),
         )
-        .optional()
+        .map_err(|e| e.to_string())?;
+    let closed_at: String = tx
+        .query_row("SELECT CURRENT_TIMESTAMP", [], |row| row.get(0))
         .map_err(|e| e.to_string())?;
 
-    let (member_membership_id, _membership_id) =
-        details.ok_or_else(|| "Check-in nicht gefunden".to_string())?;
+    let mut report = build_cash_report(&tx, session_id, "Z", Some(&payload.counted))
+        .map_err(|e| e.to_string())?;
+    report.z_number = Some(z_number);
+    report.closed_by = Some(user.id);
+    report.closed_at = Some(closed_at.clone());
 
-    if let Some(mm_id) = member_membership_id {
-        if let Some((remaining_uses, max_uses)) = tx
-            .query_row(
-                "SELECT remaining_uses, ms.max_uses
-                FROM member_memberships mm
-                JOIN memberships ms ON ms.id = mm.membership_id
-                WHERE mm.id = ?",
-                [mm_id],
-                |row| Ok((row.get::<_, Option<i64>>(0)?, row.get::<_, Option<i64>>(1)?)),
-            )
-            .optional()
-            .map_err(|e| e.to_string())?
-        {
-            if let Some(current) = remaining_uses {
-                let cap = max_uses.unwrap_or(i64::MAX);
-                let new_value = (current + 1).min(cap);
-                tx.execute(
-                    "UPDATE member_memberships SET remaining_uses = ? WHERE id = ?",
-                    params![new_value, mm_id],
-                )
-                .map_err(|e| e.to_string())?;
-            }
-        }
-    }
+    let cash = report
+        .payments
+        .iter()
+        .find(|p| p.method == PaymentMethod::Cash)
+        .ok_or_else(|| "Bargeldabgleich fehlt.".to_string())?;
+    let snapshot = serde_json::to_string(&report).map_err(|e| e.to_string())?;
 
-    tx.execute("DELETE FROM member_checkins WHERE id = ?", [id])
-        .map_err(|e| e.to_string())?;
-    tx.commit().map_err(|e| e.to_string())
+    tx.execute(
+        "UPDATE cash_sessions
+        SET status = 'closed',
+            closed_by = ?1,
+            closed_at = ?2,
+            counted_cash_cents = ?3,
+            expected_cash_cents = ?4,
+            difference_cents = ?5,
+            z_number = ?6,
+            z_report = ?7,
+            notes = COALESCE(?8, notes)
+        WHERE id = ?9",
+        params![
+            user.id,
+            closed_at,
+            cash.counted_cents,
+            cash.expected_cents,
+            cash.difference_cents,
+            z_number,
+            snapshot,
+            payload.notes,
+            session_id
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(report)
 }
 
 #[tauri::command]
-fn list_checkins_today(db: State<DatabasePath>) -> Result<Vec<CheckinRecord>, String> {
+fn get_z_report(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    cash_session_id: i64,
+) -> Result<CashReport, String> {
+    session.require(Role::Manager)?;
     let conn = db.connect().map_err(|e| e.to_string())?;
-    let mut stmt = conn
-        .prepare(
-            "
-        SELECT c.id,
-               c.member_id,
-               m.first_name || ' ' || m.last_name AS member_name,
-               ms.membership_type,
-               c.created_at
-        FROM member_checkins c
-        JOIN members m ON m.id = c.member_id
-        LEFT JOIN memberships ms ON ms.id = c.membership_id
-        WHERE DATE(c.created_at, 'localtime') = DATE('now', 'localtime')
-        ORDER BY c.created_at DESC
-        ",
+    let snapshot: Option<Option<String>> = conn
+        .query_row(
+            "SELECT z_report FROM cash_sessions WHERE id = ?",
+            [cash_session_id],
+            |row| row.get(0),
         )
+        .optional()
         .map_err(|e| e.to_string())?;
-
-    let rows = stmt
-        .query_map([], |row| {
-            Ok(CheckinRecord {
-                id: row.get(0)?,
-                member_id: row.get(1)?,
-                member_name: row.get(2)?,
-                membership_name: row.get(3).ok(),
-                created_at: row.get(4)?,
-            })
-        })
-        .map_err(|e| e.to_string())?;
-
-    rows.collect::<Result<Vec<_>, _>>()
-        .map_err(|e| e.to_string())
+    let snapshot = snapshot
+        .ok_or_else(|| "Kassensitzung nicht gefunden".to_string())?
+        .ok_or_else(|| "Kassensitzung ist noch nicht abgeschlossen.".to_string())?;
+    serde_json::from_str(&snapshot).map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-fn list_transactions_today(db: State<DatabasePath>) -> Result<Vec<TransactionRecord>, String> {
+fn export_dsfinvk(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    settings: State<SettingsState>,
+    payload: ExportDsfinvkPayload,
+) -> Result<DsfinvkExportRecord, String> {
+    session.require(Role::Manager)?;
+    let target_dir = payload.target_dir.trim();
+    if target_dir.is_empty() {
+        return Err("Zielverzeichnis fehlt.".into());
+    }
+    let settings = settings.get()?;
     let conn = db.connect().map_err(|e| e.to_string())?;
-    let mut stmt = conn
-        .prepare(
-            "SELECT id, product_id, quantity, total_cents, description, created_at \
-            FROM transactions \
-            WHERE DATE(created_at, 'localtime') = DATE('now', 'localtime') \
-            ORDER BY created_at DESC",
-        )
-        .map_err(|e| e.to_string())?;
 
-    let rows = stmt
-        .query_map([], |row| {
-            Ok(TransactionRecord {
-                id: row.get(0)?,
-                product_id: row.get(1)?,
-                quantity: row.get(2)?,
-                total_cents: row.get(3)?,
-                description: row.get(4)?,
-                created_at: row.get(5)?,
-            })
-        })
-        .map_err(|e| e.to_string())?;
+    let session_ids: Vec<i64> = match payload.z_numbers {
+        Some(z_numbers) if !z_numbers.is_empty() => {
+            let mut stmt = conn
+                .prepare("SELECT id FROM cash_sessions WHERE z_number = ? AND status = 'closed'")
+                .map_err(|e| e.to_string())?;
+            let mut ids = Vec::new();
+            for z_number in z_numbers {
+                let id: Option<i64> = stmt
+                    .query_row([z_number], |row| row.get(0))
+                    .optional()
+                    .map_err(|e| e.to_string())?;
+                ids.push(id.ok_or_else(|| format!("Z-Bon {} nicht gefunden", z_number))?);
+            }
+            ids
+        }
+        _ => {
+            let (from, to) = match (payload.from_date, payload.to_date) {
+                (Some(from), Some(to)) => {
+                    (normalize_date(&conn, &from)?, normalize_date(&conn, &to)?)
+                }
+                _ => return Err("Zeitraum oder Z-Nummern angeben.".into()),
+            };
+            if from > to {
+                return Err("Das Startdatum liegt nach dem Enddatum.".into());
+            }
+            let mut stmt = conn
+                .prepare(
+                    "SELECT id FROM cash_sessions
+                    WHERE status = 'closed'
+                        AND DATE(closed_at, 'localtime') BETWEEN ? AND ?
+                    ORDER BY z_number ASC",
+                )
+                .map_err(|e| e.to_string())?;
+            let rows = stmt
+                .query_map(params![from, to], |row| row.get(0))
+                .map_err(|e| e.to_string())?;
+            rows.collect::<Result<Vec<_>, _>>()
+                .map_err(|e| e.to_string())?
+        }
+    };
 
-    rows.collect::<Result<Vec<_>, _>>()
-        .map_err(|e| e.to_string())
+    let summary = dsfinvk::export(
+        &conn,
+        &settings.business,
+        &settings.currency,
+        &session_ids,
+        &PathBuf::from(target_dir),
+        payload.zip.unwrap_or(false),
+    )?;
+    Ok(DsfinvkExportRecord {
+        path: summary.path.to_string_lossy().to_string(),
+        closing_count: summary.closing_count,
+        transaction_count: summary.transaction_count,
+    })
 }
 
 #[tauri::command]
-fn list_roles(db: State<DatabasePath>) -> Result<Vec<RoleRecord>, String> {
+fn list_roles(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+) -> Result<Vec<RoleRecord>, String> {
+    session.require(Role::Admin)?;
     let conn = db.connect().map_err(|e| e.to_string())?;
     let mut stmt = conn
         .prepare("SELECT id, name FROM user_roles ORDER BY id ASC")
@@ -1316,7 +6161,11 @@ fn list_roles(db: State<DatabasePath>) -> Result<Vec<RoleRecord>, String> {
 }
 
 #[tauri::command]
-fn list_users(db: State<DatabasePath>) -> Result<Vec<UserRecord>, String> {
+fn list_users(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+) -> Result<Vec<UserRecord>, String> {
+    session.require(Role::Admin)?;
     let conn = db.connect().map_err(|e| e.to_string())?;
     let mut stmt = conn
         .prepare(
@@ -1346,7 +6195,12 @@ fn list_users(db: State<DatabasePath>) -> Result<Vec<UserRecord>, String> {
 }
 
 #[tauri::command]
-fn save_user(db: State<DatabasePath>, payload: UserPayload) -> Result<i64, String> {
+fn save_user(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    payload: UserPayload,
+) -> Result<i64, String> {
+    session.require(Role::Admin)?;
     let conn = db.connect().map_err(|e| e.to_string())?;
 
     if let Some(id) = payload.id {
@@ -1400,15 +6254,55 @@ fn save_user(db: State<DatabasePath>, payload: UserPayload) -> Result<i64, Strin
 }
 
 #[tauri::command]
-fn delete_user(db: State<DatabasePath>, id: i64) -> Result<(), String> {
+fn delete_user(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    id: i64,
+) -> Result<(), String> {
+    let user = session.require(Role::Admin)?;
+    if user.id == id {
+        return Err("Eigenes Benutzerkonto kann nicht gelöscht werden".into());
+    }
     let conn = db.connect().map_err(|e| e.to_string())?;
     conn.execute("DELETE FROM users WHERE id = ?", [id])
         .map_err(|e| e.to_string())?;
     Ok(())
 }
 
+fn verify_manager_approval(
+    conn: &Connection,
+    username: &str,
+    password: &str,
+) -> Result<i64, String> {
+    let approver: Option<(i64, String, String, bool)> = conn
+        .query_row(
+            "SELECT u.id, u.password_hash, r.name, u.active
+            FROM users u JOIN user_roles r ON r.id = u.role_id
+            WHERE u.username = ?",
+            [username.trim()],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    let (id, hash, role, active) =
+        approver.ok_or_else(|| "Freigabe: Benutzer nicht gefunden".to_string())?;
+    if !active || !verify_password(&hash, password)? {
+        return Err("Freigabe: Anmeldedaten ungültig".into());
+    }
+    if Role::from_name(&role).is_none_or(|role| role < Role::Manager) {
+        return Err("Freigabe erfordert einen Manager".into());
+    }
+    Ok(id)
+}
+
 #[tauri::command]
-fn login_user(db: State<DatabasePath>, payload: LoginPayload) -> Result<LoginResponse, String> {
+fn login_user(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    payload: LoginPayload,
+) -> Result<LoginResponse, String> {
+    // A failed login must not leave the previous user signed in.
+    session.clear()?;
     let conn = db.connect().map_err(|e| e.to_string())?;
     let mut stmt = conn
         .prepare(
@@ -1442,229 +6336,1148 @@ fn login_user(db: State<DatabasePath>, payload: LoginPayload) -> Result<LoginRes
         return Err("Passwort ungültig".into());
     }
 
-    Ok(LoginResponse {
+    if Role::from_name(&role).is_none() {
+        return Err(format!("Unbekannte Rolle: {}", role));
+    }
+
+    let user = LoginResponse {
         id,
         username,
         display_name,
         role,
-    })
+    };
+    session.set(user.clone())?;
+    Ok(user)
+}
+
+#[tauri::command]
+fn logout_user(session: State<SessionState>) -> Result<(), String> {
+    session.clear()
+}
+
+#[tauri::command]
+fn current_user(session: State<SessionState>) -> Result<Option<LoginResponse>, String> {
+    session.get()
 }
 
 #[tauri::command]
-fn get_settings(state: State<SettingsState>) -> Result<AppSettings, String> {
+fn get_settings(
+    state: State<SettingsState>,
+    session: State<SessionState>,
+) -> Result<AppSettings, String> {
+    session.require(Role::User)?;
     state.get()
 }
 
-#[tauri::command]
-fn update_settings(
-    state: State<SettingsState>,
-    payload: AppSettings,
-) -> Result<AppSettings, String> {
-    state.save(payload.clone())?;
-    Ok(payload)
+#[tauri::command]
+fn update_settings(
+    state: State<SettingsState>,
+    session: State<SessionState>,
+    payload: AppSettings,
+) -> Result<AppSettings, String> {
+    session.require(Role::Admin)?;
+    let mut payload = payload;
+    let current = state.get()?;
+    payload.backup_failure = current.backup_failure;
+    if payload.db_location.trim() != current.db_location {
+        payload.db_location = validate_existing_database(&payload.db_location)?
+            .to_string_lossy()
+            .to_string();
+    }
+    if payload.credit_limit_cents < 0 {
+        return Err("Kreditlimit darf nicht negativ sein".into());
+    }
+    if !payload.sepa.creditor_id.trim().is_empty() {
+        sepa::normalize_creditor_id(&payload.sepa.creditor_id)?;
+    }
+    if !payload.sepa.iban.trim().is_empty() {
+        sepa::normalize_iban(&payload.sepa.iban)?;
+    }
+    if !payload.sepa.bic.trim().is_empty() {
+        sepa::normalize_bic(&payload.sepa.bic)?;
+    }
+    if ![checkin::SELECT_EARLIEST_END, checkin::SELECT_ASK]
+        .contains(&payload.checkin.membership_selection.as_str())
+    {
+        return Err("Unbekannte Auswahlregel für Mitgliedschaften beim Check-in".into());
+    }
+    if ![
+        waivers::ENFORCE_OFF,
+        waivers::ENFORCE_WARN,
+        waivers::ENFORCE_REFUSE,
+    ]
+    .contains(&payload.checkin.waiver_enforcement.as_str())
+    {
+        return Err("Unbekannte Einstellung für Haftungsausschlüsse".into());
+    }
+    if payload.checkin.reentry_window_hours > 24 {
+        return Err("Wiedereintritt ist höchstens 24 Stunden lang möglich".into());
+    }
+    if payload.backup.interval_hours == 0 {
+        return Err("Sicherungsintervall muss mindestens eine Stunde betragen".into());
+    }
+    if payload.backup.keep_daily == 0 {
+        return Err("Mindestens eine tägliche Sicherung muss aufbewahrt werden".into());
+    }
+    let backup_directory = payload.backup.directory.trim().to_string();
+    if !backup_directory.is_empty() && !PathBuf::from(&backup_directory).is_absolute() {
+        return Err("Sicherungsordner muss ein absoluter Pfad sein".into());
+    }
+    payload.backup.directory = backup_directory;
+    state.save(payload.clone())?;
+    Ok(payload)
+}
+
+#[tauri::command]
+fn list_backups(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    settings: State<SettingsState>,
+) -> Result<Vec<backup::BackupRecord>, String> {
+    session.require(Role::Manager)?;
+    let db_path = db.path();
+    let dir = backup::backup_dir(&settings.get()?.backup, &db_path);
+    backup::list(&db_path, &dir)
+}
+
+#[tauri::command]
+fn create_backup(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    settings: State<SettingsState>,
+) -> Result<backup::BackupRecord, String> {
+    session.require(Role::Manager)?;
+    let db_path = db.path();
+    let dir = backup::backup_dir(&settings.get()?.backup, &db_path);
+    backup::create(&db_path, &dir, backup::KIND_MANUAL)
+}
+
+#[tauri::command]
+fn restore_backup(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    settings: State<SettingsState>,
+    payload: RestoreBackupPayload,
+) -> Result<backup::BackupRecord, String> {
+    session.require(Role::Admin)?;
+    let db_path = db.lock()?;
+    let dir = backup::backup_dir(&settings.get()?.backup, &db_path);
+    backup::restore(&db_path, &dir, &payload.file_name)
+}
+
+#[tauri::command]
+fn export_backup_archive(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    settings: State<SettingsState>,
+    payload: ExportBackupArchivePayload,
+) -> Result<archive::ArchiveRecord, String> {
+    session.require(Role::Admin)?;
+    let settings_json =
+        serde_json::to_string_pretty(&settings.get()?).map_err(|e| e.to_string())?;
+    archive::export(
+        &db.path(),
+        &settings_json,
+        &PathBuf::from(payload.target_dir.trim()),
+        &payload.password,
+    )
+}
+
+#[tauri::command]
+fn restore_backup_archive(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    settings: State<SettingsState>,
+    payload: RestoreBackupArchivePayload,
+) -> Result<backup::BackupRecord, String> {
+    session.require(Role::Admin)?;
+    let opened = archive::open(
+        &PathBuf::from(payload.archive_path.trim()),
+        &payload.password,
+    )?;
+    let current = settings.get()?;
+    let archived_settings = if payload.restore_settings.unwrap_or(false) {
+        let mut archived: AppSettings = serde_json::from_str(&opened.settings)
+            .map_err(|e| format!("Einstellungen im Archiv sind ungültig: {}", e))?;
+        archived.db_location = current.db_location.clone();
+        Some(archived)
+    } else {
+        None
+    };
+
+    let db_path = db.lock()?;
+    let safety = archive::restore_database(&db_path, &current.backup, &opened)?;
+    if let Some(archived) = archived_settings {
+        settings.save(archived)?;
+    }
+    Ok(safety)
+}
+
+fn run_automatic_backup(app: &tauri::AppHandle, kind: &str, only_if_due: bool) {
+    let Ok(settings) = app.state::<SettingsState>().get() else {
+        return;
+    };
+    if !settings.enable_backups {
+        return;
+    }
+    let db_path = app.state::<DatabasePath>().path();
+    let dir = backup::backup_dir(&settings.backup, &db_path);
+    let result = backup::is_due(&db_path, &dir, settings.backup.interval_hours)
+        .map(|due| due || !only_if_due)
+        .and_then(|run| {
+            if run {
+                backup::create(&db_path, &dir, kind)?;
+                backup::prune(&db_path, &dir, &settings.backup)?;
+            }
+            Ok(())
+        });
+    // The outcome is kept in the settings so the UI can warn about failing backups.
+    let backup_failure = result.err().map(|err| {
+        let at = backup::local_time(&db_path, "%d.%m.%Y %H:%M").unwrap_or_default();
+        format!("Automatische Sicherung am {} fehlgeschlagen: {}", at, err)
+    });
+    let state = app.state::<SettingsState>();
+    if let Ok(current) = state.get() {
+        if current.backup_failure != backup_failure {
+            let _ = state.save(AppSettings {
+                backup_failure,
+                ..current
+            });
+        }
+    }
+}
+
+#[tauri::command]
+fn move_database(
+    db: State<DatabasePath>,
+    session: State<SessionState>,
+    settings: State<SettingsState>,
+    payload: MoveDatabasePayload,
+) -> Result<DatabaseMoveRecord, String> {
+    session.require(Role::Admin)?;
+    let target = validate_db_location(&payload.target_path)?;
+    if target.exists() {
+        return Err(format!(
+            "Am Zielort {} existiert bereits eine Datei",
+            target.display()
+        ));
+    }
+
+    let mut current = db.lock()?;
+    if *current == target {
+        return Err("Die Datenbank liegt bereits an diesem Ort".into());
+    }
+    // Holding the path lock keeps new connections from opening, but commands
+    // that connected earlier could still commit to the old file while it is
+    // copied. The write lock makes those writes fail instead of getting lost.
+    let writer_lock = Connection::open(&*current).map_err(|e| e.to_string())?;
+    writer_lock
+        .busy_timeout(std::time::Duration::from_secs(5))
+        .map_err(|e| e.to_string())?;
+    writer_lock
+        .execute_batch("BEGIN IMMEDIATE")
+        .map_err(|_| "Datenbank wird gerade beschrieben, bitte erneut versuchen".to_string())?;
+    let source = Connection::open(&*current).map_err(|e| e.to_string())?;
+    source
+        .execute("VACUUM INTO ?", [target.to_string_lossy().as_ref()])
+        .map_err(|e| format!("Datenbank konnte nicht kopiert werden: {}", e))?;
+    drop(source);
+
+    let verified = Connection::open(&target)
+        .map_err(|e| e.to_string())
+        .and_then(|conn| integrity_check(&conn));
+    if let Err(err) = verified {
+        let _ = fs::remove_file(&target);
+        return Err(format!("Kopie der Datenbank ist fehlerhaft: {}", err));
+    }
+
+    let mut updated = settings.get()?;
+    updated.db_location = target.to_string_lossy().to_string();
+    if let Err(err) = settings.save(updated) {
+        let _ = fs::remove_file(&target);
+        return Err(err);
+    }
+
+    let previous_path = std::mem::replace(&mut *current, target.clone());
+    drop(writer_lock);
+    Ok(DatabaseMoveRecord {
+        previous_path: previous_path.to_string_lossy().to_string(),
+        path: target.to_string_lossy().to_string(),
+    })
+}
+
+/// Switching `db_location` in the settings only takes effect on the next start,
+/// where a missing file would silently be replaced by a fresh database. Only an
+/// existing database of this app is accepted; new locations go through
+/// `move_database`.
+fn validate_existing_database(location: &str) -> Result<PathBuf, String> {
+    let path = validate_db_location(location)?;
+    if !path.is_file() {
+        return Err(format!(
+            "{} existiert nicht. Zum Verschieben bitte „Datenbank verschieben“ verwenden.",
+            path.display()
+        ));
+    }
+    // Opened without SQLITE_OPEN_CREATE; read-only would fail the FTS5 integrity check.
+    let conn = Connection::open_with_flags(&path, rusqlite::OpenFlags::SQLITE_OPEN_READ_WRITE)
+        .map_err(|e| e.to_string())?;
+    integrity_check(&conn)?;
+    let version: i64 = conn
+        .pragma_query_value(None, "user_version", |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    let has_members: bool = conn
+        .query_row(
+            "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'members')",
+            [],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    if version == 0 && !has_members {
+        return Err(format!("{} ist keine Boulderado-Datenbank", path.display()));
+    }
+    Ok(path)
+}
+
+fn validate_db_location(location: &str) -> Result<PathBuf, String> {
+    let location = location.trim();
+    if location.is_empty() {
+        return Err("Datenbankpfad darf nicht leer sein".into());
+    }
+    let path = PathBuf::from(location);
+    if !path.is_absolute() {
+        return Err("Datenbankpfad muss ein absoluter Pfad sein".into());
+    }
+    if path.is_dir() {
+        return Err(format!("{} ist ein Ordner, keine Datenbankdatei", location));
+    }
+    let parent = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .ok_or_else(|| "Datenbankpfad hat keinen Ordner".to_string())?;
+    if !parent.is_dir() {
+        return Err(format!("Ordner {} existiert nicht", parent.display()));
+    }
+    let probe = parent.join(".boulderado-schreibtest");
+    fs::write(&probe, b"")
+        .map_err(|e| format!("Ordner {} ist nicht beschreibbar: {}", parent.display(), e))?;
+    let _ = fs::remove_file(&probe);
+
+    if path.exists() {
+        let mut header = [0u8; 16];
+        let mut file = fs::File::open(&path).map_err(|e| e.to_string())?;
+        let read = std::io::Read::read(&mut file, &mut header).map_err(|e| e.to_string())?;
+        if header[..read] != b"SQLite format 3\0"[..read] {
+            return Err(format!("{} ist keine SQLite-Datenbank", location));
+        }
+    }
+    Ok(path)
+}
+
+fn integrity_check(conn: &Connection) -> Result<(), String> {
+    let mut stmt = conn
+        .prepare("PRAGMA integrity_check")
+        .map_err(|e| e.to_string())?;
+    let problems = stmt
+        .query_map([], |row| row.get::<_, String>(0))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    if problems.len() == 1 && problems[0] == "ok" {
+        Ok(())
+    } else {
+        Err(problems.join("; "))
+    }
+}
+
+fn load_settings_from_disk(path: &PathBuf, default: AppSettings) -> AppSettings {
+    match fs::read_to_string(path) {
+        Ok(content) => serde_json::from_str(&content).unwrap_or(default),
+        Err(_) => default,
+    }
+}
+
+const TRANSACTION_SELECT: &str = "
+    SELECT t.id,
+           t.product_id,
+           t.quantity,
+           t.total_cents,
+           t.description,
+           t.member_id,
+           t.user_id,
+           t.cash_session_id,
+           t.cancels_transaction_id,
+           (SELECT s.id FROM transactions s WHERE s.cancels_transaction_id = t.id),
+           t.created_at
+    FROM transactions t";
+
+const JOURNAL_GENESIS_HASH: &str =
+    "0000000000000000000000000000000000000000000000000000000000000000";
+
+fn journal_hash(prev_hash: &str, payload: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(prev_hash.as_bytes());
+    hasher.update(b"\n");
+    hasher.update(payload.as_bytes());
+    hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+fn journal_payload(
+    conn: &Connection,
+    transaction_id: i64,
+    entry_type: &str,
+) -> rusqlite::Result<String> {
+    let mut payload = conn.query_row(
+        "SELECT product_id, quantity, total_cents, description, member_id, user_id, cash_session_id, cancels_transaction_id, created_at
+        FROM transactions WHERE id = ?",
+        [transaction_id],
+        |row| {
+            Ok(JournalPayload {
+                entry_type: entry_type.to_string(),
+                transaction_id,
+                product_id: row.get(0)?,
+                quantity: row.get(1)?,
+                total_cents: row.get(2)?,
+                description: row.get(3)?,
+                member_id: row.get(4)?,
+                user_id: row.get(5)?,
+                cash_session_id: row.get(6)?,
+                cancels_transaction_id: row.get(7)?,
+                created_at: row.get(8)?,
+                lines: Vec::new(),
+                payments: Vec::new(),
+            })
+        },
+    )?;
+
+    let mut stmt = conn.prepare(
+        "SELECT product_name, unit_price_cents, quantity, line_total_cents,
+                vat_key, vat_rate_basis_points, net_cents, tax_cents
+        FROM transaction_lines WHERE transaction_id = ? ORDER BY id ASC",
+    )?;
+    payload.lines = stmt
+        .query_map([transaction_id], |row| {
+            Ok(JournalLine {
+                product_name: row.get(0)?,
+                unit_price_cents: row.get(1)?,
+                quantity: row.get(2)?,
+                line_total_cents: row.get(3)?,
+                vat_key: row.get(4)?,
+                vat_rate_basis_points: row.get(5)?,
+                net_cents: row.get(6)?,
+                tax_cents: row.get(7)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut stmt = conn.prepare(
+        "SELECT method, amount_cents FROM transaction_payments WHERE transaction_id = ? ORDER BY id ASC",
+    )?;
+    payload.payments = stmt
+        .query_map([transaction_id], |row| {
+            Ok(JournalPayment {
+                method: row.get(0)?,
+                amount_cents: row.get(1)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    serde_json::to_string(&payload)
+        .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))
+}
+
+fn append_journal_entry(
+    conn: &Connection,
+    transaction_id: i64,
+    entry_type: &str,
+) -> rusqlite::Result<()> {
+    let prev_hash: String = conn
+        .query_row(
+            "SELECT hash FROM journal_entries ORDER BY id DESC LIMIT 1",
+            [],
+            |row| row.get(0),
+        )
+        .optional()?
+        .unwrap_or_else(|| JOURNAL_GENESIS_HASH.to_string());
+    let payload = journal_payload(conn, transaction_id, entry_type)?;
+    let hash = journal_hash(&prev_hash, &payload);
+    conn.execute(
+        "INSERT INTO journal_entries (transaction_id, entry_type, payload, prev_hash, hash) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![transaction_id, entry_type, payload, prev_hash, hash],
+    )?;
+    Ok(())
+}
+
+fn format_amount(cents: i64) -> String {
+    let sign = if cents < 0 { "-" } else { "" };
+    format!("{}{}.{:02}", sign, cents.abs() / 100, cents.abs() % 100)
+}
+
+fn tse_process_data(conn: &Connection, transaction_id: i64) -> Result<String, String> {
+    let total_cents: i64 = conn
+        .query_row(
+            "SELECT total_cents FROM transactions WHERE id = ?",
+            [transaction_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    let (cash_cents, non_cash_cents): (i64, i64) = conn
+        .query_row(
+            "SELECT COALESCE(SUM(CASE WHEN method = 'cash' THEN amount_cents END), 0),
+                    COALESCE(SUM(CASE WHEN method <> 'cash' THEN amount_cents END), 0)
+            FROM transaction_payments WHERE transaction_id = ?",
+            [transaction_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .map_err(|e| e.to_string())?;
+
+    let mut slots = [0i64; 5];
+    let mut stmt = conn
+        .prepare(
+            "SELECT vat_key, COALESCE(SUM(line_total_cents), 0)
+            FROM transaction_lines WHERE transaction_id = ? GROUP BY vat_key",
+        )
+        .map_err(|e| e.to_string())?;
+    let by_key = stmt
+        .query_map([transaction_id], |row| {
+            Ok((row.get::<_, Option<i64>>(0)?, row.get::<_, i64>(1)?))
+        })
+        .and_then(|rows| rows.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| e.to_string())?;
+    for (vat_key, gross_cents) in by_key {
+        let slot = vat::tse_slot(vat_key)
+            .map_err(|e| format!("Beleg {} kann nicht signiert werden: {}", transaction_id, e))?;
+        slots[slot] += gross_cents;
+    }
+    if slots.iter().sum::<i64>() != total_cents {
+        return Err(format!(
+            "Beleg {} kann nicht signiert werden: Positionen ergeben nicht den Belegbetrag.",
+            transaction_id
+        ));
+    }
+
+    let mut payments = Vec::new();
+    if cash_cents != 0 {
+        payments.push(format!("{}:Bar", format_amount(cash_cents)));
+    }
+    if non_cash_cents != 0 {
+        payments.push(format!("{}:Unbar", format_amount(non_cash_cents)));
+    }
+    Ok(format!(
+        "Beleg^{}^{}",
+        slots
+            .iter()
+            .map(|cents| format_amount(*cents))
+            .collect::<Vec<_>>()
+            .join("_"),
+        payments.join("_")
+    ))
+}
+
+/// Signs the booking with the TSE as the very last step and commits it.
+///
+/// The TSE counts a signature as soon as it is issued. If storing it or the
+/// commit fails afterwards, the booking is rolled back but the signature stays
+/// in the TSE log, so it is kept as a pending signature for reconciliation.
+fn sign_and_commit(
+    tx: rusqlite::Transaction,
+    db: &DatabasePath,
+    tse: &dyn Tse,
+    client_id: &str,
+    transaction_id: i64,
+) -> Result<(), String> {
+    let process_data = tse_process_data(&tx, transaction_id)?;
+    let signed = tse.info().and_then(|info| {
+        let start = tse.start_transaction(client_id, PROCESS_TYPE_RECEIPT, "")?;
+        let signature = tse.finish_transaction(
+            client_id,
+            start.transaction_number,
+            PROCESS_TYPE_RECEIPT,
+            &process_data,
+        )?;
+        Ok((info, signature))
+    });
+
+    let committed = match store_signature(&tx, transaction_id, &process_data, &signed) {
+        Ok(()) => tx.commit(),
+        Err(error) => {
+            drop(tx);
+            Err(error)
+        }
+    };
+    if let (Err(error), Ok((info, signature))) = (&committed, &signed) {
+        let conn = db.connect().map_err(|e| e.to_string())?;
+        conn.execute(
+            "INSERT INTO pending_tse_signatures (
+                transaction_id, tse_serial, tse_transaction_number, signature_counter,
+                start_time, finish_time, process_type, process_data, signature, error
+            )
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![
+                transaction_id,
+                info.serial_number,
+                signature.transaction_number as i64,
+                signature.signature_counter as i64,
+                signature.start_time,
+                signature.finish_time,
+                signature.process_type,
+                signature.process_data,
+                signature.signature,
+                error.to_string()
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    committed.map_err(|e| e.to_string())
+}
+
+fn store_signature(
+    conn: &Connection,
+    transaction_id: i64,
+    process_data: &str,
+    signed: &Result<(TseInfo, tse::TseSignature), String>,
+) -> rusqlite::Result<()> {
+    match signed {
+        Ok((info, signature)) => {
+            conn.execute(
+                "INSERT OR IGNORE INTO tse_devices (serial_number, provider, signature_algorithm, time_format, public_key)
+                VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    info.serial_number,
+                    info.provider,
+                    info.signature_algorithm,
+                    info.time_format,
+                    info.public_key
+                ],
+            )?;
+            conn.execute(
+                "INSERT INTO transaction_signatures (
+                    transaction_id, tse_serial, tse_transaction_number, signature_counter,
+                    start_time, finish_time, process_type, process_data, signature
+                )
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                params![
+                    transaction_id,
+                    info.serial_number,
+                    signature.transaction_number as i64,
+                    signature.signature_counter as i64,
+                    signature.start_time,
+                    signature.finish_time,
+                    signature.process_type,
+                    signature.process_data,
+                    signature.signature
+                ],
+            )?;
+        }
+        Err(error) => {
+            conn.execute(
+                "INSERT INTO transaction_signatures (transaction_id, process_type, process_data, error)
+                VALUES (?1, ?2, ?3, ?4)",
+                params![
+                    transaction_id,
+                    PROCESS_TYPE_RECEIPT,
+                    process_data,
+                    format!("TSE ausgefallen: {}", error)
+                ],
+            )?;
+        }
+    }
+    Ok(())
+}
+
+fn backfill_journal(conn: &mut Connection) -> rusqlite::Result<()> {
+    let tx = conn.transaction()?;
+    let missing = {
+        let mut stmt = tx.prepare(
+            "SELECT t.id, t.cancels_transaction_id IS NOT NULL
+            FROM transactions t
+            LEFT JOIN journal_entries j ON j.transaction_id = t.id
+            WHERE j.id IS NULL
+            ORDER BY t.id ASC",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, bool>(1)?))
+        })?;
+        rows.collect::<Result<Vec<_>, _>>()?
+    };
+    for (transaction_id, is_storno) in missing {
+        let entry_type = if is_storno { "storno" } else { "legacy" };
+        append_journal_entry(&tx, transaction_id, entry_type)?;
+    }
+    tx.commit()
+}
+
+fn verify_journal_chain(conn: &Connection) -> rusqlite::Result<JournalVerification> {
+    let mut issues = Vec::new();
+    let mut expected_prev = JOURNAL_GENESIS_HASH.to_string();
+    let mut entry_count = 0;
+    let mut last_hash = None;
+
+    let mut stmt = conn.prepare(
+        "SELECT j.id, j.transaction_id, j.entry_type, j.payload, j.prev_hash, j.hash, t.id IS NOT NULL
+        FROM journal_entries j
+        LEFT JOIN transactions t ON t.id = j.transaction_id
+        ORDER BY j.id ASC",
+    )?;
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        let entry_id: i64 = row.get(0)?;
+        let transaction_id: i64 = row.get(1)?;
+        let entry_type: String = row.get(2)?;
+        let payload: String = row.get(3)?;
+        let prev_hash: String = row.get(4)?;
+        let hash: String = row.get(5)?;
+        let transaction_exists: bool = row.get(6)?;
+        entry_count += 1;
+
+        let mut issue = |problem: &str| {
+            issues.push(JournalIssue {
+                entry_id: Some(entry_id),
+                transaction_id: Some(transaction_id),
+                problem: problem.to_string(),
+            })
+        };
+        if prev_hash != expected_prev {
+            issue("Verkettung unterbrochen: vorheriger Hash stimmt nicht überein");
+        }
+        if journal_hash(&prev_hash, &payload) != hash {
+            issue("Hash des Eintrags stimmt nicht mit dem Inhalt überein");
+        }
+        if !transaction_exists {
+            issue("Transaktion fehlt in der Datenbank");
+        } else if journal_payload(conn, transaction_id, &entry_type)? != payload {
+            issue("Transaktion wurde nach der Buchung verändert");
+        }
+
+        expected_prev = hash.clone();
+        last_hash = Some(hash);
+    }
+
+    let mut stmt = conn.prepare(
+        "SELECT t.id FROM transactions t
+        LEFT JOIN journal_entries j ON j.transaction_id = t.id
+        WHERE j.id IS NULL
+        ORDER BY t.id ASC",
+    )?;
+    let unjournaled = stmt
+        .query_map([], |row| row.get::<_, i64>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+    for transaction_id in unjournaled {
+        issues.push(JournalIssue {
+            entry_id: None,
+            transaction_id: Some(transaction_id),
+            problem: "Transaktion ohne Journaleintrag".into(),
+        });
+    }
+
+    Ok(JournalVerification {
+        entry_count,
+        last_hash,
+        valid: issues.is_empty(),
+        issues,
+    })
+}
+
+const CASH_SESSION_SELECT: &str = "
+    SELECT cs.id,
+           cs.status,
+           cs.opened_by,
+           u.display_name,
+           cs.opened_at,
+           cs.opening_float_cents,
+           cs.closed_by,
+           cs.closed_at,
+           cs.counted_cash_cents,
+           cs.expected_cash_cents,
+           cs.difference_cents,
+           cs.z_number,
+           cs.notes
+    FROM cash_sessions cs
+    LEFT JOIN users u ON u.id = cs.opened_by";
+
+fn map_cash_session_row(row: &rusqlite::Row) -> rusqlite::Result<CashSessionRecord> {
+    Ok(CashSessionRecord {
+        id: row.get(0)?,
+        status: row.get(1)?,
+        opened_by: row.get(2)?,
+        opened_by_name: row.get(3)?,
+        opened_at: row.get(4)?,
+        opening_float_cents: row.get(5)?,
+        closed_by: row.get(6)?,
+        closed_at: row.get(7)?,
+        counted_cash_cents: row.get(8)?,
+        expected_cash_cents: row.get(9)?,
+        difference_cents: row.get(10)?,
+        z_number: row.get(11)?,
+        notes: row.get(12)?,
+    })
+}
+
+fn record_balance_entry(
+    conn: &Connection,
+    member_id: i64,
+    kind: &str,
+    amount_cents: i64,
+    transaction_id: Option<i64>,
+    reason: Option<&str>,
+    user_id: Option<i64>,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO balance_entries (member_id, kind, amount_cents, transaction_id, reason, user_id)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![member_id, kind, amount_cents, transaction_id, reason, user_id],
+    )?;
+    Ok(())
+}
+
+/// Every booking that moves money belongs to an open cash session, otherwise it
+/// would never show up in an X or Z report.
+fn require_cash_session(conn: &Connection) -> Result<i64, String> {
+    open_cash_session_id(conn)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "Keine Kassensitzung geöffnet.".to_string())
+}
+
+fn open_cash_session_id(conn: &Connection) -> rusqlite::Result<Option<i64>> {
+    conn.query_row(
+        "SELECT id FROM cash_sessions WHERE status = 'open' LIMIT 1",
+        [],
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+fn load_cash_session(conn: &Connection, id: i64) -> rusqlite::Result<CashSessionRecord> {
+    conn.query_row(
+        &format!("{} WHERE cs.id = ?", CASH_SESSION_SELECT),
+        [id],
+        map_cash_session_row,
+    )
 }
 
-fn initialize_db(conn: &Connection) -> rusqlite::Result<()> {
-    conn.execute_batch(
+fn build_cash_report(
+    conn: &Connection,
+    session_id: i64,
+    kind: &str,
+    counted: Option<&[PaymentPayload]>,
+) -> rusqlite::Result<CashReport> {
+    let (opened_by, opened_at, opening_float_cents): (i64, String, i64) = conn.query_row(
+        "SELECT opened_by, opened_at, opening_float_cents FROM cash_sessions WHERE id = ?",
+        [session_id],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+    )?;
+    let (transaction_count, sales_total_cents): (i64, i64) = conn.query_row(
+        "SELECT COUNT(*), COALESCE(SUM(total_cents), 0) FROM transactions WHERE cash_session_id = ?",
+        [session_id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+    let movement_total = |kind: &str| -> rusqlite::Result<i64> {
+        conn.query_row(
+            "SELECT COALESCE(SUM(amount_cents), 0) FROM cash_movements WHERE cash_session_id = ? AND kind = ?",
+            params![session_id, kind],
+            |row| row.get(0),
+        )
+    };
+    let cash_in_cents = movement_total("cash_in")?;
+    let cash_out_cents = movement_total("cash_out")?;
+    let vat = load_vat_summary(conn, "t.cash_session_id = ?", &[&session_id])?;
+    let created_at: String = conn.query_row("SELECT CURRENT_TIMESTAMP", [], |row| row.get(0))?;
+
+    let mut stmt = conn.prepare(
         "
-        CREATE TABLE IF NOT EXISTS user_roles (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            name TEXT NOT NULL UNIQUE
-        );
-        CREATE TABLE IF NOT EXISTS users (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            username TEXT NOT NULL UNIQUE,
-            display_name TEXT NOT NULL,
-            password_hash TEXT NOT NULL,
-            role_id INTEGER NOT NULL,
-            active INTEGER NOT NULL DEFAULT 1,
-            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
-            updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
-            FOREIGN KEY(role_id) REFERENCES user_roles(id)
-        );
-        CREATE TABLE IF NOT EXISTS product_types (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            name TEXT NOT NULL,
-            color TEXT,
-            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
-            updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
-        );
-        CREATE TABLE IF NOT EXISTS products (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            name TEXT NOT NULL,
-            price_cents INTEGER NOT NULL,
-            accent TEXT,
-            icon TEXT,
-            note TEXT,
-            product_type_id INTEGER,
-            FOREIGN KEY(product_type_id) REFERENCES product_types(id)
-        );
-        CREATE TABLE IF NOT EXISTS transactions (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            product_id INTEGER,
-            quantity INTEGER NOT NULL DEFAULT 1,
-            total_cents INTEGER NOT NULL,
-            description TEXT,
-            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
-            FOREIGN KEY(product_id) REFERENCES products(id)
-        );
-        CREATE TABLE IF NOT EXISTS members (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            first_name TEXT NOT NULL,
-            last_name TEXT NOT NULL,
-            email TEXT,
-            phone TEXT,
-            status TEXT NOT NULL DEFAULT 'active',
-            notes TEXT,
-            balance_cents INTEGER NOT NULL DEFAULT 0,
-            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
-            updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
-        );
-        CREATE TABLE IF NOT EXISTS memberships (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            membership_type TEXT NOT NULL,
-            price_cents INTEGER,
-            notes TEXT,
-            duration_days INTEGER,
-            max_uses INTEGER,
-            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
-            updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
-        );
-        CREATE TABLE IF NOT EXISTS member_memberships (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            member_id INTEGER NOT NULL,
-            membership_id INTEGER NOT NULL,
-            remaining_uses INTEGER,
-            start_date TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
-            end_date TEXT,
-            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
-            FOREIGN KEY(member_id) REFERENCES members(id) ON DELETE CASCADE,
-            FOREIGN KEY(membership_id) REFERENCES memberships(id)
-        );
-        CREATE TABLE IF NOT EXISTS buckets (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            name TEXT NOT NULL,
-            status TEXT NOT NULL DEFAULT 'open',
-            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
-            updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
-        );
-        CREATE TABLE IF NOT EXISTS bucket_items (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            bucket_id INTEGER NOT NULL,
-            product_id INTEGER NOT NULL,
-            product_name TEXT NOT NULL,
-            quantity INTEGER NOT NULL DEFAULT 1,
-            price_cents INTEGER NOT NULL,
-            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
-            FOREIGN KEY(bucket_id) REFERENCES buckets(id) ON DELETE CASCADE,
-            FOREIGN KEY(product_id) REFERENCES products(id),
-            UNIQUE(bucket_id, product_id)
-        );
-        CREATE TABLE IF NOT EXISTS member_checkins (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            member_id INTEGER NOT NULL,
-            membership_id INTEGER,
-            member_membership_id INTEGER,
-            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
-            FOREIGN KEY(member_id) REFERENCES members(id) ON DELETE CASCADE,
-            FOREIGN KEY(membership_id) REFERENCES memberships(id),
-            FOREIGN KEY(member_membership_id) REFERENCES member_memberships(id) ON DELETE SET NULL
-        );
-        CREATE INDEX IF NOT EXISTS idx_bucket_items_bucket ON bucket_items(bucket_id);
+        SELECT tp.method, COUNT(*), COALESCE(SUM(tp.amount_cents), 0)
+        FROM transaction_payments tp
+        JOIN transactions t ON t.id = tp.transaction_id
+        WHERE t.cash_session_id = ?
+        GROUP BY tp.method
         ",
-    )
+    )?;
+    let sales = stmt
+        .query_map([session_id], |row| {
+            Ok((
+                row.get::<_, PaymentMethod>(0)?,
+                row.get::<_, i64>(1)?,
+                row.get::<_, i64>(2)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let payments = PaymentMethod::ALL
+        .into_iter()
+        .map(|method| {
+            let (payment_count, sales_cents) = sales
+                .iter()
+                .find(|(m, _, _)| *m == method)
+                .map(|(_, count, total)| (*count, *total))
+                .unwrap_or((0, 0));
+            let expected_cents = if method == PaymentMethod::Cash {
+                opening_float_cents + sales_cents + cash_in_cents - cash_out_cents
+            } else {
+                sales_cents
+            };
+            let counted_cents = counted.and_then(|counted| {
+                let mut amounts = counted.iter().filter(|c| c.method == method).peekable();
+                amounts.peek()?;
+                Some(amounts.map(|c| c.amount_cents).sum::<i64>())
+            });
+            PaymentReconciliation {
+                method,
+                label: method.label().to_string(),
+                payment_count,
+                sales_cents,
+                expected_cents,
+                counted_cents,
+                difference_cents: counted_cents.map(|counted| counted - expected_cents),
+            }
+        })
+        .collect();
+
+    Ok(CashReport {
+        kind: kind.to_string(),
+        cash_session_id: session_id,
+        z_number: None,
+        opened_by,
+        opened_at,
+        closed_by: None,
+        closed_at: None,
+        created_at,
+        opening_float_cents,
+        transaction_count,
+        sales_total_cents,
+        cash_in_cents,
+        cash_out_cents,
+        payments,
+        vat,
+    })
 }
 
-fn ensure_product_type_column(conn: &Connection) -> rusqlite::Result<()> {
-    let mut stmt = conn.prepare("PRAGMA table_info(products)")?;
-    let mut has_column = false;
-    let mut rows = stmt.query([])?;
-    while let Some(row) = rows.next()? {
-        let name: String = row.get(1)?;
-        if name == "product_type_id" {
-            has_column = true;
-            break;
-        }
-    }
+fn load_vat_summary(
+    conn: &Connection,
+    filter: &str,
+    params: &[&dyn rusqlite::ToSql],
+) -> rusqlite::Result<Vec<VatSummaryRecord>> {
+    let mut stmt = conn.prepare(&format!(
+        "
+        SELECT tl.vat_key,
+               tl.tax_rate_name,
+               tl.vat_rate_basis_points,
+               COUNT(*),
+               COALESCE(SUM(tl.line_total_cents), 0),
+               COALESCE(SUM(COALESCE(tl.net_cents, tl.line_total_cents)), 0),
+               COALESCE(SUM(COALESCE(tl.tax_cents, 0)), 0)
+        FROM transaction_lines tl
+        JOIN transactions t ON t.id = tl.transaction_id
+        WHERE {}
+        GROUP BY tl.vat_key, tl.tax_rate_name, tl.vat_rate_basis_points
+        ORDER BY tl.vat_key IS NULL, tl.vat_key, tl.vat_rate_basis_points DESC
+        ",
+        filter
+    ))?;
+    let rows = stmt.query_map(params, |row| {
+        Ok(VatSummaryRecord {
+            vat_key: row.get(0)?,
+            tax_rate_name: row.get(1)?,
+            rate_basis_points: row.get(2)?,
+            line_count: row.get(3)?,
+            gross_cents: row.get(4)?,
+            net_cents: row.get(5)?,
+            tax_cents: row.get(6)?,
+        })
+    })?;
+    rows.collect()
+}
 
-    if !has_column {
-        let _ = conn.execute(
-            "ALTER TABLE products ADD COLUMN product_type_id INTEGER",
-            [],
-        );
+fn normalize_date(conn: &Connection, value: &str) -> Result<String, String> {
+    let value = value.trim();
+    let normalized: Option<String> = conn
+        .query_row("SELECT DATE(?)", [value], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    match normalized {
+        Some(date) if date == value => Ok(date),
+        _ => Err(format!("Ungültiges Datum: {}", value)),
     }
-    Ok(())
 }
 
-fn load_settings_from_disk(path: &PathBuf, default: AppSettings) -> AppSettings {
-    match fs::read_to_string(path) {
-        Ok(content) => serde_json::from_str(&content).unwrap_or(default),
-        Err(_) => default,
-    }
+fn map_transaction_row(row: &rusqlite::Row) -> rusqlite::Result<TransactionRecord> {
+    Ok(TransactionRecord {
+        id: row.get(0)?,
+        product_id: row.get(1)?,
+        quantity: row.get(2)?,
+        total_cents: row.get(3)?,
+        description: row.get(4)?,
+        member_id: row.get(5)?,
+        user_id: row.get(6)?,
+        cash_session_id: row.get(7)?,
+        cancels_transaction_id: row.get(8)?,
+        cancelled_by_transaction_id: row.get(9)?,
+        created_at: row.get(10)?,
+        lines: Vec::new(),
+        payments: Vec::new(),
+        signature: None,
+    })
 }
 
-fn ensure_member_columns(conn: &Connection) -> rusqlite::Result<()> {
-    let mut stmt = conn.prepare("PRAGMA table_info(members)")?;
-    let mut has_balance = false;
-    let mut rows = stmt.query([])?;
-    while let Some(row) = rows.next()? {
-        let name: String = row.get(1)?;
-        if name == "balance_cents" {
-            has_balance = true;
-        }
-    }
+fn resolve_checkout_payments(
+    payload: &CheckoutBucketPayload,
+    total_cents: i64,
+) -> Result<Vec<(PaymentMethod, i64)>, String> {
+    let Some(payments) = payload.payments.as_ref() else {
+        let method = if payload.use_balance {
+            PaymentMethod::Balance
+        } else {
+            match payload.payment_method.as_deref() {
+                Some(value) => PaymentMethod::parse(value)
+                    .ok_or_else(|| format!("Unbekannte Zahlungsart: {}", value))?,
+                None => PaymentMethod::Cash,
+            }
+        };
+        return Ok(vec![(method, total_cents)]);
+    };
 
-    if !has_balance {
-        conn.execute(
-            "ALTER TABLE members ADD COLUMN balance_cents INTEGER NOT NULL DEFAULT 0",
-            [],
-        )?;
+    if payments.is_empty() {
+        return Err("Mindestens eine Zahlung angeben.".into());
+    }
+    if payments.iter().any(|payment| payment.amount_cents <= 0) {
+        return Err("Zahlungsbeträge müssen größer als 0 sein.".into());
+    }
+    let paid_cents: i64 = payments.iter().map(|payment| payment.amount_cents).sum();
+    if paid_cents != total_cents {
+        return Err(format!(
+            "Summe der Zahlungen ({} ct) entspricht nicht dem Bucket-Betrag ({} ct).",
+            paid_cents, total_cents
+        ));
     }
-    Ok(())
-}
 
-fn ensure_membership_columns(conn: &Connection) -> rusqlite::Result<()> {
-    let mut stmt = conn.prepare("PRAGMA table_info(memberships)")?;
-    let mut has_duration = false;
-    let mut has_max = false;
-    let mut rows = stmt.query([])?;
-    while let Some(row) = rows.next()? {
-        let name: String = row.get(1)?;
-        if name == "duration_days" {
-            has_duration = true;
-        } else if name == "max_uses" {
-            has_max = true;
+    // Merge repeated methods so every sale stores at most one row per payment type.
+    let mut merged: Vec<(PaymentMethod, i64)> = Vec::new();
+    for payment in payments {
+        match merged
+            .iter_mut()
+            .find(|(method, _)| *method == payment.method)
+        {
+            Some((_, amount)) => *amount += payment.amount_cents,
+            None => merged.push((payment.method, payment.amount_cents)),
         }
     }
+    Ok(merged)
+}
 
-    if !has_duration {
-        conn.execute(
-            "ALTER TABLE memberships ADD COLUMN duration_days INTEGER",
-            [],
-        )?;
-    }
-    if !has_max {
-        conn.execute("ALTER TABLE memberships ADD COLUMN max_uses INTEGER", [])?;
+fn attach_transaction_lines(
+    conn: &Connection,
+    records: &mut [TransactionRecord],
+) -> rusqlite::Result<()> {
+    let mut stmt = conn.prepare(
+        "
+        SELECT id,
+               transaction_id,
+               product_id,
+               product_name,
+               product_type_id,
+               product_type_name,
+               unit_price_cents,
+               quantity,
+               line_total_cents,
+               tax_rate_id,
+               tax_rate_name,
+               vat_key,
+               vat_rate_basis_points,
+               net_cents,
+               tax_cents
+        FROM transaction_lines
+        WHERE transaction_id = ?
+        ORDER BY id ASC
+        ",
+    )?;
+    for record in records.iter_mut() {
+        let rows = stmt.query_map([record.id], |row| {
+            Ok(TransactionLineRecord {
+                id: row.get(0)?,
+                transaction_id: row.get(1)?,
+                product_id: row.get(2)?,
+                product_name: row.get(3)?,
+                product_type_id: row.get(4)?,
+                product_type_name: row.get(5)?,
+                unit_price_cents: row.get(6)?,
+                quantity: row.get(7)?,
+                line_total_cents: row.get(8)?,
+                tax_rate_id: row.get(9)?,
+                tax_rate_name: row.get(10)?,
+                vat_key: row.get(11)?,
+                vat_rate_basis_points: row.get(12)?,
+                net_cents: row.get(13)?,
+                tax_cents: row.get(14)?,
+            })
+        })?;
+        record.lines = rows.collect::<Result<Vec<_>, _>>()?;
     }
     Ok(())
 }
 
-fn ensure_checkin_columns(conn: &Connection) -> rusqlite::Result<()> {
-    let mut stmt = conn.prepare("PRAGMA table_info(member_checkins)")?;
-    let mut has_member_membership_id = false;
-    let mut rows = stmt.query([])?;
-    while let Some(row) = rows.next()? {
-        let name: String = row.get(1)?;
-        if name == "member_membership_id" {
-            has_member_membership_id = true;
-        }
+fn attach_transaction_payments(
+    conn: &Connection,
+    records: &mut [TransactionRecord],
+) -> rusqlite::Result<()> {
+    let mut stmt = conn.prepare(
+        "
+        SELECT id, transaction_id, method, amount_cents, member_id
+        FROM transaction_payments
+        WHERE transaction_id = ?
+        ORDER BY id ASC
+        ",
+    )?;
+    for record in records.iter_mut() {
+        let rows = stmt.query_map([record.id], |row| {
+            Ok(TransactionPaymentRecord {
+                id: row.get(0)?,
+                transaction_id: row.get(1)?,
+                method: row.get(2)?,
+                amount_cents: row.get(3)?,
+                member_id: row.get(4)?,
+            })
+        })?;
+        record.payments = rows.collect::<Result<Vec<_>, _>>()?;
     }
+    Ok(())
+}
 
-    if !has_member_membership_id {
-        conn.execute(
-            "ALTER TABLE member_checkins ADD COLUMN member_membership_id INTEGER",
-            [],
-        )?;
+fn attach_transaction_signatures(
+    conn: &Connection,
+    records: &mut [TransactionRecord],
+) -> rusqlite::Result<()> {
+    let mut stmt = conn.prepare(
+        "
+        SELECT tse_serial,
+               tse_transaction_number,
+               signature_counter,
+               start_time,
+               finish_time,
+               process_type,
+               process_data,
+               signature,
+               error
+        FROM transaction_signatures
+        WHERE transaction_id = ?
+        ",
+    )?;
+    for record in records.iter_mut() {
+        record.signature = stmt
+            .query_row([record.id], |row| {
+                Ok(TransactionSignatureRecord {
+                    tse_serial: row.get(0)?,
+                    tse_transaction_number: row.get(1)?,
+                    signature_counter: row.get(2)?,
+                    start_time: row.get(3)?,
+                    finish_time: row.get(4)?,
+                    process_type: row.get(5)?,
+                    process_data: row.get(6)?,
+                    signature: row.get(7)?,
+                    error: row.get(8)?,
+                })
+            })
+            .optional()?;
     }
     Ok(())
 }
@@ -1732,6 +7545,28 @@ fn seed_default_product_types(conn: &Connection) -> rusqlite::Result<()> {
     )
 }
 
+fn seed_default_tax_rates(conn: &mut Connection) -> rusqlite::Result<()> {
+    let count: i64 = conn.query_row("SELECT COUNT(*) FROM tax_rates", [], |row| row.get(0))?;
+    if count > 0 {
+        return Ok(());
+    }
+    let tx = conn.transaction()?;
+    for tax_rate in DEFAULT_TAX_RATES {
+        tx.execute(
+            "INSERT INTO tax_rates (name, vat_key, is_default) VALUES (?1, ?2, ?3)",
+            params![tax_rate.name, tax_rate.vat_key, tax_rate.is_default],
+        )?;
+        let tax_rate_id = tx.last_insert_rowid();
+        for (rate_basis_points, valid_from, valid_to) in tax_rate.periods {
+            tx.execute(
+                "INSERT INTO tax_rate_periods (tax_rate_id, rate_basis_points, valid_from, valid_to) VALUES (?1, ?2, ?3, ?4)",
+                params![tax_rate_id, rate_basis_points, valid_from, valid_to],
+            )?;
+        }
+    }
+    tx.commit()
+}
+
 fn ensure_default_bucket(conn: &mut Connection) -> rusqlite::Result<()> {
     let existing = conn
         .query_row("SELECT id FROM buckets LIMIT 1", [], |row| {
@@ -1816,20 +7651,6 @@ pub fn run() {
         .setup(|app| {
             let app_dir = app.path().app_data_dir()?;
             fs::create_dir_all(&app_dir)?;
-            let db_path = app_dir.join("boulderado-pos.db");
-
-            let mut conn = Connection::open(&db_path)?;
-            initialize_db(&conn)?;
-            ensure_product_type_column(&conn)?;
-            ensure_member_columns(&conn)?;
-            ensure_membership_columns(&conn)?;
-            ensure_checkin_columns(&conn)?;
-            ensure_roles(&mut conn)?;
-            ensure_admin_user(&conn)?;
-            seed_default_product_types(&conn)?;
-            seed_default_products(&mut conn)?;
-            ensure_default_bucket(&mut conn)?;
-            drop(conn);
 
             let exe_dir = app
                 .path()
@@ -1843,11 +7664,43 @@ pub fn run() {
             let conf_dir = exe_dir.join("conf");
             fs::create_dir_all(&conf_dir)?;
             let settings_path = conf_dir.join("settings.json");
-            let default_settings = AppSettings::with_defaults(&db_path);
-            let initial_settings = load_settings_from_disk(&settings_path, default_settings);
+            let default_db_path = app_dir.join("boulderado-pos.db");
+            let default_settings = AppSettings::with_defaults(&default_db_path);
+            let mut initial_settings = load_settings_from_disk(&settings_path, default_settings);
+            if initial_settings.db_location.trim().is_empty() {
+                initial_settings.db_location = default_db_path.to_string_lossy().to_string();
+            }
+            let db_path = validate_db_location(&initial_settings.db_location).map_err(|e| {
+                std::io::Error::other(format!(
+                    "Datenbankpfad aus {} ist ungültig: {}",
+                    settings_path.display(),
+                    e
+                ))
+            })?;
+
+            let mut conn = Connection::open(&db_path)?;
+            let backup_dir = backup::backup_dir(&initial_settings.backup, &db_path);
+            migrations::run(&mut conn, &db_path, &backup_dir).map_err(std::io::Error::other)?;
+            backfill_journal(&mut conn)?;
+            ensure_roles(&mut conn)?;
+            ensure_admin_user(&conn)?;
+            seed_default_product_types(&conn)?;
+            seed_default_products(&mut conn)?;
+            seed_default_tax_rates(&mut conn)?;
+            ensure_default_bucket(&mut conn)?;
+            drop(conn);
 
-            app.manage(DatabasePath(db_path));
+            app.manage(DatabasePath::new(db_path));
             app.manage(SettingsState::new(settings_path, initial_settings));
+            app.manage(SessionState::default());
+            let tse = SimulatorTse::open(&app_dir.join("tse")).map_err(std::io::Error::other)?;
+            app.manage(TseState::new(Box::new(tse)));
+
+            let handle = app.handle().clone();
+            std::thread::spawn(move || loop {
+                run_automatic_backup(&handle, backup::KIND_SCHEDULED, true);
+                std::thread::sleep(std::time::Duration::from_secs(60));
+            });
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
@@ -1855,43 +7708,119 @@ pub fn run() {
             list_products,
             save_product,
             delete_product,
-            record_transaction,
             list_transactions,
-            delete_transaction,
+            cancel_transaction,
+            verify_journal,
+            get_tse_info,
+            get_receipt,
+            get_receipt_text,
+            save_receipt_pdf,
+            print_receipt,
+            export_tse,
+            list_pending_tse_signatures,
+            resolve_pending_tse_signature,
             record_checkin,
+            checkin_by_card,
+            list_member_cards,
+            add_member_card,
+            set_member_card_status,
+            member_card_qr_svg,
             delete_checkin,
             list_checkins_today,
+            list_guest_entries,
+            list_waiver_texts,
+            publish_waiver_text,
+            record_waiver,
+            list_member_waivers,
+            get_waiver_signature,
+            list_waiver_gaps,
+            list_member_certifications,
+            grant_certification,
+            revoke_certification,
+            list_households,
+            save_household,
+            delete_household,
+            add_household_member,
+            remove_household_member,
+            record_guest_checkin,
+            checkin_statistics,
             list_transactions_today,
+            list_payment_totals_today,
+            open_cash_session,
+            get_current_cash_session,
+            list_cash_sessions,
+            record_cash_movement,
+            list_cash_movements,
+            cash_session_x_report,
+            close_cash_session,
+            get_z_report,
+            export_dsfinvk,
             list_buckets,
             create_bucket,
             rename_bucket,
             get_bucket_items,
             add_product_to_bucket,
+            add_membership_to_bucket,
             close_bucket,
             delete_bucket,
             checkout_bucket,
             list_product_types,
             save_product_type,
             delete_product_type,
+            list_tax_rates,
+            save_tax_rate,
+            delete_tax_rate,
+            get_vat_summary,
             list_members,
+            search_members,
             save_member,
             delete_member,
+            list_balance_entries,
+            top_up_balance,
+            adjust_balance,
+            set_member_credit_limit,
             list_memberships,
             save_membership,
             delete_membership,
             list_member_memberships,
             assign_member_membership,
             delete_member_membership,
+            renew_member_membership,
+            freeze_member_membership,
+            unfreeze_member_membership,
+            extend_member_membership,
+            list_member_membership_events,
+            list_contracts,
+            save_contract,
+            cancel_contract,
+            run_contract_billing,
+            list_contract_invoices,
+            export_sepa_direct_debit,
+            import_sepa_returns,
+            clear_debit_return_flag,
             list_users,
             save_user,
             delete_user,
             list_roles,
             login_user,
+            logout_user,
+            current_user,
             get_settings,
-            update_settings
+            update_settings,
+            move_database,
+            list_backups,
+            create_backup,
+            restore_backup,
+            export_backup_archive,
+            restore_backup_archive
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while running tauri application")
+        .run(|app, event| {
+            if let tauri::RunEvent::Exit = event {
+                run_automatic_backup(app, backup::KIND_EXIT, false);
+            }
+        });
 }
 #[derive(Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -1901,6 +7830,119 @@ struct AppSettings {
     currency: String,
     auto_updates: bool,
     enable_backups: bool,
+    #[serde(default)]
+    business: BusinessSettings,
+    #[serde(default)]
+    printer: PrinterSettings,
+    #[serde(default)]
+    backup: BackupSettings,
+    #[serde(default)]
+    credit_limit_cents: i64,
+    #[serde(default)]
+    sepa: SepaSettings,
+    #[serde(default)]
+    checkin: CheckinSettings,
+    #[serde(default)]
+    backup_failure: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase", default)]
+struct CheckinSettings {
+    reentry_window_hours: u32,
+    once_per_day: bool,
+    membership_selection: String,
+    waiver_enforcement: String,
+    require_belay_for_rope: bool,
+}
+
+impl Default for CheckinSettings {
+    fn default() -> Self {
+        Self {
+            reentry_window_hours: 0,
+            once_per_day: true,
+            membership_selection: checkin::SELECT_EARLIEST_END.into(),
+            waiver_enforcement: waivers::ENFORCE_WARN.into(),
+            require_belay_for_rope: false,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+struct SepaSettings {
+    creditor_name: String,
+    creditor_id: String,
+    iban: String,
+    bic: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase", default)]
+struct BackupSettings {
+    directory: String,
+    interval_hours: u32,
+    keep_daily: u32,
+    keep_weekly: u32,
+}
+
+impl Default for BackupSettings {
+    fn default() -> Self {
+        Self {
+            directory: String::new(),
+            interval_hours: 4,
+            keep_daily: 7,
+            keep_weekly: 4,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase", default)]
+struct PrinterSettings {
+    enabled: bool,
+    connection: String,
+    address: String,
+    paper_width_mm: u32,
+}
+
+impl Default for PrinterSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            connection: "tcp".into(),
+            address: String::new(),
+            paper_width_mm: 80,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase", default)]
+struct BusinessSettings {
+    name: String,
+    street: String,
+    postal_code: String,
+    city: String,
+    country: String,
+    tax_number: String,
+    vat_id: String,
+    cash_register_id: String,
+}
+
+impl Default for BusinessSettings {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            street: String::new(),
+            postal_code: String::new(),
+            city: String::new(),
+            country: "DEU".into(),
+            tax_number: String::new(),
+            vat_id: String::new(),
+            cash_register_id: "KASSE-1".into(),
+        }
+    }
 }
 
 impl AppSettings {
@@ -1911,6 +7953,13 @@ impl AppSettings {
             currency: "EUR".into(),
             auto_updates: true,
             enable_backups: false,
+            business: BusinessSettings::default(),
+            printer: PrinterSettings::default(),
+            backup: BackupSettings::default(),
+            credit_limit_cents: 0,
+            sepa: SepaSettings::default(),
+            checkin: CheckinSettings::default(),
+            backup_failure: None,
         }
     }
 }
@@ -1948,3 +7997,300 @@ impl SettingsState {
         Ok(())
     }
 }
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Role {
+    User,
+    Manager,
+    Admin,
+}
+
+impl Role {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "user" => Some(Role::User),
+            "manager" => Some(Role::Manager),
+            "admin" => Some(Role::Admin),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Default)]
+struct SessionState {
+    current: Mutex<Option<LoginResponse>>,
+}
+
+impl SessionState {
+    fn get(&self) -> Result<Option<LoginResponse>, String> {
+        let guard = self
+            .current
+            .lock()
+            .map_err(|_| "Sitzung konnte nicht gelesen werden".to_string())?;
+        Ok(guard.clone())
+    }
+
+    fn set(&self, user: LoginResponse) -> Result<(), String> {
+        let mut guard = self
+            .current
+            .lock()
+            .map_err(|_| "Sitzung konnte nicht gespeichert werden".to_string())?;
+        *guard = Some(user);
+        Ok(())
+    }
+
+    fn clear(&self) -> Result<(), String> {
+        let mut guard = self
+            .current
+            .lock()
+            .map_err(|_| "Sitzung konnte nicht beendet werden".to_string())?;
+        *guard = None;
+        Ok(())
+    }
+
+    fn require(&self, required: Role) -> Result<LoginResponse, String> {
+        let user = self.get()?.ok_or_else(|| "Nicht angemeldet".to_string())?;
+        let role = Role::from_name(&user.role)
+            .ok_or_else(|| format!("Unbekannte Rolle: {}", user.role))?;
+        if role < required {
+            return Err("Keine Berechtigung für diese Aktion".into());
+        }
+        Ok(user)
+    }
+}
+
+struct TseState {
+    device: Box<dyn Tse>,
+}
+
+impl TseState {
+    fn new(device: Box<dyn Tse>) -> Self {
+        Self { device }
+    }
+
+    fn device(&self) -> &dyn Tse {
+        self.device.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn database() -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch("PRAGMA foreign_keys = ON;").unwrap();
+        migrations::run(&mut conn, Path::new(":memory:"), Path::new("")).unwrap();
+        seed_default_product_types(&conn).unwrap();
+        seed_default_products(&mut conn).unwrap();
+        seed_default_tax_rates(&mut conn).unwrap();
+        conn
+    }
+
+    /// Books one unit of the product and returns tax rate, rate and tax of the line.
+    fn sell(conn: &Connection, product_id: i64) -> Result<(i64, i64, i64), String> {
+        conn.execute("INSERT INTO buckets (name) VALUES ('Test')", [])
+            .unwrap();
+        let bucket_id = conn.last_insert_rowid();
+        conn.execute(
+            "INSERT INTO bucket_items (bucket_id, product_id, product_name, quantity, price_cents)
+            SELECT ?1, id, name, 1, price_cents FROM products WHERE id = ?2",
+            [bucket_id, product_id],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO transactions (quantity, total_cents) VALUES (1, 0)",
+            [],
+        )
+        .unwrap();
+        let transaction_id = conn.last_insert_rowid();
+        insert_sale_lines(conn, transaction_id, bucket_id)?;
+        Ok(conn
+            .query_row(
+                "SELECT tax_rate_id, vat_rate_basis_points, tax_cents
+                FROM transaction_lines WHERE transaction_id = ?",
+                [transaction_id],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .unwrap())
+    }
+
+    #[test]
+    fn untaxed_product_uses_default_rate() {
+        let conn = database();
+        let (product_id, price_cents): (i64, i64) = conn
+            .query_row(
+                "SELECT id, price_cents FROM products WHERE tax_rate_id IS NULL LIMIT 1",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        let default_id: i64 = conn
+            .query_row(
+                "SELECT id FROM tax_rates WHERE is_default = 1 AND vat_key = 1",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        let (tax_rate_id, rate, tax_cents) = sell(&conn, product_id).unwrap();
+        assert_eq!(tax_rate_id, default_id);
+        assert_eq!(rate, 1900);
+        assert_eq!(
+            tax_cents,
+            price_cents - (price_cents as f64 * 10000.0 / 11900.0).round() as i64
+        );
+    }
+
+    #[test]
+    fn sale_without_any_rate_is_refused() {
+        let conn = database();
+        conn.execute("UPDATE tax_rates SET is_default = 0", [])
+            .unwrap();
+        let err = sell(&conn, 1).unwrap_err();
+        assert!(err.contains("kein Standardsteuersatz"), "{}", err);
+        let lines: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM transaction_lines WHERE vat_rate_basis_points IS NOT NULL",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(lines, 0);
+    }
+
+    #[test]
+    fn rate_without_valid_period_is_refused() {
+        let conn = database();
+        conn.execute(
+            "UPDATE tax_rate_periods SET valid_from = '2099-01-01', valid_to = NULL
+            WHERE tax_rate_id = (SELECT id FROM tax_rates WHERE is_default = 1)",
+            [],
+        )
+        .unwrap();
+        let err = sell(&conn, 1).unwrap_err();
+        assert!(err.contains("heute kein gültiger Steuersatz"), "{}", err);
+    }
+
+    /// Books three sales and journals them; returns the transaction ids.
+    fn journaled_sales(conn: &Connection) -> Vec<i64> {
+        (1..=3)
+            .map(|cents| {
+                conn.execute(
+                    "INSERT INTO transactions (quantity, total_cents) VALUES (1, ?)",
+                    [cents * 100],
+                )
+                .unwrap();
+                let transaction_id = conn.last_insert_rowid();
+                append_journal_entry(conn, transaction_id, "sale").unwrap();
+                transaction_id
+            })
+            .collect()
+    }
+
+    fn problems(verification: &JournalVerification) -> Vec<(Option<i64>, &str)> {
+        verification
+            .issues
+            .iter()
+            .map(|issue| (issue.transaction_id, issue.problem.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn journal_entries_are_chained() {
+        let conn = database();
+        journaled_sales(&conn);
+        let hashes: Vec<(String, String, String)> = conn
+            .prepare("SELECT payload, prev_hash, hash FROM journal_entries ORDER BY id")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(hashes.len(), 3);
+        assert_eq!(hashes[0].1, JOURNAL_GENESIS_HASH);
+        for pair in hashes.windows(2) {
+            assert_eq!(pair[1].1, pair[0].2);
+        }
+        for (payload, prev_hash, hash) in &hashes {
+            assert_eq!(&journal_hash(prev_hash, payload), hash);
+        }
+
+        let verification = verify_journal_chain(&conn).unwrap();
+        assert!(verification.valid);
+        assert_eq!(verification.entry_count, 3);
+        assert_eq!(verification.last_hash.as_ref(), Some(&hashes[2].2));
+    }
+
+    #[test]
+    fn changed_transaction_is_detected() {
+        let conn = database();
+        let ids = journaled_sales(&conn);
+        conn.execute_batch("DROP TRIGGER transactions_no_update;")
+            .unwrap();
+        conn.execute(
+            "UPDATE transactions SET total_cents = 1 WHERE id = ?",
+            [ids[1]],
+        )
+        .unwrap();
+        let verification = verify_journal_chain(&conn).unwrap();
+        assert!(!verification.valid);
+        assert_eq!(
+            problems(&verification),
+            vec![(Some(ids[1]), "Transaktion wurde nach der Buchung verändert")]
+        );
+    }
+
+    #[test]
+    fn changed_journal_entry_is_detected() {
+        let conn = database();
+        let ids = journaled_sales(&conn);
+        conn.execute_batch("DROP TRIGGER journal_entries_no_update;")
+            .unwrap();
+        conn.execute(
+            "UPDATE journal_entries
+            SET payload = REPLACE(payload, '\"totalCents\":100', '\"totalCents\":1')
+            WHERE transaction_id = ?",
+            [ids[0]],
+        )
+        .unwrap();
+        let verification = verify_journal_chain(&conn).unwrap();
+        assert!(!verification.valid);
+        assert_eq!(
+            problems(&verification),
+            vec![
+                (
+                    Some(ids[0]),
+                    "Hash des Eintrags stimmt nicht mit dem Inhalt überein"
+                ),
+                (Some(ids[0]), "Transaktion wurde nach der Buchung verändert"),
+            ]
+        );
+    }
+
+    #[test]
+    fn removed_journal_entry_is_detected() {
+        let conn = database();
+        let ids = journaled_sales(&conn);
+        conn.execute_batch("DROP TRIGGER journal_entries_no_delete;")
+            .unwrap();
+        conn.execute(
+            "DELETE FROM journal_entries WHERE transaction_id = ?",
+            [ids[1]],
+        )
+        .unwrap();
+        let verification = verify_journal_chain(&conn).unwrap();
+        assert!(!verification.valid);
+        assert_eq!(verification.entry_count, 2);
+        assert_eq!(
+            problems(&verification),
+            vec![
+                (
+                    Some(ids[2]),
+                    "Verkettung unterbrochen: vorheriger Hash stimmt nicht überein"
+                ),
+                (Some(ids[1]), "Transaktion ohne Journaleintrag"),
+            ]
+        );
+    }
+}
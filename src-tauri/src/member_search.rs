@@ -0,0 +1,42 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+
+pub const DEFAULT_LIMIT: u32 = 50;
+pub const MAX_LIMIT: u32 = 200;
+
+/// Turns free text into an FTS5 expression: every word becomes a quoted
+/// prefix term and all terms must match. Punctuation is dropped because the
+/// unicode61 tokenizer splits on it anyway (e-mail addresses, phone numbers).
+pub fn match_expression(query: &str) -> Option<String> {
+    let terms: Vec<String> = query
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|term| !term.is_empty())
+        .map(|term| format!("\"{}\"*", term))
+        .collect();
+    (!terms.is_empty()).then(|| terms.join(" "))
+}
+
+/// Position after the last returned member in name order.
+pub struct Cursor {
+    pub last_name: String,
+    pub first_name: String,
+    pub id: i64,
+}
+
+impl Cursor {
+    pub fn encode(&self) -> String {
+        let json = serde_json::json!([self.last_name, self.first_name, self.id]);
+        URL_SAFE_NO_PAD.encode(json.to_string())
+    }
+
+    pub fn decode(value: &str) -> Result<Self, String> {
+        let invalid = || "Ungültiger Seitenzeiger".to_string();
+        let bytes = URL_SAFE_NO_PAD.decode(value).map_err(|_| invalid())?;
+        let (last_name, first_name, id): (String, String, i64) =
+            serde_json::from_slice(&bytes).map_err(|_| invalid())?;
+        Ok(Cursor {
+            last_name,
+            first_name,
+            id,
+        })
+    }
+}
@@ -0,0 +1,1318 @@
+use rusqlite::{Connection, Transaction};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+struct Migration {
+    version: i64,
+    name: &'static str,
+    up: fn(&Transaction) -> rusqlite::Result<()>,
+}
+
+// Databases created before schema versioning report user_version 0 but may
+// already contain any subset of migrations 1-6, so those steps are idempotent.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "Basisschema",
+        up: base_schema,
+    },
+    Migration {
+        version: 2,
+        name: "Belegpositionen und Zahlungen",
+        up: transaction_lines_and_payments,
+    },
+    Migration {
+        version: 3,
+        name: "Kassensitzungen",
+        up: cash_sessions,
+    },
+    Migration {
+        version: 4,
+        name: "Storno und Journal",
+        up: storno_and_journal,
+    },
+    Migration {
+        version: 5,
+        name: "TSE-Signaturen",
+        up: tse_signatures,
+    },
+    Migration {
+        version: 6,
+        name: "Steuersätze",
+        up: tax_rates,
+    },
+    Migration {
+        version: 7,
+        name: "Guthabenbuchungen",
+        up: balance_entries,
+    },
+    Migration {
+        version: 8,
+        name: "Kreditlimits",
+        up: credit_limits,
+    },
+    Migration {
+        version: 9,
+        name: "Mitgliedschaften im Verkauf",
+        up: membership_sales,
+    },
+    Migration {
+        version: 10,
+        name: "Mitgliedschaftsverlauf",
+        up: membership_history,
+    },
+    Migration {
+        version: 11,
+        name: "Lastschriftverträge",
+        up: direct_debit_contracts,
+    },
+    Migration {
+        version: 12,
+        name: "Check-in-Regeln",
+        up: checkin_rules,
+    },
+    Migration {
+        version: 13,
+        name: "Gästeeintritte",
+        up: guest_checkins,
+    },
+    Migration {
+        version: 14,
+        name: "Mitgliedskarten",
+        up: member_cards,
+    },
+    Migration {
+        version: 15,
+        name: "Haftungsausschlüsse",
+        up: waivers,
+    },
+    Migration {
+        version: 16,
+        name: "Sicherungsscheine",
+        up: belay_certifications,
+    },
+    Migration {
+        version: 17,
+        name: "Mitgliedersuche",
+        up: member_search,
+    },
+    Migration {
+        version: 18,
+        name: "Haushalte",
+        up: households,
+    },
+    Migration {
+        version: 19,
+        name: "Offene TSE-Signaturen",
+        up: pending_tse_signatures,
+    },
+    Migration {
+        version: 20,
+        name: "Unlöschbare Guthabenbuchungen",
+        up: balance_entries_append_only,
+    },
+    Migration {
+        version: 21,
+        name: "Standardsteuersatz",
+        up: default_tax_rate,
+    },
+];
+
+pub fn latest_version() -> i64 {
+    MIGRATIONS.last().map(|m| m.version).unwrap_or(0)
+}
+
+pub fn run(conn: &mut Connection, db_path: &Path, backup_dir: &Path) -> Result<(), String> {
+    let current: i64 = conn
+        .pragma_query_value(None, "user_version", |row| row.get(0))
+        .map_err(|e| format!("Datenbankversion konnte nicht gelesen werden: {}", e))?;
+    let latest = latest_version();
+    if current > latest {
+        return Err(format!(
+            "Die Datenbank hat Version {}, dieses Programm kennt nur Version {}. Bitte eine aktuelle Programmversion verwenden.",
+            current, latest
+        ));
+    }
+    if current == latest {
+        return Ok(());
+    }
+
+    let backup = backup_before_migration(conn, db_path, backup_dir, current)?;
+    let mut version = current;
+    for migration in MIGRATIONS.iter().filter(|m| m.version > current) {
+        apply(conn, migration).map_err(|e| {
+            let backup = backup
+                .as_ref()
+                .map(|path| format!(" Sicherung vor der Migration: {}.", path.display()))
+                .unwrap_or_default();
+            format!(
+                "Datenbankmigration {} ({}) ist fehlgeschlagen: {}. Die Datenbank steht weiterhin auf Version {}.{}",
+                migration.version, migration.name, e, version, backup
+            )
+        })?;
+        version = migration.version;
+    }
+    Ok(())
+}
+
+fn apply(conn: &mut Connection, migration: &Migration) -> rusqlite::Result<()> {
+    let tx = conn.transaction()?;
+    (migration.up)(&tx)?;
+    tx.pragma_update(None, "user_version", migration.version)?;
+    tx.commit()
+}
+
+fn backup_before_migration(
+    conn: &Connection,
+    db_path: &Path,
+    dir: &Path,
+    version: i64,
+) -> Result<Option<PathBuf>, String> {
+    let table_count: i64 = conn
+        .query_row("SELECT COUNT(*) FROM sqlite_master", [], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    if table_count == 0 {
+        return Ok(None);
+    }
+
+    fs::create_dir_all(dir)
+        .map_err(|e| format!("Sicherungsordner konnte nicht angelegt werden: {}", e))?;
+    let stamp: String = conn
+        .query_row(
+            "SELECT strftime('%Y%m%d-%H%M%S', 'now', 'localtime')",
+            [],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    let stem = db_path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("datenbank");
+    let path = dir.join(format!("{}-vor-migration-v{}-{}.db", stem, version, stamp));
+    conn.execute("VACUUM INTO ?", [path.to_string_lossy().as_ref()])
+        .map_err(|e| format!("Sicherung vor der Migration fehlgeschlagen: {}", e))?;
+    Ok(Some(path))
+}
+
+fn add_column(
+    tx: &Transaction,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> rusqlite::Result<()> {
+    let mut stmt = tx.prepare(&format!("PRAGMA table_info({})", table))?;
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        let name: String = row.get(1)?;
+        if name == column {
+            return Ok(());
+        }
+    }
+    tx.execute(
+        &format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition),
+        [],
+    )?;
+    Ok(())
+}
+
+fn base_schema(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS user_roles (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL UNIQUE
+        );
+        CREATE TABLE IF NOT EXISTS users (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            username TEXT NOT NULL UNIQUE,
+            display_name TEXT NOT NULL,
+            password_hash TEXT NOT NULL,
+            role_id INTEGER NOT NULL,
+            active INTEGER NOT NULL DEFAULT 1,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY(role_id) REFERENCES user_roles(id)
+        );
+        CREATE TABLE IF NOT EXISTS product_types (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            color TEXT,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        );
+        CREATE TABLE IF NOT EXISTS products (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            price_cents INTEGER NOT NULL,
+            accent TEXT,
+            icon TEXT,
+            note TEXT,
+            product_type_id INTEGER,
+            FOREIGN KEY(product_type_id) REFERENCES product_types(id)
+        );
+        CREATE TABLE IF NOT EXISTS transactions (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            product_id INTEGER,
+            quantity INTEGER NOT NULL DEFAULT 1,
+            total_cents INTEGER NOT NULL,
+            description TEXT,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY(product_id) REFERENCES products(id)
+        );
+        CREATE TABLE IF NOT EXISTS members (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            first_name TEXT NOT NULL,
+            last_name TEXT NOT NULL,
+            email TEXT,
+            phone TEXT,
+            status TEXT NOT NULL DEFAULT 'active',
+            notes TEXT,
+            balance_cents INTEGER NOT NULL DEFAULT 0,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        );
+        CREATE TABLE IF NOT EXISTS memberships (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            membership_type TEXT NOT NULL,
+            price_cents INTEGER,
+            notes TEXT,
+            duration_days INTEGER,
+            max_uses INTEGER,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        );
+        CREATE TABLE IF NOT EXISTS member_memberships (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            member_id INTEGER NOT NULL,
+            membership_id INTEGER NOT NULL,
+            remaining_uses INTEGER,
+            start_date TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            end_date TEXT,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY(member_id) REFERENCES members(id) ON DELETE CASCADE,
+            FOREIGN KEY(membership_id) REFERENCES memberships(id)
+        );
+        CREATE TABLE IF NOT EXISTS buckets (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            status TEXT NOT NULL DEFAULT 'open',
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        );
+        CREATE TABLE IF NOT EXISTS bucket_items (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            bucket_id INTEGER NOT NULL,
+            product_id INTEGER NOT NULL,
+            product_name TEXT NOT NULL,
+            quantity INTEGER NOT NULL DEFAULT 1,
+            price_cents INTEGER NOT NULL,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY(bucket_id) REFERENCES buckets(id) ON DELETE CASCADE,
+            FOREIGN KEY(product_id) REFERENCES products(id),
+            UNIQUE(bucket_id, product_id)
+        );
+        CREATE TABLE IF NOT EXISTS member_checkins (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            member_id INTEGER NOT NULL,
+            membership_id INTEGER,
+            member_membership_id INTEGER,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY(member_id) REFERENCES members(id) ON DELETE CASCADE,
+            FOREIGN KEY(membership_id) REFERENCES memberships(id),
+            FOREIGN KEY(member_membership_id) REFERENCES member_memberships(id) ON DELETE SET NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_bucket_items_bucket ON bucket_items(bucket_id);
+        ",
+    )?;
+    add_column(tx, "products", "product_type_id", "INTEGER")?;
+    add_column(tx, "members", "balance_cents", "INTEGER NOT NULL DEFAULT 0")?;
+    add_column(tx, "memberships", "duration_days", "INTEGER")?;
+    add_column(tx, "memberships", "max_uses", "INTEGER")?;
+    add_column(tx, "member_checkins", "member_membership_id", "INTEGER")
+}
+
+fn transaction_lines_and_payments(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS transaction_lines (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            transaction_id INTEGER NOT NULL,
+            product_id INTEGER,
+            product_name TEXT NOT NULL,
+            product_type_id INTEGER,
+            product_type_name TEXT,
+            unit_price_cents INTEGER NOT NULL,
+            quantity INTEGER NOT NULL DEFAULT 1,
+            line_total_cents INTEGER NOT NULL,
+            FOREIGN KEY(transaction_id) REFERENCES transactions(id) ON DELETE CASCADE,
+            FOREIGN KEY(product_id) REFERENCES products(id) ON DELETE SET NULL
+        );
+        CREATE TABLE IF NOT EXISTS transaction_payments (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            transaction_id INTEGER NOT NULL,
+            method TEXT NOT NULL CHECK (method IN ('cash', 'card', 'balance')),
+            amount_cents INTEGER NOT NULL,
+            member_id INTEGER,
+            FOREIGN KEY(transaction_id) REFERENCES transactions(id) ON DELETE CASCADE,
+            FOREIGN KEY(member_id) REFERENCES members(id) ON DELETE SET NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_transaction_payments_transaction ON transaction_payments(transaction_id);
+        CREATE INDEX IF NOT EXISTS idx_transaction_lines_transaction ON transaction_lines(transaction_id);
+        CREATE INDEX IF NOT EXISTS idx_transaction_lines_product ON transaction_lines(product_id);
+        ",
+    )?;
+    add_column(tx, "transactions", "member_id", "INTEGER")?;
+    add_column(tx, "transactions", "user_id", "INTEGER")
+}
+
+fn cash_sessions(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS cash_sessions (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            status TEXT NOT NULL DEFAULT 'open' CHECK (status IN ('open', 'closed')),
+            opened_by INTEGER NOT NULL,
+            opened_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            opening_float_cents INTEGER NOT NULL DEFAULT 0,
+            closed_by INTEGER,
+            closed_at TEXT,
+            counted_cash_cents INTEGER,
+            expected_cash_cents INTEGER,
+            difference_cents INTEGER,
+            z_number INTEGER UNIQUE,
+            z_report TEXT,
+            notes TEXT,
+            FOREIGN KEY(opened_by) REFERENCES users(id),
+            FOREIGN KEY(closed_by) REFERENCES users(id)
+        );
+        CREATE TABLE IF NOT EXISTS cash_movements (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            cash_session_id INTEGER NOT NULL,
+            kind TEXT NOT NULL CHECK (kind IN ('cash_in', 'cash_out')),
+            amount_cents INTEGER NOT NULL,
+            reason TEXT,
+            user_id INTEGER NOT NULL,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY(cash_session_id) REFERENCES cash_sessions(id),
+            FOREIGN KEY(user_id) REFERENCES users(id)
+        );
+        CREATE TRIGGER IF NOT EXISTS cash_sessions_closed_no_update
+        BEFORE UPDATE ON cash_sessions
+        WHEN OLD.status = 'closed'
+        BEGIN
+            SELECT RAISE(ABORT, 'Abgeschlossene Kassensitzungen sind unveränderlich');
+        END;
+        CREATE TRIGGER IF NOT EXISTS cash_sessions_closed_no_delete
+        BEFORE DELETE ON cash_sessions
+        WHEN OLD.status = 'closed'
+        BEGIN
+            SELECT RAISE(ABORT, 'Abgeschlossene Kassensitzungen sind unveränderlich');
+        END;
+        CREATE TRIGGER IF NOT EXISTS cash_movements_closed_session
+        BEFORE INSERT ON cash_movements
+        WHEN (SELECT status FROM cash_sessions WHERE id = NEW.cash_session_id) IS NOT 'open'
+        BEGIN
+            SELECT RAISE(ABORT, 'Kassensitzung ist nicht geöffnet');
+        END;
+        CREATE UNIQUE INDEX IF NOT EXISTS idx_cash_sessions_single_open ON cash_sessions(status) WHERE status = 'open';
+        ",
+    )?;
+    add_column(
+        tx,
+        "transactions",
+        "cash_session_id",
+        "INTEGER REFERENCES cash_sessions(id)",
+    )
+}
+
+fn storno_and_journal(tx: &Transaction) -> rusqlite::Result<()> {
+    add_column(
+        tx,
+        "transactions",
+        "cancels_transaction_id",
+        "INTEGER REFERENCES transactions(id)",
+    )?;
+    tx.execute_batch(
+        "
+        CREATE UNIQUE INDEX IF NOT EXISTS idx_transactions_cancels ON transactions(cancels_transaction_id)
+        WHERE cancels_transaction_id IS NOT NULL;
+        CREATE TABLE IF NOT EXISTS journal_entries (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            transaction_id INTEGER NOT NULL UNIQUE,
+            entry_type TEXT NOT NULL,
+            payload TEXT NOT NULL,
+            prev_hash TEXT NOT NULL,
+            hash TEXT NOT NULL,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY(transaction_id) REFERENCES transactions(id)
+        );
+        CREATE TRIGGER IF NOT EXISTS journal_entries_no_update
+        BEFORE UPDATE ON journal_entries
+        BEGIN
+            SELECT RAISE(ABORT, 'Journal ist unveränderlich');
+        END;
+        CREATE TRIGGER IF NOT EXISTS journal_entries_no_delete
+        BEFORE DELETE ON journal_entries
+        BEGIN
+            SELECT RAISE(ABORT, 'Journal ist unveränderlich');
+        END;
+        CREATE TRIGGER IF NOT EXISTS transactions_no_update
+        BEFORE UPDATE ON transactions
+        BEGIN
+            SELECT RAISE(ABORT, 'Transaktionen sind unveränderlich, bitte stornieren');
+        END;
+        CREATE TRIGGER IF NOT EXISTS transactions_no_delete
+        BEFORE DELETE ON transactions
+        BEGIN
+            SELECT RAISE(ABORT, 'Transaktionen sind unveränderlich, bitte stornieren');
+        END;
+        CREATE TRIGGER IF NOT EXISTS transaction_lines_no_update
+        BEFORE UPDATE OF transaction_id, product_name, unit_price_cents, quantity, line_total_cents
+        ON transaction_lines
+        BEGIN
+            SELECT RAISE(ABORT, 'Transaktionen sind unveränderlich, bitte stornieren');
+        END;
+        CREATE TRIGGER IF NOT EXISTS transaction_lines_no_delete
+        BEFORE DELETE ON transaction_lines
+        BEGIN
+            SELECT RAISE(ABORT, 'Transaktionen sind unveränderlich, bitte stornieren');
+        END;
+        CREATE TRIGGER IF NOT EXISTS transaction_payments_no_update
+        BEFORE UPDATE OF transaction_id, method, amount_cents
+        ON transaction_payments
+        BEGIN
+            SELECT RAISE(ABORT, 'Transaktionen sind unveränderlich, bitte stornieren');
+        END;
+        CREATE TRIGGER IF NOT EXISTS transaction_payments_no_delete
+        BEFORE DELETE ON transaction_payments
+        BEGIN
+            SELECT RAISE(ABORT, 'Transaktionen sind unveränderlich, bitte stornieren');
+        END;
+        ",
+    )
+}
+
+fn tse_signatures(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS tse_devices (
+            serial_number TEXT PRIMARY KEY,
+            provider TEXT NOT NULL,
+            signature_algorithm TEXT NOT NULL,
+            time_format TEXT NOT NULL,
+            public_key TEXT NOT NULL,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        );
+        CREATE TABLE IF NOT EXISTS transaction_signatures (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            transaction_id INTEGER NOT NULL UNIQUE,
+            tse_serial TEXT,
+            tse_transaction_number INTEGER,
+            signature_counter INTEGER,
+            start_time TEXT,
+            finish_time TEXT,
+            process_type TEXT NOT NULL,
+            process_data TEXT NOT NULL,
+            signature TEXT,
+            error TEXT,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY(transaction_id) REFERENCES transactions(id),
+            FOREIGN KEY(tse_serial) REFERENCES tse_devices(serial_number)
+        );
+        CREATE TRIGGER IF NOT EXISTS transaction_signatures_no_update
+        BEFORE UPDATE ON transaction_signatures
+        BEGIN
+            SELECT RAISE(ABORT, 'TSE-Signaturen sind unveränderlich');
+        END;
+        CREATE TRIGGER IF NOT EXISTS transaction_signatures_no_delete
+        BEFORE DELETE ON transaction_signatures
+        BEGIN
+            SELECT RAISE(ABORT, 'TSE-Signaturen sind unveränderlich');
+        END;
+        ",
+    )
+}
+
+fn tax_rates(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS tax_rates (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            vat_key INTEGER NOT NULL,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        );
+        CREATE TABLE IF NOT EXISTS tax_rate_periods (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            tax_rate_id INTEGER NOT NULL,
+            rate_basis_points INTEGER NOT NULL CHECK (rate_basis_points BETWEEN 0 AND 10000),
+            valid_from TEXT NOT NULL,
+            valid_to TEXT,
+            FOREIGN KEY(tax_rate_id) REFERENCES tax_rates(id) ON DELETE CASCADE
+        );
+        CREATE INDEX IF NOT EXISTS idx_tax_rate_periods_rate ON tax_rate_periods(tax_rate_id, valid_from);
+        ",
+    )?;
+    let tax_rate_reference = "INTEGER REFERENCES tax_rates(id) ON DELETE SET NULL";
+    add_column(tx, "products", "tax_rate_id", tax_rate_reference)?;
+    add_column(tx, "product_types", "tax_rate_id", tax_rate_reference)?;
+    add_column(tx, "transaction_lines", "tax_rate_id", tax_rate_reference)?;
+    add_column(tx, "transaction_lines", "tax_rate_name", "TEXT")?;
+    add_column(tx, "transaction_lines", "vat_key", "INTEGER")?;
+    add_column(tx, "transaction_lines", "vat_rate_basis_points", "INTEGER")?;
+    add_column(tx, "transaction_lines", "net_cents", "INTEGER")?;
+    add_column(tx, "transaction_lines", "tax_cents", "INTEGER")
+}
+
+fn balance_entries(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "
+        CREATE TABLE balance_entries (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            member_id INTEGER NOT NULL,
+            kind TEXT NOT NULL CHECK (kind IN ('opening', 'top_up', 'purchase', 'refund', 'correction')),
+            amount_cents INTEGER NOT NULL,
+            transaction_id INTEGER,
+            reason TEXT,
+            user_id INTEGER,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY(member_id) REFERENCES members(id) ON DELETE CASCADE,
+            FOREIGN KEY(transaction_id) REFERENCES transactions(id),
+            FOREIGN KEY(user_id) REFERENCES users(id)
+        );
+        CREATE INDEX idx_balance_entries_member ON balance_entries(member_id, id);
+        CREATE INDEX idx_balance_entries_transaction ON balance_entries(transaction_id);
+        INSERT INTO balance_entries (member_id, kind, amount_cents, reason)
+        SELECT id, 'opening', balance_cents, 'Übernahme des bisherigen Guthabens'
+        FROM members
+        WHERE balance_cents <> 0;
+        CREATE TRIGGER balance_entries_apply
+        AFTER INSERT ON balance_entries
+        BEGIN
+            UPDATE members
+            SET balance_cents = balance_cents + NEW.amount_cents,
+                updated_at = CURRENT_TIMESTAMP
+            WHERE id = NEW.member_id;
+        END;
+        CREATE TRIGGER balance_entries_no_update
+        BEFORE UPDATE ON balance_entries
+        BEGIN
+            SELECT RAISE(ABORT, 'Guthabenbuchungen sind unveränderlich');
+        END;
+        CREATE TRIGGER members_balance_from_ledger
+        BEFORE UPDATE OF balance_cents ON members
+        WHEN NEW.balance_cents IS NOT (
+            SELECT COALESCE(SUM(amount_cents), 0) FROM balance_entries WHERE member_id = NEW.id
+        )
+        BEGIN
+            SELECT RAISE(ABORT, 'Guthaben kann nur über Guthabenbuchungen geändert werden');
+        END;
+        CREATE TRIGGER members_balance_starts_empty
+        BEFORE INSERT ON members
+        WHEN NEW.balance_cents <> 0
+        BEGIN
+            SELECT RAISE(ABORT, 'Guthaben kann nur über Guthabenbuchungen geändert werden');
+        END;
+        ",
+    )
+}
+
+fn credit_limits(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "
+        ALTER TABLE members ADD COLUMN credit_limit_cents INTEGER
+            CHECK (credit_limit_cents IS NULL OR credit_limit_cents >= 0);
+        CREATE TABLE credit_overrides (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            transaction_id INTEGER NOT NULL,
+            member_id INTEGER,
+            shortfall_cents INTEGER NOT NULL,
+            credit_limit_cents INTEGER NOT NULL,
+            reason TEXT NOT NULL,
+            requested_by INTEGER NOT NULL,
+            approved_by INTEGER NOT NULL,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY(transaction_id) REFERENCES transactions(id),
+            FOREIGN KEY(member_id) REFERENCES members(id) ON DELETE SET NULL,
+            FOREIGN KEY(requested_by) REFERENCES users(id),
+            FOREIGN KEY(approved_by) REFERENCES users(id)
+        );
+        ",
+    )
+}
+
+fn membership_sales(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "
+        ALTER TABLE memberships ADD COLUMN tax_rate_id INTEGER
+            REFERENCES tax_rates(id) ON DELETE SET NULL;
+        ALTER TABLE transaction_lines ADD COLUMN membership_id INTEGER
+            REFERENCES memberships(id) ON DELETE SET NULL;
+        ALTER TABLE member_memberships ADD COLUMN transaction_id INTEGER
+            REFERENCES transactions(id);
+        CREATE TABLE bucket_items_new (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            bucket_id INTEGER NOT NULL,
+            product_id INTEGER,
+            membership_id INTEGER,
+            product_name TEXT NOT NULL,
+            quantity INTEGER NOT NULL DEFAULT 1,
+            price_cents INTEGER NOT NULL,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY(bucket_id) REFERENCES buckets(id) ON DELETE CASCADE,
+            FOREIGN KEY(product_id) REFERENCES products(id),
+            FOREIGN KEY(membership_id) REFERENCES memberships(id),
+            CHECK ((product_id IS NULL) <> (membership_id IS NULL)),
+            UNIQUE(bucket_id, product_id),
+            UNIQUE(bucket_id, membership_id)
+        );
+        INSERT INTO bucket_items_new (id, bucket_id, product_id, product_name, quantity, price_cents, created_at)
+        SELECT id, bucket_id, product_id, product_name, quantity, price_cents, created_at
+        FROM bucket_items;
+        DROP TABLE bucket_items;
+        ALTER TABLE bucket_items_new RENAME TO bucket_items;
+        CREATE INDEX idx_bucket_items_bucket ON bucket_items(bucket_id);
+        CREATE INDEX idx_member_memberships_transaction ON member_memberships(transaction_id);
+        ",
+    )
+}
+
+fn membership_history(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "
+        ALTER TABLE member_memberships ADD COLUMN frozen_since TEXT;
+        ALTER TABLE member_memberships ADD COLUMN renewed_from_id INTEGER
+            REFERENCES member_memberships(id) ON DELETE SET NULL;
+        CREATE TABLE member_membership_events (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            member_membership_id INTEGER NOT NULL,
+            kind TEXT NOT NULL
+                CHECK (kind IN ('assigned', 'renewed', 'frozen', 'unfrozen', 'extended', 'shifted')),
+            days INTEGER,
+            previous_end_date TEXT,
+            new_end_date TEXT,
+            reason TEXT,
+            user_id INTEGER,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY(member_membership_id) REFERENCES member_memberships(id) ON DELETE CASCADE,
+            FOREIGN KEY(user_id) REFERENCES users(id)
+        );
+        CREATE INDEX idx_member_membership_events_entry
+            ON member_membership_events(member_membership_id, id);
+        INSERT INTO member_membership_events (member_membership_id, kind, new_end_date, created_at)
+        SELECT id, 'assigned', end_date, created_at FROM member_memberships;
+        ",
+    )
+}
+
+fn direct_debit_contracts(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "
+        ALTER TABLE members ADD COLUMN iban TEXT;
+        ALTER TABLE members ADD COLUMN bic TEXT;
+        ALTER TABLE members ADD COLUMN debit_returned_at TEXT;
+        CREATE TABLE membership_contracts (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            member_id INTEGER NOT NULL,
+            membership_id INTEGER NOT NULL,
+            amount_cents INTEGER NOT NULL CHECK (amount_cents > 0),
+            interval_months INTEGER NOT NULL CHECK (interval_months IN (1, 3, 6, 12)),
+            mandate_reference TEXT NOT NULL UNIQUE,
+            mandate_signed_on TEXT NOT NULL,
+            start_date TEXT NOT NULL,
+            end_date TEXT,
+            cancelled_at TEXT,
+            next_due_date TEXT NOT NULL,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY(member_id) REFERENCES members(id),
+            FOREIGN KEY(membership_id) REFERENCES memberships(id)
+        );
+        CREATE TABLE sepa_exports (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            message_id TEXT NOT NULL,
+            collection_date TEXT NOT NULL,
+            invoice_count INTEGER NOT NULL,
+            total_cents INTEGER NOT NULL,
+            file_path TEXT,
+            created_by INTEGER,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY(created_by) REFERENCES users(id)
+        );
+        CREATE TABLE contract_invoices (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            contract_id INTEGER NOT NULL,
+            member_id INTEGER NOT NULL,
+            member_membership_id INTEGER,
+            period_start TEXT NOT NULL,
+            period_end TEXT NOT NULL,
+            due_date TEXT NOT NULL,
+            amount_cents INTEGER NOT NULL,
+            status TEXT NOT NULL DEFAULT 'open'
+                CHECK (status IN ('open', 'exported', 'paid', 'returned')),
+            end_to_end_id TEXT UNIQUE,
+            sequence_type TEXT CHECK (sequence_type IN ('FRST', 'RCUR')),
+            sepa_export_id INTEGER,
+            return_reason TEXT,
+            returned_at TEXT,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY(contract_id) REFERENCES membership_contracts(id),
+            FOREIGN KEY(member_id) REFERENCES members(id),
+            FOREIGN KEY(member_membership_id) REFERENCES member_memberships(id) ON DELETE SET NULL,
+            FOREIGN KEY(sepa_export_id) REFERENCES sepa_exports(id)
+        );
+        CREATE INDEX idx_membership_contracts_due ON membership_contracts(next_due_date);
+        CREATE INDEX idx_contract_invoices_status ON contract_invoices(status, due_date);
+        CREATE INDEX idx_contract_invoices_contract ON contract_invoices(contract_id, id);
+        ",
+    )
+}
+
+fn checkin_rules(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "
+        ALTER TABLE memberships ADD COLUMN allowed_weekdays TEXT;
+        ALTER TABLE member_checkins ADD COLUMN reentry INTEGER NOT NULL DEFAULT 0;
+        CREATE TABLE membership_time_windows (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            membership_id INTEGER NOT NULL,
+            start_time TEXT NOT NULL,
+            end_time TEXT NOT NULL,
+            CHECK (start_time < end_time),
+            FOREIGN KEY(membership_id) REFERENCES memberships(id) ON DELETE CASCADE
+        );
+        CREATE INDEX idx_membership_time_windows_membership ON membership_time_windows(membership_id);
+        ",
+    )
+}
+
+fn guest_checkins(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "
+        ALTER TABLE products ADD COLUMN grants_entry INTEGER NOT NULL DEFAULT 0;
+        CREATE TABLE member_checkins_new (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            member_id INTEGER,
+            membership_id INTEGER,
+            member_membership_id INTEGER,
+            transaction_line_id INTEGER,
+            guest_name TEXT,
+            waiver_reference TEXT,
+            reentry INTEGER NOT NULL DEFAULT 0,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY(member_id) REFERENCES members(id) ON DELETE CASCADE,
+            FOREIGN KEY(membership_id) REFERENCES memberships(id),
+            FOREIGN KEY(member_membership_id) REFERENCES member_memberships(id) ON DELETE SET NULL,
+            FOREIGN KEY(transaction_line_id) REFERENCES transaction_lines(id),
+            CHECK ((member_id IS NULL) <> (transaction_line_id IS NULL))
+        );
+        INSERT INTO member_checkins_new (id, member_id, membership_id, member_membership_id, reentry, created_at)
+        SELECT id, member_id, membership_id, member_membership_id, reentry, created_at
+        FROM member_checkins;
+        DROP TABLE member_checkins;
+        ALTER TABLE member_checkins_new RENAME TO member_checkins;
+        CREATE INDEX idx_member_checkins_member ON member_checkins(member_id, created_at);
+        CREATE INDEX idx_member_checkins_line ON member_checkins(transaction_line_id);
+        ",
+    )
+}
+
+fn member_cards(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "
+        CREATE TABLE member_cards (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            member_id INTEGER NOT NULL,
+            token TEXT NOT NULL UNIQUE,
+            kind TEXT NOT NULL CHECK (kind IN ('qr', 'barcode', 'nfc')),
+            status TEXT NOT NULL DEFAULT 'active'
+                CHECK (status IN ('active', 'lost', 'blocked')),
+            label TEXT,
+            status_changed_at TEXT,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY(member_id) REFERENCES members(id) ON DELETE CASCADE
+        );
+        CREATE INDEX idx_member_cards_member ON member_cards(member_id);
+        ",
+    )
+}
+
+fn waivers(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "
+        ALTER TABLE members ADD COLUMN birth_date TEXT;
+        CREATE TABLE waiver_texts (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            version TEXT NOT NULL UNIQUE,
+            body TEXT NOT NULL,
+            created_by INTEGER,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY(created_by) REFERENCES users(id)
+        );
+        CREATE TABLE waivers (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            member_id INTEGER NOT NULL,
+            waiver_text_id INTEGER NOT NULL,
+            signed_on TEXT NOT NULL,
+            signer_name TEXT NOT NULL,
+            guardian_name TEXT,
+            signature_mime TEXT,
+            signature BLOB,
+            recorded_by INTEGER,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY(member_id) REFERENCES members(id) ON DELETE CASCADE,
+            FOREIGN KEY(waiver_text_id) REFERENCES waiver_texts(id),
+            FOREIGN KEY(recorded_by) REFERENCES users(id)
+        );
+        CREATE INDEX idx_waivers_member ON waivers(member_id, waiver_text_id);
+        ",
+    )
+}
+
+fn belay_certifications(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "
+        CREATE TABLE member_certifications (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            member_id INTEGER NOT NULL,
+            kind TEXT NOT NULL CHECK (kind IN ('toprope', 'vorstieg')),
+            issued_on TEXT NOT NULL,
+            expires_on TEXT,
+            issued_by INTEGER,
+            notes TEXT,
+            revoked_at TEXT,
+            revoked_by INTEGER,
+            revoke_reason TEXT,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            CHECK (expires_on IS NULL OR expires_on > issued_on),
+            FOREIGN KEY(member_id) REFERENCES members(id) ON DELETE CASCADE,
+            FOREIGN KEY(issued_by) REFERENCES users(id),
+            FOREIGN KEY(revoked_by) REFERENCES users(id)
+        );
+        CREATE INDEX idx_member_certifications_member ON member_certifications(member_id, kind);
+        ",
+    )
+}
+
+fn member_search(tx: &Transaction) -> rusqlite::Result<()> {
+    // One FTS row per member, keyed by the member id. Phone numbers are also
+    // stored as bare digits so "0170/123" finds "0170 123".
+    tx.execute_batch(
+        "
+        CREATE VIRTUAL TABLE member_search USING fts5(
+            name,
+            email,
+            phone,
+            phone_digits,
+            cards,
+            tokenize = 'unicode61 remove_diacritics 2',
+            prefix = '2 3'
+        );
+        CREATE VIEW member_search_source AS
+        SELECT m.id AS member_id,
+               m.first_name || ' ' || m.last_name AS name,
+               COALESCE(m.email, '') AS email,
+               COALESCE(m.phone, '') AS phone,
+               replace(replace(replace(replace(replace(replace(COALESCE(m.phone, ''),
+                   ' ', ''), '-', ''), '/', ''), '(', ''), ')', ''), '+', '') AS phone_digits,
+               COALESCE((SELECT group_concat(token, ' ') FROM member_cards c WHERE c.member_id = m.id), '') AS cards
+        FROM members m;
+        INSERT INTO member_search (rowid, name, email, phone, phone_digits, cards)
+        SELECT member_id, name, email, phone, phone_digits, cards FROM member_search_source;
+
+        CREATE TRIGGER member_search_insert AFTER INSERT ON members
+        BEGIN
+            INSERT INTO member_search (rowid, name, email, phone, phone_digits, cards)
+            SELECT member_id, name, email, phone, phone_digits, cards
+            FROM member_search_source WHERE member_id = NEW.id;
+        END;
+        CREATE TRIGGER member_search_update AFTER UPDATE OF first_name, last_name, email, phone ON members
+        BEGIN
+            DELETE FROM member_search WHERE rowid = OLD.id;
+            INSERT INTO member_search (rowid, name, email, phone, phone_digits, cards)
+            SELECT member_id, name, email, phone, phone_digits, cards
+            FROM member_search_source WHERE member_id = NEW.id;
+        END;
+        CREATE TRIGGER member_search_delete AFTER DELETE ON members
+        BEGIN
+            DELETE FROM member_search WHERE rowid = OLD.id;
+        END;
+        CREATE TRIGGER member_search_card_insert AFTER INSERT ON member_cards
+        BEGIN
+            UPDATE member_search
+            SET cards = (SELECT cards FROM member_search_source WHERE member_id = NEW.member_id)
+            WHERE rowid = NEW.member_id;
+        END;
+        CREATE TRIGGER member_search_card_update AFTER UPDATE OF token, member_id ON member_cards
+        BEGIN
+            UPDATE member_search
+            SET cards = (SELECT cards FROM member_search_source WHERE member_id = OLD.member_id)
+            WHERE rowid = OLD.member_id;
+            UPDATE member_search
+            SET cards = (SELECT cards FROM member_search_source WHERE member_id = NEW.member_id)
+            WHERE rowid = NEW.member_id;
+        END;
+        CREATE TRIGGER member_search_card_delete AFTER DELETE ON member_cards
+        BEGIN
+            UPDATE member_search
+            SET cards = (SELECT cards FROM member_search_source WHERE member_id = OLD.member_id)
+            WHERE rowid = OLD.member_id;
+        END;
+        ",
+    )
+}
+
+fn households(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "
+        CREATE TABLE households (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            payer_member_id INTEGER,
+            shared_balance INTEGER NOT NULL DEFAULT 0,
+            shared_memberships INTEGER NOT NULL DEFAULT 0,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY(payer_member_id) REFERENCES members(id) ON DELETE SET NULL
+        );
+        CREATE TABLE household_members (
+            household_id INTEGER NOT NULL,
+            member_id INTEGER NOT NULL UNIQUE,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            PRIMARY KEY (household_id, member_id),
+            FOREIGN KEY(household_id) REFERENCES households(id) ON DELETE CASCADE,
+            FOREIGN KEY(member_id) REFERENCES members(id) ON DELETE CASCADE
+        );
+        ",
+    )
+}
+
+fn pending_tse_signatures(tx: &Transaction) -> rusqlite::Result<()> {
+    // Signatures the TSE issued for bookings that were rolled back afterwards.
+    // transaction_id refers to the booking that never got committed.
+    tx.execute_batch(
+        "
+        CREATE TABLE pending_tse_signatures (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            transaction_id INTEGER NOT NULL,
+            tse_serial TEXT NOT NULL,
+            tse_transaction_number INTEGER NOT NULL,
+            signature_counter INTEGER NOT NULL,
+            start_time TEXT NOT NULL,
+            finish_time TEXT NOT NULL,
+            process_type TEXT NOT NULL,
+            process_data TEXT NOT NULL,
+            signature TEXT NOT NULL,
+            error TEXT NOT NULL,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            resolved_at TEXT,
+            resolved_by INTEGER,
+            resolution TEXT,
+            FOREIGN KEY(resolved_by) REFERENCES users(id)
+        );
+        ",
+    )
+}
+
+fn balance_entries_append_only(tx: &Transaction) -> rusqlite::Result<()> {
+    // The ledger used to be cascade-deleted together with its member. The table is
+    // rebuilt without ON DELETE CASCADE; the members trigger refers to it and would
+    // block the rename, so it is recreated afterwards.
+    tx.execute_batch(
+        "
+        DROP TRIGGER members_balance_from_ledger;
+        CREATE TABLE balance_entries_new (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            member_id INTEGER NOT NULL,
+            kind TEXT NOT NULL CHECK (kind IN ('opening', 'top_up', 'purchase', 'refund', 'correction')),
+            amount_cents INTEGER NOT NULL,
+            transaction_id INTEGER,
+            reason TEXT,
+            user_id INTEGER,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY(member_id) REFERENCES members(id),
+            FOREIGN KEY(transaction_id) REFERENCES transactions(id),
+            FOREIGN KEY(user_id) REFERENCES users(id)
+        );
+        INSERT INTO balance_entries_new (id, member_id, kind, amount_cents, transaction_id, reason, user_id, created_at)
+        SELECT id, member_id, kind, amount_cents, transaction_id, reason, user_id, created_at
+        FROM balance_entries;
+        DROP TABLE balance_entries;
+        ALTER TABLE balance_entries_new RENAME TO balance_entries;
+        CREATE INDEX idx_balance_entries_member ON balance_entries(member_id, id);
+        CREATE INDEX idx_balance_entries_transaction ON balance_entries(transaction_id);
+        CREATE TRIGGER balance_entries_apply
+        AFTER INSERT ON balance_entries
+        BEGIN
+            UPDATE members
+            SET balance_cents = balance_cents + NEW.amount_cents,
+                updated_at = CURRENT_TIMESTAMP
+            WHERE id = NEW.member_id;
+        END;
+        CREATE TRIGGER balance_entries_no_update
+        BEFORE UPDATE ON balance_entries
+        BEGIN
+            SELECT RAISE(ABORT, 'Guthabenbuchungen sind unveränderlich');
+        END;
+        CREATE TRIGGER balance_entries_no_delete
+        BEFORE DELETE ON balance_entries
+        BEGIN
+            SELECT RAISE(ABORT, 'Guthabenbuchungen können nicht gelöscht werden');
+        END;
+        CREATE TRIGGER members_balance_from_ledger
+        BEFORE UPDATE OF balance_cents ON members
+        WHEN NEW.balance_cents IS NOT (
+            SELECT COALESCE(SUM(amount_cents), 0) FROM balance_entries WHERE member_id = NEW.id
+        )
+        BEGIN
+            SELECT RAISE(ABORT, 'Guthaben kann nur über Guthabenbuchungen geändert werden');
+        END;
+        ",
+    )
+}
+
+fn default_tax_rate(tx: &Transaction) -> rusqlite::Result<()> {
+    // Sales lines without a tax rate of their own are taxed with the default rate.
+    add_column(tx, "tax_rates", "is_default", "INTEGER NOT NULL DEFAULT 0")?;
+    tx.execute_batch(
+        "
+        CREATE UNIQUE INDEX idx_tax_rates_default ON tax_rates(is_default) WHERE is_default = 1;
+        UPDATE tax_rates SET is_default = 1
+        WHERE id = (SELECT MIN(id) FROM tax_rates WHERE vat_key = 1);
+        ",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tables as created by releases before schema versioning.
+    const UNVERSIONED_SCHEMA: &str = "
+        CREATE TABLE user_roles (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL UNIQUE
+        );
+        CREATE TABLE users (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            username TEXT NOT NULL UNIQUE,
+            display_name TEXT NOT NULL,
+            password_hash TEXT NOT NULL,
+            role_id INTEGER NOT NULL,
+            active INTEGER NOT NULL DEFAULT 1,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY(role_id) REFERENCES user_roles(id)
+        );
+        CREATE TABLE product_types (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            color TEXT,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        );
+        CREATE TABLE products (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            price_cents INTEGER NOT NULL,
+            accent TEXT,
+            icon TEXT,
+            note TEXT,
+            product_type_id INTEGER,
+            FOREIGN KEY(product_type_id) REFERENCES product_types(id)
+        );
+        CREATE TABLE transactions (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            product_id INTEGER,
+            quantity INTEGER NOT NULL DEFAULT 1,
+            total_cents INTEGER NOT NULL,
+            description TEXT,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY(product_id) REFERENCES products(id)
+        );
+        CREATE TABLE members (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            first_name TEXT NOT NULL,
+            last_name TEXT NOT NULL,
+            email TEXT,
+            phone TEXT,
+            status TEXT NOT NULL DEFAULT 'active',
+            notes TEXT,
+            balance_cents INTEGER NOT NULL DEFAULT 0,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        );
+        CREATE TABLE memberships (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            membership_type TEXT NOT NULL,
+            price_cents INTEGER,
+            notes TEXT,
+            duration_days INTEGER,
+            max_uses INTEGER,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        );
+        CREATE TABLE member_memberships (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            member_id INTEGER NOT NULL,
+            membership_id INTEGER NOT NULL,
+            remaining_uses INTEGER,
+            start_date TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            end_date TEXT,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY(member_id) REFERENCES members(id) ON DELETE CASCADE,
+            FOREIGN KEY(membership_id) REFERENCES memberships(id)
+        );
+        CREATE TABLE buckets (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            status TEXT NOT NULL DEFAULT 'open',
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        );
+        CREATE TABLE bucket_items (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            bucket_id INTEGER NOT NULL,
+            product_id INTEGER NOT NULL,
+            product_name TEXT NOT NULL,
+            quantity INTEGER NOT NULL DEFAULT 1,
+            price_cents INTEGER NOT NULL,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY(bucket_id) REFERENCES buckets(id) ON DELETE CASCADE,
+            FOREIGN KEY(product_id) REFERENCES products(id),
+            UNIQUE(bucket_id, product_id)
+        );
+        CREATE TABLE member_checkins (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            member_id INTEGER NOT NULL,
+            membership_id INTEGER,
+            member_membership_id INTEGER,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY(member_id) REFERENCES members(id) ON DELETE CASCADE,
+            FOREIGN KEY(membership_id) REFERENCES memberships(id),
+            FOREIGN KEY(member_membership_id) REFERENCES member_memberships(id) ON DELETE SET NULL
+        );
+        CREATE INDEX idx_bucket_items_bucket ON bucket_items(bucket_id);
+    ";
+
+    fn connection() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch("PRAGMA foreign_keys = ON;").unwrap();
+        conn
+    }
+
+    fn user_version(conn: &Connection) -> i64 {
+        conn.pragma_query_value(None, "user_version", |row| row.get(0))
+            .unwrap()
+    }
+
+    fn assert_consistent(conn: &Connection) {
+        let integrity: String = conn
+            .query_row("PRAGMA integrity_check", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(integrity, "ok");
+        let violations: i64 = conn
+            .query_row("SELECT COUNT(*) FROM pragma_foreign_key_check", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(violations, 0);
+    }
+
+    #[test]
+    fn migrates_empty_database_to_latest_version() {
+        let mut conn = connection();
+        run(&mut conn, Path::new(":memory:"), Path::new("")).unwrap();
+        assert_eq!(user_version(&conn), latest_version());
+        assert_consistent(&conn);
+
+        // A second run is a no-op.
+        run(&mut conn, Path::new(":memory:"), Path::new("")).unwrap();
+        assert_eq!(user_version(&conn), latest_version());
+    }
+
+    #[test]
+    fn migrates_unversioned_database_and_keeps_data() {
+        let mut conn = connection();
+        conn.execute_batch(UNVERSIONED_SCHEMA).unwrap();
+        conn.execute_batch(
+            "
+            INSERT INTO user_roles (id, name) VALUES (1, 'admin');
+            INSERT INTO users (id, username, display_name, password_hash, role_id)
+            VALUES (1, 'admin', 'Admin', 'hash', 1);
+            INSERT INTO products (id, name, price_cents) VALUES (1, 'Tageskarte', 1400);
+            INSERT INTO transactions (id, product_id, quantity, total_cents, description)
+            VALUES (1, 1, 2, 2800, 'Tageskarte x2');
+            INSERT INTO members (id, first_name, last_name, balance_cents)
+            VALUES (1, 'Erika', 'Müller', 1250),
+                   (2, 'Max', 'Mustermann', 0);
+            INSERT INTO memberships (id, membership_type, price_cents, max_uses)
+            VALUES (1, '10er-Karte', 12000, 10);
+            INSERT INTO member_memberships (id, member_id, membership_id, remaining_uses, start_date)
+            VALUES (1, 1, 1, 7, '2025-01-01');
+            INSERT INTO member_checkins (member_id, membership_id, member_membership_id)
+            VALUES (1, 1, 1);
+            ",
+        )
+        .unwrap();
+        let backup_dir = std::env::temp_dir().join(format!("migrations-{}", std::process::id()));
+
+        run(&mut conn, Path::new("boulderado.db"), &backup_dir).unwrap();
+        assert_eq!(user_version(&conn), latest_version());
+        assert_consistent(&conn);
+
+        let backups = fs::read_dir(&backup_dir).unwrap().count();
+        let _ = fs::remove_dir_all(&backup_dir);
+        assert_eq!(backups, 1);
+
+        let transaction: (i64, i64, Option<String>) = conn
+            .query_row(
+                "SELECT quantity, total_cents, description FROM transactions WHERE id = 1",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .unwrap();
+        assert_eq!(transaction, (2, 2800, Some("Tageskarte x2".to_string())));
+
+        let balances: Vec<(i64, i64)> = conn
+            .prepare(
+                "SELECT m.balance_cents, COALESCE(SUM(b.amount_cents), 0)
+                FROM members m
+                LEFT JOIN balance_entries b ON b.member_id = m.id
+                GROUP BY m.id
+                ORDER BY m.id",
+            )
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(balances, vec![(1250, 1250), (0, 0)]);
+
+        let remaining_uses: i64 = conn
+            .query_row(
+                "SELECT remaining_uses FROM member_memberships WHERE id = 1",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(remaining_uses, 7);
+        let checkins: i64 = conn
+            .query_row("SELECT COUNT(*) FROM member_checkins", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(checkins, 1);
+    }
+
+    #[test]
+    fn refuses_newer_database() {
+        let mut conn = connection();
+        conn.pragma_update(None, "user_version", latest_version() + 1)
+            .unwrap();
+        let err = run(&mut conn, Path::new(":memory:"), Path::new("")).unwrap_err();
+        assert!(err.contains("aktuelle Programmversion"), "{}", err);
+    }
+}
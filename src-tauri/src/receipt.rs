@@ -0,0 +1,762 @@
+use rusqlite::{Connection, OptionalExtension};
+use serde::Serialize;
+use std::{
+    fs::OpenOptions,
+    io::Write,
+    net::{TcpStream, ToSocketAddrs},
+    path::Path,
+    time::Duration,
+};
+
+use crate::{format_amount, BusinessSettings, PaymentMethod, PrinterSettings};
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Receipt {
+    pub receipt_number: i64,
+    pub cash_register_id: String,
+    pub created_at: String,
+    pub cashier: Option<String>,
+    pub cancels_receipt_number: Option<i64>,
+    pub copy: bool,
+    pub currency: String,
+    pub header: ReceiptHeader,
+    pub lines: Vec<ReceiptLine>,
+    pub total_cents: i64,
+    pub vat: Vec<ReceiptVat>,
+    pub payments: Vec<ReceiptPayment>,
+    pub tse: Option<ReceiptTse>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ReceiptHeader {
+    pub name: String,
+    pub street: String,
+    pub postal_code: String,
+    pub city: String,
+    pub tax_number: String,
+    pub vat_id: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ReceiptLine {
+    pub name: String,
+    pub quantity: i64,
+    pub unit_price_cents: i64,
+    pub total_cents: i64,
+    pub vat_letter: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ReceiptVat {
+    pub letter: String,
+    pub rate_basis_points: i64,
+    pub net_cents: i64,
+    pub tax_cents: i64,
+    pub gross_cents: i64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ReceiptPayment {
+    pub label: String,
+    pub amount_cents: i64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ReceiptTse {
+    pub serial_number: Option<String>,
+    pub transaction_number: Option<i64>,
+    pub signature_counter: Option<i64>,
+    pub start_time: Option<String>,
+    pub finish_time: Option<String>,
+    pub process_type: String,
+    pub process_data: String,
+    pub signature: Option<String>,
+    pub signature_algorithm: Option<String>,
+    pub time_format: Option<String>,
+    pub public_key: Option<String>,
+    pub error: Option<String>,
+}
+
+pub(crate) fn load(
+    conn: &Connection,
+    business: &BusinessSettings,
+    currency: &str,
+    transaction_id: i64,
+    copy: bool,
+) -> Result<Receipt, String> {
+    let header = conn
+        .query_row(
+            "SELECT STRFTIME('%d.%m.%Y %H:%M', t.created_at, 'localtime'),
+                    u.display_name,
+                    t.cancels_transaction_id,
+                    t.total_cents
+            FROM transactions t
+            LEFT JOIN users u ON u.id = t.user_id
+            WHERE t.id = ?",
+            [transaction_id],
+            |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, Option<String>>(1)?,
+                    row.get::<_, Option<i64>>(2)?,
+                    row.get::<_, i64>(3)?,
+                ))
+            },
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    let (created_at, cashier, cancels_receipt_number, total_cents) =
+        header.ok_or_else(|| "Transaktion nicht gefunden".to_string())?;
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT product_name, quantity, unit_price_cents, line_total_cents,
+                    vat_rate_basis_points, net_cents, tax_cents
+            FROM transaction_lines WHERE transaction_id = ? ORDER BY id ASC",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([transaction_id], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, i64>(1)?,
+                row.get::<_, i64>(2)?,
+                row.get::<_, i64>(3)?,
+                row.get::<_, Option<i64>>(4)?,
+                row.get::<_, Option<i64>>(5)?,
+                row.get::<_, Option<i64>>(6)?,
+            ))
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    let mut vat: Vec<ReceiptVat> = Vec::new();
+    let mut lines = Vec::with_capacity(rows.len());
+    for (name, quantity, unit_price_cents, line_total, rate, net, tax) in rows {
+        let vat_letter = rate.map(|rate| {
+            let index = match vat.iter().position(|v| v.rate_basis_points == rate) {
+                Some(index) => index,
+                None => {
+                    vat.push(ReceiptVat {
+                        letter: ((b'A' + vat.len() as u8) as char).to_string(),
+                        rate_basis_points: rate,
+                        net_cents: 0,
+                        tax_cents: 0,
+                        gross_cents: 0,
+                    });
+                    vat.len() - 1
+                }
+            };
+            let entry = &mut vat[index];
+            entry.net_cents += net.unwrap_or(line_total);
+            entry.tax_cents += tax.unwrap_or(0);
+            entry.gross_cents += line_total;
+            entry.letter.clone()
+        });
+        lines.push(ReceiptLine {
+            name,
+            quantity,
+            unit_price_cents,
+            total_cents: line_total,
+            vat_letter,
+        });
+    }
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT method, amount_cents FROM transaction_payments WHERE transaction_id = ? ORDER BY id ASC",
+        )
+        .map_err(|e| e.to_string())?;
+    let payments = stmt
+        .query_map([transaction_id], |row| {
+            Ok(ReceiptPayment {
+                label: row.get::<_, PaymentMethod>(0)?.label().to_string(),
+                amount_cents: row.get(1)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    let tse = conn
+        .query_row(
+            "SELECT s.tse_serial, s.tse_transaction_number, s.signature_counter, s.start_time, s.finish_time,
+                    s.process_type, s.process_data, s.signature, d.signature_algorithm, d.time_format,
+                    d.public_key, s.error
+            FROM transaction_signatures s
+            LEFT JOIN tse_devices d ON d.serial_number = s.tse_serial
+            WHERE s.transaction_id = ?",
+            [transaction_id],
+            |row| {
+                Ok(ReceiptTse {
+                    serial_number: row.get(0)?,
+                    transaction_number: row.get(1)?,
+                    signature_counter: row.get(2)?,
+                    start_time: row.get(3)?,
+                    finish_time: row.get(4)?,
+                    process_type: row.get(5)?,
+                    process_data: row.get(6)?,
+                    signature: row.get(7)?,
+                    signature_algorithm: row.get(8)?,
+                    time_format: row.get(9)?,
+                    public_key: row.get(10)?,
+                    error: row.get(11)?,
+                })
+            },
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+
+    Ok(Receipt {
+        receipt_number: transaction_id,
+        cash_register_id: business.cash_register_id.clone(),
+        created_at,
+        cashier,
+        cancels_receipt_number,
+        copy,
+        currency: currency.to_string(),
+        header: ReceiptHeader {
+            name: business.name.clone(),
+            street: business.street.clone(),
+            postal_code: business.postal_code.clone(),
+            city: business.city.clone(),
+            tax_number: business.tax_number.clone(),
+            vat_id: business.vat_id.clone(),
+        },
+        lines,
+        total_cents,
+        vat,
+        payments,
+        tse,
+    })
+}
+
+enum Row {
+    Title(String),
+    Center(String),
+    Text(String),
+    Bold(String),
+    Rule,
+    Qr(String),
+}
+
+fn money(cents: i64) -> String {
+    format_amount(cents).replace('.', ",")
+}
+
+fn percent(basis_points: i64) -> String {
+    format!("{},{:02}%", basis_points / 100, basis_points % 100)
+}
+
+fn columns(left: &str, right: &str, width: usize) -> Vec<String> {
+    let used = left.chars().count() + right.chars().count();
+    if used < width {
+        vec![format!("{}{}{}", left, " ".repeat(width - used), right)]
+    } else {
+        let mut rows = wrap(left, width);
+        rows.push(format!("{:>width$}", right, width = width));
+        rows
+    }
+}
+
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let chars: Vec<char> = text.chars().collect();
+    if chars.is_empty() {
+        return vec![String::new()];
+    }
+    chars
+        .chunks(width.max(1))
+        .map(|chunk| chunk.iter().collect())
+        .collect()
+}
+
+fn layout(receipt: &Receipt, width: usize) -> Vec<Row> {
+    let mut rows = Vec::new();
+    let header = &receipt.header;
+    if !header.name.is_empty() {
+        rows.push(Row::Title(header.name.clone()));
+    }
+    if !header.street.is_empty() {
+        rows.push(Row::Center(header.street.clone()));
+    }
+    let city = format!("{} {}", header.postal_code, header.city);
+    if !city.trim().is_empty() {
+        rows.push(Row::Center(city.trim().to_string()));
+    }
+    if !header.tax_number.is_empty() {
+        rows.push(Row::Center(format!("St.-Nr.: {}", header.tax_number)));
+    }
+    if !header.vat_id.is_empty() {
+        rows.push(Row::Center(format!("USt-IdNr.: {}", header.vat_id)));
+    }
+    rows.push(Row::Rule);
+
+    for row in columns(
+        &format!("Beleg-Nr. {}", receipt.receipt_number),
+        &receipt.created_at,
+        width,
+    ) {
+        rows.push(Row::Text(row));
+    }
+    rows.push(Row::Text(format!("Kasse: {}", receipt.cash_register_id)));
+    if let Some(cashier) = &receipt.cashier {
+        rows.push(Row::Text(format!("Bediener: {}", cashier)));
+    }
+    if let Some(original) = receipt.cancels_receipt_number {
+        rows.push(Row::Bold(format!("STORNO zu Beleg-Nr. {}", original)));
+    }
+    if receipt.copy {
+        rows.push(Row::Bold("KOPIE".into()));
+    }
+    rows.push(Row::Rule);
+
+    for line in &receipt.lines {
+        let letter = line.vat_letter.as_deref().unwrap_or(" ");
+        for row in columns(
+            &format!("{} x {}", line.quantity, line.name),
+            &format!("{} {}", money(line.total_cents), letter),
+            width,
+        ) {
+            rows.push(Row::Text(row));
+        }
+        if line.quantity.abs() != 1 {
+            rows.push(Row::Text(format!(
+                "    à {} {}",
+                money(line.unit_price_cents),
+                receipt.currency
+            )));
+        }
+    }
+    rows.push(Row::Rule);
+    for row in columns(
+        "SUMME",
+        &format!("{} {}", receipt.currency, money(receipt.total_cents)),
+        width,
+    ) {
+        rows.push(Row::Bold(row));
+    }
+    for payment in &receipt.payments {
+        for row in columns(&payment.label, &money(payment.amount_cents), width) {
+            rows.push(Row::Text(row));
+        }
+    }
+
+    if !receipt.vat.is_empty() {
+        rows.push(Row::Rule);
+        let column = (width.saturating_sub(10)) / 3;
+        rows.push(Row::Text(format!(
+            "{:<10}{:>column$}{:>column$}{:>column$}",
+            "USt",
+            "Netto",
+            "Steuer",
+            "Brutto",
+            column = column
+        )));
+        for vat in &receipt.vat {
+            rows.push(Row::Text(format!(
+                "{:<10}{:>column$}{:>column$}{:>column$}",
+                format!("{} {}", vat.letter, percent(vat.rate_basis_points)),
+                money(vat.net_cents),
+                money(vat.tax_cents),
+                money(vat.gross_cents),
+                column = column
+            )));
+        }
+    }
+
+    if let Some(tse) = &receipt.tse {
+        rows.push(Row::Rule);
+        rows.push(Row::Bold("TSE".into()));
+        if let Some(error) = &tse.error {
+            for row in wrap(error, width) {
+                rows.push(Row::Text(row));
+            }
+        }
+        let fields = [
+            ("Seriennr.", tse.serial_number.clone()),
+            ("Transaktion", tse.transaction_number.map(|n| n.to_string())),
+            ("Sig.-Zähler", tse.signature_counter.map(|n| n.to_string())),
+            ("Start", tse.start_time.clone()),
+            ("Ende", tse.finish_time.clone()),
+            ("Signatur", tse.signature.clone()),
+        ];
+        for (label, value) in fields {
+            if let Some(value) = value {
+                for row in wrap(&format!("{}: {}", label, value), width) {
+                    rows.push(Row::Text(row));
+                }
+            }
+        }
+        if let (Some(number), Some(counter), Some(start), Some(finish), Some(sig)) = (
+            tse.transaction_number,
+            tse.signature_counter,
+            &tse.start_time,
+            &tse.finish_time,
+            &tse.signature,
+        ) {
+            rows.push(Row::Qr(format!(
+                "V0;{};{};{};{};{};{};{};{};{};{};{}",
+                receipt.cash_register_id,
+                tse.process_type,
+                tse.process_data,
+                number,
+                counter,
+                start,
+                finish,
+                tse.signature_algorithm.as_deref().unwrap_or_default(),
+                tse.time_format.as_deref().unwrap_or_default(),
+                sig,
+                tse.public_key.as_deref().unwrap_or_default()
+            )));
+        }
+    }
+    rows
+}
+
+pub(crate) fn line_width(paper_width_mm: u32) -> usize {
+    if paper_width_mm <= 58 {
+        32
+    } else {
+        48
+    }
+}
+
+pub(crate) fn render_text(receipt: &Receipt, width: usize) -> String {
+    let mut out = String::new();
+    for row in layout(receipt, width) {
+        let text = match row {
+            Row::Title(text) | Row::Center(text) => {
+                let len = text.chars().count();
+                format!("{}{}", " ".repeat(width.saturating_sub(len) / 2), text)
+            }
+            Row::Text(text) | Row::Bold(text) => text,
+            Row::Rule => "-".repeat(width),
+            Row::Qr(_) => continue,
+        };
+        out.push_str(text.trim_end());
+        out.push('\n');
+    }
+    out
+}
+
+const ESC: u8 = 0x1b;
+const GS: u8 = 0x1d;
+
+fn cp858(text: &str) -> Vec<u8> {
+    text.chars()
+        .map(|c| match c {
+            c if c.is_ascii() => c as u8,
+            'Ç' => 0x80,
+            'ü' => 0x81,
+            'é' => 0x82,
+            'â' => 0x83,
+            'ä' => 0x84,
+            'à' => 0x85,
+            'ç' => 0x87,
+            'ê' => 0x88,
+            'è' => 0x8a,
+            'Ä' => 0x8e,
+            'É' => 0x90,
+            'ô' => 0x93,
+            'ö' => 0x94,
+            'Ö' => 0x99,
+            'Ü' => 0x9a,
+            'ß' => 0xe1,
+            '€' => 0xd5,
+            _ => b'?',
+        })
+        .collect()
+}
+
+pub(crate) fn render_escpos(receipt: &Receipt, width: usize) -> Vec<u8> {
+    let mut out = vec![ESC, b'@', ESC, b't', 19];
+    for row in layout(receipt, width) {
+        match row {
+            Row::Title(text) => {
+                out.extend_from_slice(&[ESC, b'a', 1, ESC, b'E', 1, GS, b'!', 0x11]);
+                out.extend(cp858(&text));
+                out.extend_from_slice(&[b'\n', GS, b'!', 0, ESC, b'E', 0, ESC, b'a', 0]);
+            }
+            Row::Center(text) => {
+                out.extend_from_slice(&[ESC, b'a', 1]);
+                out.extend(cp858(&text));
+                out.extend_from_slice(&[b'\n', ESC, b'a', 0]);
+            }
+            Row::Text(text) => {
+                out.extend(cp858(&text));
+                out.push(b'\n');
+            }
+            Row::Bold(text) => {
+                out.extend_from_slice(&[ESC, b'E', 1]);
+                out.extend(cp858(&text));
+                out.extend_from_slice(&[b'\n', ESC, b'E', 0]);
+            }
+            Row::Rule => {
+                out.extend_from_slice("-".repeat(width).as_bytes());
+                out.push(b'\n');
+            }
+            Row::Qr(data) => {
+                let data = data.as_bytes();
+                let len = data.len() + 3;
+                out.extend_from_slice(&[ESC, b'a', 1]);
+                out.extend_from_slice(&[GS, b'(', b'k', 4, 0, 49, 65, 50, 0]);
+                out.extend_from_slice(&[GS, b'(', b'k', 3, 0, 49, 67, 4]);
+                out.extend_from_slice(&[GS, b'(', b'k', 3, 0, 49, 69, 48]);
+                out.extend_from_slice(&[
+                    GS,
+                    b'(',
+                    b'k',
+                    (len % 256) as u8,
+                    (len / 256) as u8,
+                    49,
+                    80,
+                    48,
+                ]);
+                out.extend_from_slice(data);
+                out.extend_from_slice(&[GS, b'(', b'k', 3, 0, 49, 81, 48]);
+                out.extend_from_slice(&[b'\n', ESC, b'a', 0]);
+            }
+        }
+    }
+    out.extend_from_slice(&[ESC, b'd', 4, GS, b'V', 66, 0]);
+    out
+}
+
+fn pdf_text(text: &str) -> Vec<u8> {
+    let mut out = Vec::new();
+    for c in text.chars() {
+        match c {
+            '(' | ')' | '\\' => {
+                out.push(b'\\');
+                out.push(c as u8);
+            }
+            '€' => out.push(0x80),
+            c if (c as u32) < 0x100 => out.push(c as u32 as u8),
+            _ => out.push(b'?'),
+        }
+    }
+    out
+}
+
+pub(crate) fn render_pdf(receipt: &Receipt, width: usize) -> Vec<u8> {
+    let text = render_text(receipt, width);
+    let lines: Vec<&str> = text.lines().collect();
+    let font_size = 8.0;
+    let leading = 10.0;
+    let margin = 14.0;
+    let page_width = width as f64 * font_size * 0.6 + 2.0 * margin;
+    let page_height = lines.len() as f64 * leading + 2.0 * margin;
+
+    let mut content = Vec::new();
+    content.extend_from_slice(
+        format!(
+            "BT /F1 {} Tf {} TL {} {} Td\n",
+            font_size,
+            leading,
+            margin,
+            page_height - margin - font_size
+        )
+        .as_bytes(),
+    );
+    for line in &lines {
+        content.push(b'(');
+        content.extend(pdf_text(line));
+        content.extend_from_slice(b") Tj T*\n");
+    }
+    content.extend_from_slice(b"ET\n");
+
+    let objects: Vec<Vec<u8>> = vec![
+        b"<< /Type /Catalog /Pages 2 0 R >>".to_vec(),
+        b"<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_vec(),
+        format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {:.2} {:.2}] /Resources << /Font << /F1 4 0 R >> >> /Contents 5 0 R >>",
+            page_width, page_height
+        )
+        .into_bytes(),
+        b"<< /Type /Font /Subtype /Type1 /BaseFont /Courier /Encoding /WinAnsiEncoding >>".to_vec(),
+        [
+            format!("<< /Length {} >>\nstream\n", content.len()).into_bytes(),
+            content,
+            b"\nendstream".to_vec(),
+        ]
+        .concat(),
+    ];
+
+    let mut pdf = b"%PDF-1.4\n".to_vec();
+    let mut offsets = Vec::with_capacity(objects.len());
+    for (index, object) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        pdf.extend_from_slice(format!("{} 0 obj\n", index + 1).as_bytes());
+        pdf.extend_from_slice(object);
+        pdf.extend_from_slice(b"\nendobj\n");
+    }
+    let xref = pdf.len();
+    pdf.extend_from_slice(
+        format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).as_bytes(),
+    );
+    for offset in offsets {
+        pdf.extend_from_slice(format!("{:010} 00000 n \n", offset).as_bytes());
+    }
+    pdf.extend_from_slice(
+        format!(
+            "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+            objects.len() + 1,
+            xref
+        )
+        .as_bytes(),
+    );
+    pdf
+}
+
+pub(crate) fn send(printer: &PrinterSettings, bytes: &[u8]) -> Result<(), String> {
+    let address = printer.address.trim();
+    if address.is_empty() {
+        return Err("Keine Druckeradresse konfiguriert.".into());
+    }
+    match printer.connection.as_str() {
+        "tcp" => {
+            let target = address
+                .to_socket_addrs()
+                .map_err(|e| e.to_string())?
+                .next()
+                .ok_or_else(|| format!("Drucker {} nicht gefunden", address))?;
+            let mut stream = TcpStream::connect_timeout(&target, Duration::from_secs(5))
+                .map_err(|e| format!("Drucker nicht erreichbar: {}", e))?;
+            stream
+                .set_write_timeout(Some(Duration::from_secs(10)))
+                .map_err(|e| e.to_string())?;
+            stream.write_all(bytes).map_err(|e| e.to_string())?;
+            stream.flush().map_err(|e| e.to_string())
+        }
+        "device" => {
+            let mut device = OpenOptions::new()
+                .write(true)
+                .open(Path::new(address))
+                .map_err(|e| format!("Drucker nicht erreichbar: {}", e))?;
+            device.write_all(bytes).map_err(|e| e.to_string())?;
+            device.flush().map_err(|e| e.to_string())
+        }
+        other => Err(format!("Unbekannte Druckerverbindung: {}", other)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{io::Read, net::TcpListener, thread};
+
+    fn sample() -> Receipt {
+        Receipt {
+            receipt_number: 17,
+            cash_register_id: "KASSE-1".into(),
+            created_at: "01.03.2025 10:15".into(),
+            cashier: Some("Admin".into()),
+            cancels_receipt_number: None,
+            copy: false,
+            currency: "EUR".into(),
+            header: ReceiptHeader {
+                name: "Boulderhalle".into(),
+                street: "Wandweg 1".into(),
+                postal_code: "12345".into(),
+                city: "Griffstadt".into(),
+                tax_number: "12/345/67890".into(),
+                vat_id: String::new(),
+            },
+            lines: vec![ReceiptLine {
+                name: "Eistee".into(),
+                quantity: 2,
+                unit_price_cents: 380,
+                total_cents: 760,
+                vat_letter: Some("A".into()),
+            }],
+            total_cents: 760,
+            vat: vec![ReceiptVat {
+                letter: "A".into(),
+                rate_basis_points: 1900,
+                net_cents: 639,
+                tax_cents: 121,
+                gross_cents: 760,
+            }],
+            payments: vec![ReceiptPayment {
+                label: "Bar".into(),
+                amount_cents: 760,
+            }],
+            tse: Some(ReceiptTse {
+                serial_number: Some("SIM-0001".into()),
+                transaction_number: Some(5),
+                signature_counter: Some(42),
+                start_time: Some("2025-03-01T09:15:00.000Z".into()),
+                finish_time: Some("2025-03-01T09:15:01.000Z".into()),
+                process_type: "Kassenbeleg-V1".into(),
+                process_data: "Beleg^7.60_0.00_0.00_0.00_0.00^7.60:Bar".into(),
+                signature: Some("c2lnbmF0dXJl".into()),
+                signature_algorithm: Some("ecdsa-plain-SHA256".into()),
+                time_format: Some("utcTime".into()),
+                public_key: Some("cHVibGlj".into()),
+                error: None,
+            }),
+        }
+    }
+
+    fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+        haystack
+            .windows(needle.len())
+            .any(|window| window == needle)
+    }
+
+    #[test]
+    fn prints_to_network_printer() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let printer = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut received = Vec::new();
+            stream.read_to_end(&mut received).unwrap();
+            received
+        });
+
+        let bytes = render_escpos(&sample(), line_width(80));
+        let settings = PrinterSettings {
+            enabled: true,
+            connection: "tcp".into(),
+            address,
+            paper_width_mm: 80,
+        };
+        send(&settings, &bytes).unwrap();
+        let received = printer.join().unwrap();
+
+        assert_eq!(received, bytes);
+        assert!(received.starts_with(&[ESC, b'@', ESC, b't', 19]));
+        assert!(received.ends_with(&[GS, b'V', 66, 0]));
+        assert!(contains(&received, b"USt"));
+        assert!(contains(&received, b"A 19,00%"));
+        assert!(contains(&received, b"6,39"));
+        assert!(contains(&received, b"1,21"));
+        assert!(contains(&received, b"Seriennr.: SIM-0001"));
+        assert!(contains(&received, b"Sig.-Z\x84hler: 42"));
+        assert!(contains(&received, b"Signatur: c2lnbmF0dXJl"));
+        assert!(contains(&received, &[GS, b'(', b'k']));
+    }
+
+    #[test]
+    fn unreachable_printer_is_reported() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        drop(listener);
+        let settings = PrinterSettings {
+            enabled: true,
+            connection: "tcp".into(),
+            address,
+            paper_width_mm: 80,
+        };
+        let error = send(&settings, b"test").unwrap_err();
+        assert!(error.starts_with("Drucker nicht erreichbar"));
+    }
+}
@@ -0,0 +1,707 @@
+use rusqlite::{params, OptionalExtension, Transaction};
+use serde::Serialize;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::SepaSettings;
+
+const PAIN_008_NAMESPACE: &str = "urn:iso:std:iso:20022:tech:xsd:pain.008.001.02";
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SepaExportRecord {
+    pub path: String,
+    pub message_id: String,
+    pub collection_date: String,
+    pub invoice_count: i64,
+    pub total_cents: i64,
+    pub skipped_count: i64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SepaReturnRecord {
+    pub returned_count: i64,
+    pub returned_cents: i64,
+    pub unknown_references: Vec<String>,
+}
+
+struct Debit {
+    invoice_id: i64,
+    end_to_end_id: String,
+    amount_cents: i64,
+    mandate_reference: String,
+    mandate_signed_on: String,
+    debtor_name: String,
+    iban: String,
+    bic: Option<String>,
+    remittance: String,
+    sequence_type: &'static str,
+}
+
+pub fn normalize_iban(value: &str) -> Result<String, String> {
+    let iban: String = value
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>()
+        .to_uppercase();
+    // Checked for ASCII first so the byte slices below stay on char boundaries.
+    let valid_shape = iban.chars().all(|c| c.is_ascii_alphanumeric())
+        && (15..=34).contains(&iban.len())
+        && iban[..2].chars().all(|c| c.is_ascii_alphabetic())
+        && iban[2..4].chars().all(|c| c.is_ascii_digit());
+    if !valid_shape || mod97(&format!("{}{}", &iban[4..], &iban[..4])) != 1 {
+        return Err(format!("IBAN {} ist ungültig", value.trim()));
+    }
+    Ok(iban)
+}
+
+pub fn normalize_bic(value: &str) -> Result<String, String> {
+    let bic = value.trim().to_uppercase();
+    let valid = bic.chars().all(|c| c.is_ascii_alphanumeric())
+        && matches!(bic.len(), 8 | 11)
+        && bic[..6].chars().all(|c| c.is_ascii_alphabetic());
+    if !valid {
+        return Err(format!("BIC {} ist ungültig", value.trim()));
+    }
+    Ok(bic)
+}
+
+pub fn normalize_creditor_id(value: &str) -> Result<String, String> {
+    let id: String = value
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>()
+        .to_uppercase();
+    let valid_shape = id.chars().all(|c| c.is_ascii_alphanumeric())
+        && (8..=35).contains(&id.len())
+        && id[..2].chars().all(|c| c.is_ascii_alphabetic())
+        && id[2..4].chars().all(|c| c.is_ascii_digit());
+    // The business code at positions 5-7 is not part of the check digits.
+    if !valid_shape || mod97(&format!("{}{}", &id[7..], &id[..4])) != 1 {
+        return Err(format!("Gläubiger-ID {} ist ungültig", value.trim()));
+    }
+    Ok(id)
+}
+
+pub fn validate_mandate_reference(value: &str) -> Result<String, String> {
+    let reference = value.trim();
+    let valid = !reference.is_empty()
+        && reference.len() <= 35
+        && reference
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "+?/-:().,' ".contains(c));
+    if !valid {
+        return Err(
+            "Mandatsreferenz muss 1-35 Zeichen lang sein und darf nur A-Z, 0-9 und +?/-:().,' enthalten"
+                .into(),
+        );
+    }
+    Ok(reference.to_string())
+}
+
+pub fn export(
+    tx: &Transaction,
+    settings: &SepaSettings,
+    target_dir: &Path,
+    collection_date: &str,
+    user_id: i64,
+) -> Result<SepaExportRecord, String> {
+    if settings.creditor_name.trim().is_empty()
+        || settings.creditor_id.trim().is_empty()
+        || settings.iban.trim().is_empty()
+    {
+        return Err("SEPA-Gläubigerdaten in den Einstellungen hinterlegen.".into());
+    }
+    let creditor_id = normalize_creditor_id(&settings.creditor_id)?;
+    let creditor_iban = normalize_iban(&settings.iban)?;
+    let creditor_bic = match settings.bic.trim() {
+        "" => None,
+        bic => Some(normalize_bic(bic)?),
+    };
+    if !target_dir.is_dir() {
+        return Err(format!(
+            "Zielordner {} existiert nicht",
+            target_dir.display()
+        ));
+    }
+    let valid_date: bool = tx
+        .query_row(
+            "SELECT DATE(?1) IS ?1 AND DATE(?1) > DATE('now', 'localtime')",
+            [collection_date],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    if !valid_date {
+        return Err("Fälligkeitsdatum muss ein Datum (JJJJ-MM-TT) in der Zukunft sein.".into());
+    }
+
+    let debits = load_debits(tx).map_err(|e| e.to_string())?;
+    let skipped_count: i64 = tx
+        .query_row(
+            "SELECT COUNT(*) FROM contract_invoices ci
+            JOIN members m ON m.id = ci.member_id
+            WHERE ci.status = 'open'
+              AND (m.iban IS NULL OR m.debit_returned_at IS NOT NULL)",
+            [],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    if debits.is_empty() {
+        return Err("Keine offenen Lastschriften zum Exportieren.".into());
+    }
+
+    let (created_at, stamp): (String, String) = tx
+        .query_row(
+            "SELECT strftime('%Y-%m-%dT%H:%M:%S', 'now', 'localtime'),
+                    strftime('%Y%m%d%H%M%S', 'now', 'localtime')",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .map_err(|e| e.to_string())?;
+    let invoice_count = debits.len() as i64;
+    let total_cents: i64 = debits.iter().map(|debit| debit.amount_cents).sum();
+    tx.execute(
+        "INSERT INTO sepa_exports (message_id, collection_date, invoice_count, total_cents, created_by)
+        VALUES ('', ?1, ?2, ?3, ?4)",
+        params![collection_date, invoice_count, total_cents, user_id],
+    )
+    .map_err(|e| e.to_string())?;
+    let export_id = tx.last_insert_rowid();
+    let message_id = format!("BPOS-{}-{}", export_id, stamp);
+
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str(&format!(
+        "<Document xmlns=\"{}\" xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\">\n",
+        PAIN_008_NAMESPACE
+    ));
+    xml.push_str("  <CstmrDrctDbtInitn>\n");
+    xml.push_str("    <GrpHdr>\n");
+    xml.push_str(&element(6, "MsgId", &message_id));
+    xml.push_str(&element(6, "CreDtTm", &created_at));
+    xml.push_str(&element(6, "NbOfTxs", &invoice_count.to_string()));
+    xml.push_str(&element(6, "CtrlSum", &decimal(total_cents)));
+    xml.push_str("      <InitgPty>\n");
+    xml.push_str(&element(8, "Nm", &sepa_text(&settings.creditor_name, 70)));
+    xml.push_str("      </InitgPty>\n");
+    xml.push_str("    </GrpHdr>\n");
+
+    for sequence_type in ["FRST", "RCUR"] {
+        let batch: Vec<&Debit> = debits
+            .iter()
+            .filter(|debit| debit.sequence_type == sequence_type)
+            .collect();
+        if batch.is_empty() {
+            continue;
+        }
+        let batch_cents: i64 = batch.iter().map(|debit| debit.amount_cents).sum();
+        xml.push_str("    <PmtInf>\n");
+        xml.push_str(&element(
+            6,
+            "PmtInfId",
+            &format!("{}-{}", message_id, sequence_type),
+        ));
+        xml.push_str(&element(6, "PmtMtd", "DD"));
+        xml.push_str(&element(6, "BtchBookg", "true"));
+        xml.push_str(&element(6, "NbOfTxs", &batch.len().to_string()));
+        xml.push_str(&element(6, "CtrlSum", &decimal(batch_cents)));
+        xml.push_str("      <PmtTpInf>\n");
+        xml.push_str("        <SvcLvl>\n");
+        xml.push_str(&element(10, "Cd", "SEPA"));
+        xml.push_str("        </SvcLvl>\n");
+        xml.push_str("        <LclInstrm>\n");
+        xml.push_str(&element(10, "Cd", "CORE"));
+        xml.push_str("        </LclInstrm>\n");
+        xml.push_str(&element(8, "SeqTp", sequence_type));
+        xml.push_str("      </PmtTpInf>\n");
+        xml.push_str(&element(6, "ReqdColltnDt", collection_date));
+        xml.push_str("      <Cdtr>\n");
+        xml.push_str(&element(8, "Nm", &sepa_text(&settings.creditor_name, 70)));
+        xml.push_str("      </Cdtr>\n");
+        xml.push_str("      <CdtrAcct>\n        <Id>\n");
+        xml.push_str(&element(10, "IBAN", &creditor_iban));
+        xml.push_str("        </Id>\n      </CdtrAcct>\n");
+        xml.push_str(&agent(6, "CdtrAgt", creditor_bic.as_deref()));
+        xml.push_str(&element(6, "ChrgBr", "SLEV"));
+        xml.push_str("      <CdtrSchmeId>\n        <Id>\n          <PrvtId>\n            <Othr>\n");
+        xml.push_str(&element(14, "Id", &creditor_id));
+        xml.push_str("              <SchmeNm>\n");
+        xml.push_str(&element(16, "Prtry", "SEPA"));
+        xml.push_str("              </SchmeNm>\n");
+        xml.push_str(
+            "            </Othr>\n          </PrvtId>\n        </Id>\n      </CdtrSchmeId>\n",
+        );
+        for debit in batch {
+            xml.push_str("      <DrctDbtTxInf>\n");
+            xml.push_str("        <PmtId>\n");
+            xml.push_str(&element(10, "EndToEndId", &debit.end_to_end_id));
+            xml.push_str("        </PmtId>\n");
+            xml.push_str(&format!(
+                "        <InstdAmt Ccy=\"EUR\">{}</InstdAmt>\n",
+                decimal(debit.amount_cents)
+            ));
+            xml.push_str("        <DrctDbtTx>\n          <MndtRltdInf>\n");
+            xml.push_str(&element(12, "MndtId", &debit.mandate_reference));
+            xml.push_str(&element(12, "DtOfSgntr", &debit.mandate_signed_on));
+            xml.push_str("          </MndtRltdInf>\n        </DrctDbtTx>\n");
+            xml.push_str(&agent(8, "DbtrAgt", debit.bic.as_deref()));
+            xml.push_str("        <Dbtr>\n");
+            xml.push_str(&element(10, "Nm", &sepa_text(&debit.debtor_name, 70)));
+            xml.push_str("        </Dbtr>\n");
+            xml.push_str("        <DbtrAcct>\n          <Id>\n");
+            xml.push_str(&element(12, "IBAN", &debit.iban));
+            xml.push_str("          </Id>\n        </DbtrAcct>\n");
+            xml.push_str("        <RmtInf>\n");
+            xml.push_str(&element(10, "Ustrd", &sepa_text(&debit.remittance, 140)));
+            xml.push_str("        </RmtInf>\n");
+            xml.push_str("      </DrctDbtTxInf>\n");
+        }
+        xml.push_str("    </PmtInf>\n");
+    }
+    xml.push_str("  </CstmrDrctDbtInitn>\n</Document>\n");
+
+    let path = target_dir.join(format!("sepa-lastschrift-{}.xml", stamp));
+    tx.execute(
+        "UPDATE sepa_exports SET message_id = ?1, file_path = ?2 WHERE id = ?3",
+        params![message_id, path.to_string_lossy(), export_id],
+    )
+    .map_err(|e| e.to_string())?;
+    for debit in &debits {
+        tx.execute(
+            "UPDATE contract_invoices
+            SET status = 'exported', sequence_type = ?1, sepa_export_id = ?2
+            WHERE id = ?3",
+            params![debit.sequence_type, export_id, debit.invoice_id],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    // Only the partial file is written here; the caller renames it once the
+    // invoices are committed as exported, so a failed commit leaves no file
+    // that the bank could receive a second time.
+    fs::write(partial_path(&path), xml)
+        .map_err(|e| format!("Lastschriftdatei konnte nicht geschrieben werden: {}", e))?;
+
+    Ok(SepaExportRecord {
+        path: path.to_string_lossy().to_string(),
+        message_id,
+        collection_date: collection_date.to_string(),
+        invoice_count,
+        total_cents,
+        skipped_count,
+    })
+}
+
+fn partial_path(path: &Path) -> PathBuf {
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".part");
+    PathBuf::from(partial)
+}
+
+pub fn finish_export(record: &SepaExportRecord) -> Result<(), String> {
+    let path = Path::new(&record.path);
+    fs::rename(partial_path(path), path)
+        .map_err(|e| format!("Lastschriftdatei konnte nicht fertiggestellt werden: {}", e))
+}
+
+pub fn discard_export(record: &SepaExportRecord) {
+    let _ = fs::remove_file(partial_path(Path::new(&record.path)));
+}
+
+pub fn import_returns(tx: &Transaction, file_path: &Path) -> Result<SepaReturnRecord, String> {
+    let content = fs::read_to_string(file_path)
+        .map_err(|e| format!("Rückgabedatei konnte nicht gelesen werden: {}", e))?;
+    let entries = parse_returns(&content);
+    if entries.is_empty() {
+        return Err("Datei enthält keine Rücklastschriften.".into());
+    }
+
+    let mut record = SepaReturnRecord {
+        returned_count: 0,
+        returned_cents: 0,
+        unknown_references: Vec::new(),
+    };
+    for (end_to_end_id, reason) in entries {
+        let invoice: Option<(i64, i64, i64, String)> = tx
+            .query_row(
+                "SELECT id, member_id, amount_cents, status FROM contract_invoices WHERE end_to_end_id = ?",
+                [&end_to_end_id],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+            )
+            .optional()
+            .map_err(|e| e.to_string())?;
+        let Some((invoice_id, member_id, amount_cents, status)) = invoice else {
+            record.unknown_references.push(end_to_end_id);
+            continue;
+        };
+        if status == "returned" {
+            continue;
+        }
+        tx.execute(
+            "UPDATE contract_invoices
+            SET status = 'returned', return_reason = ?1, returned_at = CURRENT_TIMESTAMP
+            WHERE id = ?2",
+            params![reason, invoice_id],
+        )
+        .map_err(|e| e.to_string())?;
+        tx.execute(
+            "UPDATE members
+            SET debit_returned_at = CURRENT_TIMESTAMP, updated_at = CURRENT_TIMESTAMP
+            WHERE id = ?",
+            [member_id],
+        )
+        .map_err(|e| e.to_string())?;
+        record.returned_count += 1;
+        record.returned_cents += amount_cents;
+    }
+    Ok(record)
+}
+
+fn load_debits(tx: &Transaction) -> rusqlite::Result<Vec<Debit>> {
+    let mut stmt = tx.prepare(
+        "
+        SELECT ci.id,
+               ci.end_to_end_id,
+               ci.amount_cents,
+               c.mandate_reference,
+               c.mandate_signed_on,
+               m.first_name || ' ' || m.last_name,
+               m.iban,
+               m.bic,
+               ms.membership_type || ' ' || ci.period_start || ' - ' || ci.period_end,
+               EXISTS (
+                   SELECT 1 FROM contract_invoices earlier
+                   WHERE earlier.contract_id = ci.contract_id
+                     AND (
+                         earlier.status IN ('exported', 'paid')
+                         OR (earlier.status = 'open' AND earlier.id < ci.id)
+                     )
+               )
+        FROM contract_invoices ci
+        JOIN membership_contracts c ON c.id = ci.contract_id
+        JOIN members m ON m.id = ci.member_id
+        JOIN memberships ms ON ms.id = c.membership_id
+        WHERE ci.status = 'open'
+          AND m.iban IS NOT NULL
+          AND m.debit_returned_at IS NULL
+        ORDER BY ci.due_date ASC, ci.id ASC
+        ",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        let recurring: bool = row.get(9)?;
+        Ok(Debit {
+            invoice_id: row.get(0)?,
+            end_to_end_id: row.get(1)?,
+            amount_cents: row.get(2)?,
+            mandate_reference: row.get(3)?,
+            mandate_signed_on: row.get(4)?,
+            debtor_name: row.get(5)?,
+            iban: row.get(6)?,
+            bic: row.get(7)?,
+            remittance: row.get(8)?,
+            sequence_type: if recurring { "RCUR" } else { "FRST" },
+        })
+    })?;
+    rows.collect()
+}
+
+/// Extracts end-to-end references and reason codes from pain.002 status reports
+/// and camt.054 notifications.
+fn parse_returns(xml: &str) -> Vec<(String, Option<String>)> {
+    let mut entries = Vec::new();
+    for block_tag in ["TxInfAndSts", "TxDtls"] {
+        for block in blocks(xml, block_tag) {
+            let rejected = blocks(block, "RtrInf").next().is_some()
+                || blocks(block, "StsRsnInf").next().is_some()
+                || blocks(block, "TxSts").any(|status| status.trim() == "RJCT");
+            if !rejected {
+                continue;
+            }
+            let reference = blocks(block, "OrgnlEndToEndId")
+                .next()
+                .or_else(|| blocks(block, "EndToEndId").next())
+                .map(|value| value.trim().to_string());
+            let reason = blocks(block, "Rsn")
+                .next()
+                .and_then(|reason| blocks(reason, "Cd").next())
+                .map(|value| value.trim().to_string());
+            if let Some(reference) = reference.filter(|value| !value.is_empty()) {
+                entries.push((reference, reason));
+            }
+        }
+    }
+    entries
+}
+
+/// Contents of all elements with the given local name, with or without a
+/// namespace prefix. Nested elements of the same name are not supported.
+fn blocks<'a>(xml: &'a str, tag: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+    let mut rest = xml;
+    std::iter::from_fn(move || loop {
+        let start = rest.find('<')?;
+        let after = &rest[start + 1..];
+        let name_len = after
+            .find(|c: char| c == '>' || c == '/' || c.is_whitespace())
+            .unwrap_or(after.len());
+        let name = &after[..name_len];
+        let local = name.rsplit(':').next().unwrap_or(name);
+        if name.is_empty() || local != tag {
+            rest = after;
+            continue;
+        }
+        let open_end = after.find('>')?;
+        if after[..open_end].ends_with('/') {
+            rest = &after[open_end + 1..];
+            return Some("");
+        }
+        let content = &after[open_end + 1..];
+        let close = format!("</{}>", name);
+        let end = content.find(&close)?;
+        rest = &content[end + close.len()..];
+        return Some(&content[..end]);
+    })
+}
+
+fn mod97(value: &str) -> u32 {
+    value.chars().fold(0, |acc, c| {
+        let digits = c.to_digit(36).unwrap_or(0);
+        if digits >= 10 {
+            (acc * 100 + digits) % 97
+        } else {
+            (acc * 10 + digits) % 97
+        }
+    })
+}
+
+fn decimal(cents: i64) -> String {
+    format!("{}.{:02}", cents / 100, cents % 100)
+}
+
+fn element(indent: usize, tag: &str, value: &str) -> String {
+    format!("{}<{}>{}</{}>\n", " ".repeat(indent), tag, value, tag)
+}
+
+fn agent(indent: usize, tag: &str, bic: Option<&str>) -> String {
+    let pad = " ".repeat(indent);
+    let institution = match bic {
+        Some(bic) => element(indent + 4, "BIC", bic),
+        None => format!(
+            "{}<Othr>\n{}{}</Othr>\n",
+            " ".repeat(indent + 4),
+            element(indent + 6, "Id", "NOTPROVIDED"),
+            " ".repeat(indent + 4)
+        ),
+    };
+    format!("{pad}<{tag}>\n{pad}  <FinInstnId>\n{institution}{pad}  </FinInstnId>\n{pad}</{tag}>\n")
+}
+
+/// Reduces free text to the SEPA character set, transliterating German umlauts.
+fn sepa_text(value: &str, max_len: usize) -> String {
+    let mut text = String::new();
+    for c in value.trim().chars() {
+        match c {
+            'ä' => text.push_str("ae"),
+            'ö' => text.push_str("oe"),
+            'ü' => text.push_str("ue"),
+            'Ä' => text.push_str("Ae"),
+            'Ö' => text.push_str("Oe"),
+            'Ü' => text.push_str("Ue"),
+            'ß' => text.push_str("ss"),
+            '&' => text.push('+'),
+            c if c.is_ascii_alphanumeric() || "/-?:().,'+ ".contains(c) => text.push(c),
+            _ => text.push(' '),
+        }
+    }
+    text.chars().take(max_len).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_valid_identifiers() {
+        assert_eq!(
+            normalize_iban("de02 1203 0000 0000 2020 51").unwrap(),
+            "DE02120300000000202051"
+        );
+        assert_eq!(normalize_bic(" byladem1001 ").unwrap(), "BYLADEM1001");
+        assert_eq!(
+            normalize_creditor_id("DE98 ZZZ 09999999999").unwrap(),
+            "DE98ZZZ09999999999"
+        );
+    }
+
+    #[test]
+    fn rejects_non_ascii_input_without_panicking() {
+        assert!(normalize_iban("Aä3456789012345").is_err());
+        assert!(normalize_iban("DE0ä120300000000202051").is_err());
+        assert!(normalize_bic("BYLADäM1").is_err());
+        assert!(normalize_bic("BYLADEM€01").is_err());
+        assert!(normalize_creditor_id("Dä98ZZZ0999999").is_err());
+    }
+
+    #[test]
+    fn parses_pain_002_rejections() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Document xmlns="urn:iso:std:iso:20022:tech:xsd:pain.002.001.03">
+  <CstmrPmtStsRpt>
+    <GrpHdr><MsgId>STATUS-1</MsgId><CreDtTm>2026-03-03T08:00:00</CreDtTm></GrpHdr>
+    <OrgnlGrpInfAndSts>
+      <OrgnlMsgId>BPOS-1-20260301120000</OrgnlMsgId>
+      <OrgnlMsgNmId>pain.008.001.02</OrgnlMsgNmId>
+      <GrpSts>PART</GrpSts>
+    </OrgnlGrpInfAndSts>
+    <OrgnlPmtInfAndSts>
+      <OrgnlPmtInfId>BPOS-1-20260301120000-FRST</OrgnlPmtInfId>
+      <TxInfAndSts>
+        <StsId>1</StsId>
+        <OrgnlEndToEndId>BPOS-R1</OrgnlEndToEndId>
+        <TxSts>RJCT</TxSts>
+        <StsRsnInf>
+          <Orgtr><Nm>Bank</Nm></Orgtr>
+          <Rsn><Cd>AC04</Cd></Rsn>
+        </StsRsnInf>
+      </TxInfAndSts>
+      <TxInfAndSts>
+        <StsId>2</StsId>
+        <OrgnlEndToEndId>BPOS-R2</OrgnlEndToEndId>
+        <TxSts>ACCP</TxSts>
+      </TxInfAndSts>
+      <TxInfAndSts>
+        <StsId>3</StsId>
+        <OrgnlEndToEndId>BPOS-R3</OrgnlEndToEndId>
+        <TxSts>RJCT</TxSts>
+      </TxInfAndSts>
+    </OrgnlPmtInfAndSts>
+  </CstmrPmtStsRpt>
+</Document>"#;
+        assert_eq!(
+            parse_returns(xml),
+            vec![
+                ("BPOS-R1".to_string(), Some("AC04".to_string())),
+                ("BPOS-R3".to_string(), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_camt_054_returns_with_prefixed_tags() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<camt:Document xmlns:camt="urn:iso:std:iso:20022:tech:xsd:camt.054.001.02">
+  <camt:BkToCstmrDbtCdtNtfctn>
+    <camt:Ntfctn>
+      <camt:Ntry>
+        <camt:Amt Ccy="EUR">45.00</camt:Amt>
+        <camt:CdtDbtInd>DBIT</camt:CdtDbtInd>
+        <camt:NtryDtls>
+          <camt:TxDtls>
+            <camt:Refs>
+              <camt:MsgId>BPOS-1-20260301120000</camt:MsgId>
+              <camt:EndToEndId>BPOS-R7</camt:EndToEndId>
+              <camt:MndtId>M-7</camt:MndtId>
+            </camt:Refs>
+            <camt:RtrInf>
+              <camt:OrgnlBkTxCd><camt:Prtry><camt:Cd>NRTI+109</camt:Cd></camt:Prtry></camt:OrgnlBkTxCd>
+              <camt:Rsn><camt:Cd>MD06</camt:Cd></camt:Rsn>
+              <camt:AddtlInf>Rueckgabe</camt:AddtlInf>
+            </camt:RtrInf>
+          </camt:TxDtls>
+          <camt:TxDtls>
+            <camt:Refs><camt:EndToEndId>BPOS-R8</camt:EndToEndId></camt:Refs>
+            <camt:RtrInf><camt:Rsn><camt:Prtry>SONST</camt:Prtry></camt:Rsn></camt:RtrInf>
+          </camt:TxDtls>
+          <camt:TxDtls>
+            <camt:Refs><camt:EndToEndId>BPOS-R9</camt:EndToEndId></camt:Refs>
+          </camt:TxDtls>
+        </camt:NtryDtls>
+      </camt:Ntry>
+    </camt:Ntfctn>
+  </camt:BkToCstmrDbtCdtNtfctn>
+</camt:Document>"#;
+        assert_eq!(
+            parse_returns(xml),
+            vec![
+                ("BPOS-R7".to_string(), Some("MD06".to_string())),
+                ("BPOS-R8".to_string(), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn exports_first_and_recurring_collections() {
+        let mut conn = rusqlite::Connection::open_in_memory().unwrap();
+        crate::migrations::run(&mut conn, Path::new(":memory:"), Path::new("")).unwrap();
+        conn.execute_batch(
+            "
+            INSERT INTO user_roles (id, name) VALUES (1, 'admin');
+            INSERT INTO users (id, username, display_name, password_hash, role_id)
+            VALUES (1, 'admin', 'Admin', '', 1);
+            INSERT INTO members (id, first_name, last_name, iban)
+            VALUES (1, 'Erika', 'Müller', 'DE02120300000000202051'),
+                   (2, 'Max', 'Mustermann', 'DE02500105170137075030');
+            INSERT INTO memberships (id, membership_type, price_cents) VALUES (1, 'Monatsbeitrag', 4500);
+            INSERT INTO membership_contracts
+                (id, member_id, membership_id, amount_cents, interval_months,
+                 mandate_reference, mandate_signed_on, start_date, next_due_date)
+            VALUES (1, 1, 1, 4500, 1, 'M-1', '2026-01-01', '2026-01-01', '2026-03-01'),
+                   (2, 2, 1, 4500, 1, 'M-2', '2026-01-01', '2026-01-01', '2026-02-01');
+            INSERT INTO contract_invoices
+                (id, contract_id, member_id, period_start, period_end, due_date, amount_cents, end_to_end_id)
+            VALUES (1, 1, 1, '2026-01-01', '2026-01-31', '2026-01-01', 4500, 'BPOS-R1'),
+                   (2, 1, 1, '2026-02-01', '2026-02-28', '2026-02-01', 4500, 'BPOS-R2'),
+                   (3, 2, 2, '2026-01-01', '2026-01-31', '2026-01-01', 4500, 'BPOS-R3');
+            ",
+        )
+        .unwrap();
+        let settings = SepaSettings {
+            creditor_name: "Boulderhalle".into(),
+            creditor_id: "DE98ZZZ09999999999".into(),
+            iban: "DE02120300000000202051".into(),
+            bic: String::new(),
+        };
+        let dir = std::env::temp_dir().join(format!("sepa-export-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let export_once = |conn: &mut rusqlite::Connection| {
+            let tx = conn.transaction().unwrap();
+            let record = export(&tx, &settings, &dir, "2999-01-02", 1).unwrap();
+            tx.commit().unwrap();
+            let xml = fs::read_to_string(partial_path(Path::new(&record.path))).unwrap();
+            discard_export(&record);
+            blocks(&xml, "PmtInf")
+                .map(|batch| {
+                    let sequence = blocks(batch, "SeqTp").next().unwrap().to_string();
+                    let references: Vec<String> =
+                        blocks(batch, "EndToEndId").map(str::to_string).collect();
+                    (sequence, references)
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            export_once(&mut conn),
+            vec![
+                (
+                    "FRST".to_string(),
+                    vec!["BPOS-R1".to_string(), "BPOS-R3".to_string()]
+                ),
+                ("RCUR".to_string(), vec!["BPOS-R2".to_string()]),
+            ]
+        );
+        conn.execute(
+            "INSERT INTO contract_invoices
+                (contract_id, member_id, period_start, period_end, due_date, amount_cents, end_to_end_id)
+            VALUES (2, 2, '2026-02-01', '2026-02-28', '2026-02-01', 4500, 'BPOS-R4')",
+            [],
+        )
+        .unwrap();
+        assert_eq!(
+            export_once(&mut conn),
+            vec![("RCUR".to_string(), vec!["BPOS-R4".to_string()])]
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
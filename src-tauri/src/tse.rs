@@ -0,0 +1,493 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use p256::ecdsa::{signature::Signer, Signature, SigningKey};
+use rand_core::OsRng;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::calendar;
+
+pub const PROCESS_TYPE_RECEIPT: &str = "Kassenbeleg-V1";
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TseInfo {
+    pub provider: String,
+    pub serial_number: String,
+    pub signature_algorithm: String,
+    pub time_format: String,
+    pub public_key: String,
+    pub signature_counter: u64,
+}
+
+pub struct TseStart {
+    pub transaction_number: u64,
+    pub start_time: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TseSignature {
+    pub transaction_number: u64,
+    pub signature_counter: u64,
+    pub start_time: String,
+    pub finish_time: String,
+    pub process_type: String,
+    pub process_data: String,
+    pub signature: String,
+}
+
+pub trait Tse: Send + Sync {
+    fn info(&self) -> Result<TseInfo, String>;
+
+    fn start_transaction(
+        &self,
+        client_id: &str,
+        process_type: &str,
+        process_data: &str,
+    ) -> Result<TseStart, String>;
+
+    fn update_transaction(
+        &self,
+        client_id: &str,
+        transaction_number: u64,
+        process_type: &str,
+        process_data: &str,
+    ) -> Result<(), String>;
+
+    fn finish_transaction(
+        &self,
+        client_id: &str,
+        transaction_number: u64,
+        process_type: &str,
+        process_data: &str,
+    ) -> Result<TseSignature, String>;
+
+    fn export(&self, target_dir: &Path) -> Result<PathBuf, String>;
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SimulatorState {
+    secret_key: String,
+    signature_counter: u64,
+    transaction_number: u64,
+}
+
+struct OpenTransaction {
+    client_id: String,
+    start_time: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SimulatorLogEntry<'a> {
+    operation: &'a str,
+    client_id: &'a str,
+    transaction_number: u64,
+    signature_counter: u64,
+    log_time: &'a str,
+    process_type: &'a str,
+    process_data: &'a str,
+    signature: &'a str,
+}
+
+struct SimulatorInner {
+    state: SimulatorState,
+    open: HashMap<u64, OpenTransaction>,
+}
+
+pub struct SimulatorTse {
+    state_path: PathBuf,
+    log_path: PathBuf,
+    key: SigningKey,
+    serial_number: String,
+    public_key: String,
+    inner: Mutex<SimulatorInner>,
+}
+
+impl SimulatorTse {
+    pub fn open(dir: &Path) -> Result<Self, String> {
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        let state_path = dir.join("tse-simulator.json");
+        let log_path = dir.join("tse-simulator.log");
+        let state = if state_path.exists() {
+            let raw = fs::read_to_string(&state_path).map_err(|e| e.to_string())?;
+            serde_json::from_str(&raw).map_err(|e| e.to_string())?
+        } else {
+            let key = SigningKey::random(&mut OsRng);
+            let state = SimulatorState {
+                secret_key: STANDARD.encode(key.to_bytes()),
+                signature_counter: 0,
+                transaction_number: 0,
+            };
+            write_state(&state_path, &state)?;
+            state
+        };
+
+        let secret = STANDARD
+            .decode(&state.secret_key)
+            .map_err(|e| e.to_string())?;
+        let key = SigningKey::from_slice(&secret).map_err(|e| e.to_string())?;
+        let public_key_bytes = key.verifying_key().to_encoded_point(false);
+        let serial_number = Sha256::digest(public_key_bytes.as_bytes())
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+
+        Ok(Self {
+            state_path,
+            log_path,
+            public_key: STANDARD.encode(public_key_bytes.as_bytes()),
+            serial_number,
+            key,
+            inner: Mutex::new(SimulatorInner {
+                state,
+                open: HashMap::new(),
+            }),
+        })
+    }
+
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, SimulatorInner>, String> {
+        self.inner
+            .lock()
+            .map_err(|_| "TSE ist nicht verfügbar".to_string())
+    }
+
+    fn sign(
+        &self,
+        inner: &mut SimulatorInner,
+        operation: &str,
+        client_id: &str,
+        transaction_number: u64,
+        process_type: &str,
+        process_data: &str,
+    ) -> Result<(u64, String, String), String> {
+        let signature_counter = inner.state.signature_counter + 1;
+        let log_time = utc_now();
+        let message = format!(
+            "{}|{}|{}|{}|{}|{}|{}|{}",
+            self.serial_number,
+            operation,
+            client_id,
+            transaction_number,
+            signature_counter,
+            log_time,
+            process_type,
+            process_data
+        );
+        let signature: Signature = self.key.sign(message.as_bytes());
+        let signature = STANDARD.encode(signature.to_bytes());
+
+        inner.state.signature_counter = signature_counter;
+        write_state(&self.state_path, &inner.state)?;
+        let entry = SimulatorLogEntry {
+            operation,
+            client_id,
+            transaction_number,
+            signature_counter,
+            log_time: &log_time,
+            process_type,
+            process_data,
+            signature: &signature,
+        };
+        let line = serde_json::to_string(&entry).map_err(|e| e.to_string())?;
+        let mut log = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.log_path)
+            .map_err(|e| e.to_string())?;
+        writeln!(log, "{}", line).map_err(|e| e.to_string())?;
+
+        Ok((signature_counter, log_time, signature))
+    }
+}
+
+impl Tse for SimulatorTse {
+    fn info(&self) -> Result<TseInfo, String> {
+        let inner = self.lock()?;
+        Ok(TseInfo {
+            provider: "simulator".into(),
+            serial_number: self.serial_number.clone(),
+            signature_algorithm: "ecdsa-plain-SHA256".into(),
+            time_format: "utcTime".into(),
+            public_key: self.public_key.clone(),
+            signature_counter: inner.state.signature_counter,
+        })
+    }
+
+    fn start_transaction(
+        &self,
+        client_id: &str,
+        process_type: &str,
+        process_data: &str,
+    ) -> Result<TseStart, String> {
+        let mut inner = self.lock()?;
+        let transaction_number = inner.state.transaction_number + 1;
+        inner.state.transaction_number = transaction_number;
+        let (_, start_time, _) = self.sign(
+            &mut inner,
+            "StartTransaction",
+            client_id,
+            transaction_number,
+            process_type,
+            process_data,
+        )?;
+        inner.open.insert(
+            transaction_number,
+            OpenTransaction {
+                client_id: client_id.to_string(),
+                start_time: start_time.clone(),
+            },
+        );
+        Ok(TseStart {
+            transaction_number,
+            start_time,
+        })
+    }
+
+    fn update_transaction(
+        &self,
+        client_id: &str,
+        transaction_number: u64,
+        process_type: &str,
+        process_data: &str,
+    ) -> Result<(), String> {
+        let mut inner = self.lock()?;
+        let open = inner
+            .open
+            .get(&transaction_number)
+            .ok_or_else(|| format!("TSE-Transaktion {} ist nicht offen", transaction_number))?;
+        if open.client_id != client_id {
+            return Err("TSE-Transaktion gehört zu einer anderen Kasse".into());
+        }
+        self.sign(
+            &mut inner,
+            "UpdateTransaction",
+            client_id,
+            transaction_number,
+            process_type,
+            process_data,
+        )?;
+        Ok(())
+    }
+
+    fn finish_transaction(
+        &self,
+        client_id: &str,
+        transaction_number: u64,
+        process_type: &str,
+        process_data: &str,
+    ) -> Result<TseSignature, String> {
+        let mut inner = self.lock()?;
+        let open = inner
+            .open
+            .remove(&transaction_number)
+            .ok_or_else(|| format!("TSE-Transaktion {} ist nicht offen", transaction_number))?;
+        if open.client_id != client_id {
+            inner.open.insert(transaction_number, open);
+            return Err("TSE-Transaktion gehört zu einer anderen Kasse".into());
+        }
+        let (signature_counter, finish_time, signature) = self.sign(
+            &mut inner,
+            "FinishTransaction",
+            client_id,
+            transaction_number,
+            process_type,
+            process_data,
+        )?;
+        Ok(TseSignature {
+            transaction_number,
+            signature_counter,
+            start_time: open.start_time,
+            finish_time,
+            process_type: process_type.to_string(),
+            process_data: process_data.to_string(),
+            signature,
+        })
+    }
+
+    fn export(&self, target_dir: &Path) -> Result<PathBuf, String> {
+        let _inner = self.lock()?;
+        fs::create_dir_all(target_dir).map_err(|e| e.to_string())?;
+        let path = target_dir.join(format!("tse-export-{}.log", &self.serial_number[..16]));
+        if self.log_path.exists() {
+            fs::copy(&self.log_path, &path).map_err(|e| e.to_string())?;
+        } else {
+            fs::write(&path, "").map_err(|e| e.to_string())?;
+        }
+        Ok(path)
+    }
+}
+
+fn write_state(path: &Path, state: &SimulatorState) -> Result<(), String> {
+    let raw = serde_json::to_string_pretty(state).map_err(|e| e.to_string())?;
+    fs::write(path, raw).map_err(|e| e.to_string())
+}
+
+fn utc_now() -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let secs = now.as_secs() as i64;
+    let (days, rem) = (secs.div_euclid(86_400), secs.rem_euclid(86_400));
+    let (year, month, day) = calendar::civil_from_days(days);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        rem / 3_600,
+        rem % 3_600 / 60,
+        rem % 60,
+        now.subsec_millis()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use p256::ecdsa::{signature::Verifier, VerifyingKey};
+    use rand_core::RngCore;
+
+    fn temp_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("tse-simulator-{}", OsRng.next_u64()))
+    }
+
+    #[test]
+    fn signature_counter_increases_monotonically() {
+        let dir = temp_dir();
+        let tse = SimulatorTse::open(&dir).unwrap();
+        let mut last = tse.info().unwrap().signature_counter;
+        for _ in 0..3 {
+            let start = tse
+                .start_transaction("KASSE-1", PROCESS_TYPE_RECEIPT, "")
+                .unwrap();
+            tse.update_transaction(
+                "KASSE-1",
+                start.transaction_number,
+                PROCESS_TYPE_RECEIPT,
+                "Beleg",
+            )
+            .unwrap();
+            let signature = tse
+                .finish_transaction(
+                    "KASSE-1",
+                    start.transaction_number,
+                    PROCESS_TYPE_RECEIPT,
+                    "Beleg",
+                )
+                .unwrap();
+            assert_eq!(signature.signature_counter, last + 3);
+            last = signature.signature_counter;
+        }
+        drop(tse);
+
+        let reopened = SimulatorTse::open(&dir).unwrap();
+        assert_eq!(reopened.info().unwrap().signature_counter, last);
+        let start = reopened
+            .start_transaction("KASSE-1", PROCESS_TYPE_RECEIPT, "")
+            .unwrap();
+        let signature = reopened
+            .finish_transaction(
+                "KASSE-1",
+                start.transaction_number,
+                PROCESS_TYPE_RECEIPT,
+                "",
+            )
+            .unwrap();
+        assert_eq!(signature.signature_counter, last + 2);
+        fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn signature_verifies_against_public_key() {
+        let dir = temp_dir();
+        let tse = SimulatorTse::open(&dir).unwrap();
+        let info = tse.info().unwrap();
+        let start = tse
+            .start_transaction("KASSE-1", PROCESS_TYPE_RECEIPT, "")
+            .unwrap();
+        let signed = tse
+            .finish_transaction(
+                "KASSE-1",
+                start.transaction_number,
+                PROCESS_TYPE_RECEIPT,
+                "Beleg^7.60_0.00_0.00_0.00_0.00^7.60:Bar",
+            )
+            .unwrap();
+
+        let key =
+            VerifyingKey::from_sec1_bytes(&STANDARD.decode(&info.public_key).unwrap()).unwrap();
+        let signature =
+            Signature::from_slice(&STANDARD.decode(&signed.signature).unwrap()).unwrap();
+        let message = format!(
+            "{}|FinishTransaction|KASSE-1|{}|{}|{}|{}|{}",
+            info.serial_number,
+            signed.transaction_number,
+            signed.signature_counter,
+            signed.finish_time,
+            signed.process_type,
+            signed.process_data
+        );
+        assert!(key.verify(message.as_bytes(), &signature).is_ok());
+        let tampered = message.replace("7.60:Bar", "0.60:Bar");
+        assert!(key.verify(tampered.as_bytes(), &signature).is_err());
+        fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn foreign_client_id_is_rejected() {
+        let dir = temp_dir();
+        let tse = SimulatorTse::open(&dir).unwrap();
+        let start = tse
+            .start_transaction("KASSE-1", PROCESS_TYPE_RECEIPT, "")
+            .unwrap();
+        let counter = tse.info().unwrap().signature_counter;
+        assert!(tse
+            .update_transaction(
+                "KASSE-2",
+                start.transaction_number,
+                PROCESS_TYPE_RECEIPT,
+                ""
+            )
+            .is_err());
+        assert!(tse
+            .finish_transaction(
+                "KASSE-2",
+                start.transaction_number,
+                PROCESS_TYPE_RECEIPT,
+                ""
+            )
+            .is_err());
+        assert_eq!(tse.info().unwrap().signature_counter, counter);
+        // The transaction stays open for the client that started it.
+        assert!(tse
+            .finish_transaction(
+                "KASSE-1",
+                start.transaction_number,
+                PROCESS_TYPE_RECEIPT,
+                ""
+            )
+            .is_ok());
+        fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn utc_now_uses_iso_format() {
+        let now = utc_now();
+        assert_eq!(now.len(), 24);
+        assert!(now.ends_with('Z'));
+        assert_eq!(calendar::civil_from_days(0), (1970, 1, 1));
+        assert_eq!(calendar::days_from_civil(2024, 2, 29), 19_782);
+        assert_eq!(calendar::civil_from_days(19_782), (2024, 2, 29));
+    }
+}
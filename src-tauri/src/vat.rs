@@ -0,0 +1,49 @@
+/// DSFinV-K key for amounts outside the scope of VAT, such as balance top-ups.
+pub const VAT_KEY_NOT_TAXABLE: i64 = 5;
+
+/// DSFinV-K VAT key of a booked line. Lines without a key cannot be attributed to
+/// a rate, so they are refused instead of being guessed.
+pub fn line_vat_key(vat_key: Option<i64>) -> Result<i64, String> {
+    match vat_key {
+        Some(key @ 1..=7) => Ok(key),
+        Some(key) => Err(format!("Unbekannter Steuerschlüssel {}.", key)),
+        None => Err("Position ohne Steuerschlüssel.".into()),
+    }
+}
+
+/// Position of the line's VAT rate in the gross amounts of a TSE receipt:
+/// Normal, Ermäßigt, Durchschnittssatz 10,7 %, Durchschnittssatz 5,5 %, Null.
+pub fn tse_slot(vat_key: Option<i64>) -> Result<usize, String> {
+    match line_vat_key(vat_key)? {
+        1 => Ok(0),
+        2 => Ok(1),
+        3 => Ok(2),
+        4 => Ok(3),
+        5 | 6 => Ok(4),
+        _ => Err("Umsatzsteuer nicht ermittelbar.".into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn known_keys_map_to_tse_slots() {
+        assert_eq!(tse_slot(Some(1)), Ok(0));
+        assert_eq!(tse_slot(Some(2)), Ok(1));
+        assert_eq!(tse_slot(Some(4)), Ok(3));
+        assert_eq!(tse_slot(Some(VAT_KEY_NOT_TAXABLE)), Ok(4));
+        assert_eq!(tse_slot(Some(6)), Ok(4));
+    }
+
+    #[test]
+    fn unknown_keys_are_refused() {
+        assert!(line_vat_key(None).is_err());
+        assert!(line_vat_key(Some(0)).is_err());
+        assert!(line_vat_key(Some(8)).is_err());
+        assert_eq!(line_vat_key(Some(7)), Ok(7));
+        assert!(tse_slot(None).is_err());
+        assert!(tse_slot(Some(7)).is_err());
+    }
+}
@@ -0,0 +1,87 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use rusqlite::{Connection, OptionalExtension};
+
+pub const ENFORCE_OFF: &str = "off";
+pub const ENFORCE_WARN: &str = "warn";
+pub const ENFORCE_REFUSE: &str = "refuse";
+
+const MAX_SIGNATURE_BYTES: usize = 512 * 1024;
+
+pub struct Signature {
+    pub mime: String,
+    pub data: Vec<u8>,
+}
+
+/// Accepts a data URL as produced by a signature pad canvas or plain base64 PNG.
+pub fn decode_signature(value: &str) -> Result<Signature, String> {
+    let value = value.trim();
+    let (mime, encoded) = match value.strip_prefix("data:") {
+        Some(rest) => {
+            let (header, encoded) = rest
+                .split_once(',')
+                .ok_or("Unterschrift ist keine gültige Data-URL")?;
+            let mime = header
+                .strip_suffix(";base64")
+                .ok_or("Unterschrift muss base64-kodiert sein")?;
+            (mime.to_string(), encoded)
+        }
+        None => ("image/png".to_string(), value),
+    };
+    if !matches!(mime.as_str(), "image/png" | "image/jpeg" | "image/svg+xml") {
+        return Err(format!("Bildformat {} wird nicht unterstützt", mime));
+    }
+    let data = STANDARD
+        .decode(encoded)
+        .map_err(|_| "Unterschrift konnte nicht gelesen werden".to_string())?;
+    if data.is_empty() {
+        return Err("Unterschrift ist leer".into());
+    }
+    if data.len() > MAX_SIGNATURE_BYTES {
+        return Err("Unterschrift ist zu groß (höchstens 512 KB)".into());
+    }
+    Ok(Signature { mime, data })
+}
+
+pub fn encode_signature(mime: &str, data: &[u8]) -> String {
+    format!("data:{};base64,{}", mime, STANDARD.encode(data))
+}
+
+/// The most recently published waiver text is the one members have to sign.
+pub fn current_text(conn: &Connection) -> rusqlite::Result<Option<(i64, String)>> {
+    conn.query_row(
+        "SELECT id, version FROM waiver_texts ORDER BY id DESC LIMIT 1",
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+    .optional()
+}
+
+/// Returns why the member has no valid waiver, or `None` if the current version is signed.
+pub fn issue(conn: &Connection, member_id: i64) -> rusqlite::Result<Option<String>> {
+    let Some((text_id, version)) = current_text(conn)? else {
+        return Ok(None);
+    };
+    let signed: Option<String> = conn
+        .query_row(
+            "SELECT t.version
+            FROM waivers w
+            JOIN waiver_texts t ON t.id = w.waiver_text_id
+            WHERE w.member_id = ?
+            ORDER BY w.waiver_text_id = ? DESC, w.waiver_text_id DESC
+            LIMIT 1",
+            (member_id, text_id),
+            |row| row.get(0),
+        )
+        .optional()?;
+    Ok(match signed {
+        None => Some(format!(
+            "Kein Haftungsausschluss unterschrieben (aktuell: {})",
+            version
+        )),
+        Some(signed) if signed != version => Some(format!(
+            "Haftungsausschluss veraltet: unterschrieben {}, aktuell {}",
+            signed, version
+        )),
+        Some(_) => None,
+    })
+}